@@ -1,3 +1,4 @@
+use super::Icon;
 use super::blocks::Block;
 use super::font::{DrawingSurface, Font};
 use crate::Config;
@@ -8,6 +9,18 @@ use x11rb::connection::Connection;
 use x11rb::protocol::xproto::*;
 use x11rb::rust_connection::RustConnection;
 
+/// What each tag looks like in the bar, as masks over the tag indices.
+#[derive(Debug, Clone, Copy)]
+pub struct TagState<'a> {
+    pub current: u32,
+    pub occupied: u32,
+    pub urgent: u32,
+    /// Shared tags currently shown on another monitor.
+    pub elsewhere: u32,
+    /// Icons of the clients on each tag, drawn after its label.
+    pub icons: &'a [Vec<&'a Icon>],
+}
+
 pub struct Bar {
     window: Window,
     width: u16,
@@ -16,7 +29,11 @@ pub struct Bar {
     surface: DrawingSurface,
 
     tag_widths: Vec<u16>,
+    /// Extra width each tag took for client icons on the last draw.
+    tag_icon_widths: Vec<u16>,
     needs_redraw: bool,
+    /// Space at the right edge covered by the system tray.
+    tray_width: u16,
 
     blocks: Vec<Box<dyn Block>>,
     block_last_updates: Vec<Instant>,
@@ -31,6 +48,8 @@ pub struct Bar {
     hide_vacant_tags: bool,
     last_occupied_tags: u32,
     last_current_tags: u32,
+    /// Shared tags currently shown on another monitor.
+    last_elsewhere_tags: u32,
 }
 
 impl Bar {
@@ -48,7 +67,7 @@ impl Bar {
         let window = connection.generate_id()?;
         let graphics_context = connection.generate_id()?;
 
-        let height = (font.height() as f32 * 1.4) as u16;
+        let height = Self::height_for(font);
 
         connection.create_window(
             COPY_DEPTH_FROM_PARENT,
@@ -121,8 +140,10 @@ impl Bar {
             height,
             graphics_context,
             surface,
+            tag_icon_widths: vec![0; config.tags.len()],
             tag_widths,
             needs_redraw: true,
+            tray_width: 0,
             blocks,
             block_last_updates,
             block_underlines,
@@ -135,6 +156,7 @@ impl Bar {
             hide_vacant_tags: config.hide_vacant_tags,
             last_occupied_tags: 0,
             last_current_tags: 0,
+            last_elsewhere_tags: 0,
         })
     }
 
@@ -142,10 +164,75 @@ impl Bar {
         self.window
     }
 
+    pub fn width(&self) -> u16 {
+        self.width
+    }
+
     pub fn height(&self) -> u16 {
         self.height
     }
 
+    pub fn set_tray_width(&mut self, width: u16) {
+        if self.tray_width != width {
+            self.tray_width = width;
+            self.needs_redraw = true;
+        }
+    }
+
+    /// Height of a bar drawn with `font`.
+    pub fn height_for(font: &Font) -> u16 {
+        (font.height() as f32 * 1.4) as u16
+    }
+
+    /// Size window icons are scaled to for a bar drawn with `font`.
+    pub fn icon_size_for(font: &Font) -> u16 {
+        Self::height_for(font) * 3 / 4
+    }
+
+    /// Moves the bar to a monitor's new geometry after an output change.
+    pub(crate) fn reposition(
+        &mut self,
+        connection: &RustConnection,
+        display: *mut x11::xlib::Display,
+        x: i16,
+        y: i16,
+        width: u16,
+    ) -> Result<(), X11Error> {
+        connection.configure_window(
+            self.window,
+            &ConfigureWindowAux::new()
+                .x(x as i32)
+                .y(y as i32)
+                .width(width as u32),
+        )?;
+
+        if width != self.width {
+            self.width = width;
+            let visual = unsafe { x11::xlib::XDefaultVisual(display, 0) };
+            let colormap = unsafe { x11::xlib::XDefaultColormap(display, 0) };
+            self.surface = DrawingSurface::new(
+                display,
+                self.window as x11::xlib::Drawable,
+                width as u32,
+                self.height as u32,
+                visual,
+                colormap,
+            )?;
+        }
+
+        self.needs_redraw = true;
+        connection.flush()?;
+        Ok(())
+    }
+
+    /// Destroys the bar's window when its monitor goes away.
+    pub fn destroy(&self, connection: &RustConnection) -> Result<(), X11Error> {
+        connection.free_gc(self.graphics_context)?;
+        connection.destroy_window(self.window)?;
+        connection.flush()?;
+        Ok(())
+    }
+
     pub fn invalidate(&mut self) {
         self.needs_redraw = true;
     }
@@ -180,9 +267,7 @@ impl Bar {
         connection: &RustConnection,
         font: &Font,
         display: *mut x11::xlib::Display,
-        current_tags: u32,
-        occupied_tags: u32,
-        urgent_tags: u32,
+        tags: &TagState,
         draw_blocks: bool,
         layout_symbol: &str,
         keychord_indicator: Option<&str>,
@@ -212,22 +297,32 @@ impl Bar {
             x11::xlib::XFreeGC(display, gc);
         }
 
-        self.last_occupied_tags = occupied_tags;
-        self.last_current_tags = current_tags;
+        self.last_occupied_tags = tags.occupied;
+        self.last_current_tags = tags.current;
+        self.last_elsewhere_tags = tags.elsewhere;
 
         let mut x_position: i16 = 0;
 
         for (tag_index, tag) in self.tags.iter().enumerate() {
             let tag_mask = 1 << tag_index;
-            let is_selected = (current_tags & tag_mask) != 0;
-            let is_occupied = (occupied_tags & tag_mask) != 0;
-            let is_urgent = (urgent_tags & tag_mask) != 0;
+            let is_selected = (tags.current & tag_mask) != 0;
+            let is_occupied = (tags.occupied & tag_mask) != 0;
+            let is_urgent = (tags.urgent & tag_mask) != 0;
+            let is_elsewhere = (tags.elsewhere & tag_mask) != 0;
 
-            if self.hide_vacant_tags && !is_occupied && !is_selected {
+            if self.hide_vacant_tags && !is_occupied && !is_selected && !is_elsewhere {
                 continue;
             }
 
-            let tag_width = self.tag_widths[tag_index];
+            let icons = tags.icons.get(tag_index).map(Vec::as_slice).unwrap_or(&[]);
+            let icon_spacing = 4;
+            let icon_width = icons
+                .iter()
+                .map(|icon| icon.size() + icon_spacing)
+                .sum::<u16>();
+            self.tag_icon_widths[tag_index] = icon_width;
+            let label_width = self.tag_widths[tag_index];
+            let tag_width = label_width + icon_width;
 
             let scheme = if is_selected {
                 &self.scheme_selected
@@ -239,8 +334,19 @@ impl Bar {
                 &self.scheme_normal
             };
 
+            if scheme.background != self.scheme_normal.background {
+                self.fill_rectangle(
+                    display,
+                    x_position,
+                    0,
+                    tag_width,
+                    self.height,
+                    scheme.background,
+                );
+            }
+
             let text_width = font.text_width(tag);
-            let text_x = x_position + ((tag_width - text_width) / 2) as i16;
+            let text_x = x_position + ((label_width - text_width) / 2) as i16;
 
             let top_padding = 4;
             let text_y = top_padding + font.ascent();
@@ -249,9 +355,25 @@ impl Bar {
                 .font_draw()
                 .draw_text(font, scheme.foreground, text_x, text_y, tag);
 
-            if is_selected || is_urgent {
-                let font_height = font.height();
-                let underline_height = font_height / 8;
+            let mut icon_x = text_x + text_width as i16;
+            for icon in icons {
+                icon_x += icon_spacing as i16;
+                let icon_y = (self.height.saturating_sub(icon.size()) / 2) as i16;
+                icon.draw(self.surface.pixmap(), icon_x, icon_y, scheme.background);
+                icon_x += icon.size() as i16;
+            }
+
+            // A thinner underline in the selected colour marks a tag that is
+            // on screen, just on another monitor.
+            let underline = if is_selected || is_urgent {
+                Some((font.height() / 8, scheme.underline))
+            } else if is_elsewhere {
+                Some(((font.height() / 16).max(1), self.scheme_selected.underline))
+            } else {
+                None
+            };
+
+            if let Some((underline_height, underline_color)) = underline {
                 let bottom_gap = 3;
                 let underline_y = self.height as i16 - underline_height as i16 - bottom_gap;
 
@@ -259,20 +381,14 @@ impl Bar {
                 let underline_width = tag_width - underline_padding;
                 let underline_x = x_position + (underline_padding / 2) as i16;
 
-                unsafe {
-                    let gc = x11::xlib::XCreateGC(display, self.surface.pixmap(), 0, std::ptr::null_mut());
-                    x11::xlib::XSetForeground(display, gc, scheme.underline as u64);
-                    x11::xlib::XFillRectangle(
-                        display,
-                        self.surface.pixmap(),
-                        gc,
-                        underline_x as i32,
-                        underline_y as i32,
-                        underline_width as u32,
-                        underline_height as u32,
-                    );
-                    x11::xlib::XFreeGC(display, gc);
-                }
+                self.fill_rectangle(
+                    display,
+                    underline_x,
+                    underline_y,
+                    underline_width,
+                    underline_height,
+                    underline_color,
+                );
             }
 
             x_position += tag_width as i16;
@@ -311,7 +427,7 @@ impl Bar {
 
         if draw_blocks && !self.status_text.is_empty() {
             let padding = 10;
-            let mut x_position = self.width as i16 - padding;
+            let mut x_position = self.width as i16 - self.tray_width as i16 - padding;
 
             for (i, block) in self.blocks.iter_mut().enumerate().rev() {
                 if let Ok(text) = block.content() {
@@ -383,15 +499,46 @@ impl Bar {
         Ok(())
     }
 
+    fn fill_rectangle(
+        &self,
+        display: *mut x11::xlib::Display,
+        x: i16,
+        y: i16,
+        width: u16,
+        height: u16,
+        color: u32,
+    ) {
+        unsafe {
+            let gc = x11::xlib::XCreateGC(display, self.surface.pixmap(), 0, std::ptr::null_mut());
+            x11::xlib::XSetForeground(display, gc, color as u64);
+            x11::xlib::XFillRectangle(
+                display,
+                self.surface.pixmap(),
+                gc,
+                x as i32,
+                y as i32,
+                width as u32,
+                height as u32,
+            );
+            x11::xlib::XFreeGC(display, gc);
+        }
+    }
+
     pub fn handle_click(&self, click_x: i16) -> Option<usize> {
+        if click_x >= self.width.saturating_sub(self.tray_width) as i16 {
+            return None;
+        }
+
         let mut current_x_position = 0;
 
-        for (tag_index, &tag_width) in self.tag_widths.iter().enumerate() {
+        for (tag_index, &label_width) in self.tag_widths.iter().enumerate() {
+            let tag_width = label_width + self.tag_icon_widths.get(tag_index).copied().unwrap_or(0);
             let tag_mask = 1 << tag_index;
             let is_selected = (self.last_current_tags & tag_mask) != 0;
             let is_occupied = (self.last_occupied_tags & tag_mask) != 0;
+            let is_elsewhere = (self.last_elsewhere_tags & tag_mask) != 0;
 
-            if self.hide_vacant_tags && !is_occupied && !is_selected {
+            if self.hide_vacant_tags && !is_occupied && !is_selected && !is_elsewhere {
                 continue;
             }
 
@@ -423,6 +570,7 @@ impl Bar {
         self.block_last_updates = vec![Instant::now(); self.blocks.len()];
 
         self.tags = config.tags.clone();
+        self.tag_icon_widths = vec![0; self.tags.len()];
         self.scheme_normal = config.scheme_normal;
         self.scheme_occupied = config.scheme_occupied;
         self.scheme_selected = config.scheme_selected;
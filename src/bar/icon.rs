@@ -0,0 +1,199 @@
+use std::cell::RefCell;
+use x11::xlib::{Display, Drawable, GC, Pixmap};
+
+/// A window icon taken from `_NET_WM_ICON`, scaled once to fit a `size` x
+/// `size` square and kept as non-premultiplied ARGB.
+///
+/// The first draw against a given background blends the icon into a server
+/// side pixmap, so redraws only copy it. The pixmaps are freed on drop.
+#[derive(Debug)]
+pub struct Icon {
+    display: *mut Display,
+    size: u16,
+    width: u16,
+    height: u16,
+    pixels: Vec<u32>,
+    gc: RefCell<Option<GC>>,
+    pixmaps: RefCell<Vec<(u32, Pixmap)>>,
+}
+
+impl Icon {
+    /// Picks the image in a `_NET_WM_ICON` value closest to `size`, preferring
+    /// one at least that large so it only ever gets scaled down.
+    pub(crate) fn from_net_wm_icon(display: *mut Display, data: &[u32], size: u16) -> Option<Self> {
+        if size == 0 {
+            return None;
+        }
+
+        let mut best: Option<(u32, u32, &[u32])> = None;
+        let mut rest = data;
+        while let [width, height, tail @ ..] = rest {
+            let (width, height) = (*width, *height);
+            let Some(len) = width.checked_mul(height).map(|len| len as usize) else {
+                break;
+            };
+            if width == 0
+                || height == 0
+                || width > u16::MAX as u32
+                || height > u16::MAX as u32
+                || tail.len() < len
+            {
+                break;
+            }
+            let (pixels, next) = tail.split_at(len);
+            rest = next;
+
+            let edge = width.max(height);
+            let better = match best {
+                None => true,
+                Some((best_width, best_height, _)) => {
+                    let best_edge = best_width.max(best_height);
+                    let target = size as u32;
+                    match (edge >= target, best_edge >= target) {
+                        (true, true) => edge < best_edge,
+                        (true, false) => true,
+                        (false, true) => false,
+                        (false, false) => edge > best_edge,
+                    }
+                }
+            };
+            if better {
+                best = Some((width, height, pixels));
+            }
+        }
+
+        let (source_width, source_height, source) = best?;
+
+        let scale = size as f32 / source_width.max(source_height) as f32;
+        let width = ((source_width as f32 * scale).round() as u32).clamp(1, size as u32);
+        let height = ((source_height as f32 * scale).round() as u32).clamp(1, size as u32);
+
+        let mut pixels = Vec::with_capacity((width * height) as usize);
+        for row in 0..height {
+            let source_row = (row * source_height / height) as usize;
+            for column in 0..width {
+                let source_column = (column * source_width / width) as usize;
+                pixels.push(source[source_row * source_width as usize + source_column]);
+            }
+        }
+
+        Some(Icon {
+            display,
+            size,
+            width: width as u16,
+            height: height as u16,
+            pixels,
+            gc: RefCell::new(None),
+            pixmaps: RefCell::new(Vec::new()),
+        })
+    }
+
+    /// Edge length of the square the icon was scaled to fit.
+    pub fn size(&self) -> u16 {
+        self.size
+    }
+
+    /// Draws the icon centred in a `size()` square at (`x`, `y`), with
+    /// transparent pixels blended against `background`.
+    pub(crate) fn draw(&self, drawable: Drawable, x: i16, y: i16, background: u32) {
+        let Some(pixmap) = self.pixmap(drawable, background) else {
+            return;
+        };
+        let Some(gc) = *self.gc.borrow() else {
+            return;
+        };
+
+        let offset_x = x as i32 + (self.size - self.width) as i32 / 2;
+        let offset_y = y as i32 + (self.size - self.height) as i32 / 2;
+
+        unsafe {
+            x11::xlib::XCopyArea(
+                self.display,
+                pixmap,
+                drawable,
+                gc,
+                0,
+                0,
+                self.width as u32,
+                self.height as u32,
+                offset_x,
+                offset_y,
+            );
+        }
+    }
+
+    /// The icon blended against `background`, uploading it on first use.
+    fn pixmap(&self, drawable: Drawable, background: u32) -> Option<Pixmap> {
+        if let Some(&(_, pixmap)) = self
+            .pixmaps
+            .borrow()
+            .iter()
+            .find(|(color, _)| *color == background)
+        {
+            return Some(pixmap);
+        }
+
+        let (width, height) = (self.width as u32, self.height as u32);
+        let mut data: Vec<u32> = self
+            .pixels
+            .iter()
+            .map(|&pixel| blend(pixel, background))
+            .collect();
+
+        unsafe {
+            let screen = x11::xlib::XDefaultScreen(self.display);
+            let depth = x11::xlib::XDefaultDepth(self.display, screen) as u32;
+            let image = x11::xlib::XCreateImage(
+                self.display,
+                x11::xlib::XDefaultVisual(self.display, screen),
+                depth,
+                x11::xlib::ZPixmap,
+                0,
+                data.as_mut_ptr() as *mut std::os::raw::c_char,
+                width,
+                height,
+                32,
+                0,
+            );
+            if image.is_null() {
+                return None;
+            }
+
+            let pixmap = x11::xlib::XCreatePixmap(self.display, drawable, width, height, depth);
+            let gc = *self.gc.borrow_mut().get_or_insert_with(|| {
+                x11::xlib::XCreateGC(self.display, pixmap, 0, std::ptr::null_mut())
+            });
+            x11::xlib::XPutImage(self.display, pixmap, gc, image, 0, 0, 0, 0, width, height);
+
+            // The pixel buffer belongs to `data`; only free the XImage itself.
+            (*image).data = std::ptr::null_mut();
+            x11::xlib::XDestroyImage(image);
+
+            self.pixmaps.borrow_mut().push((background, pixmap));
+            Some(pixmap)
+        }
+    }
+}
+
+impl Drop for Icon {
+    fn drop(&mut self) {
+        unsafe {
+            for &(_, pixmap) in self.pixmaps.get_mut().iter() {
+                x11::xlib::XFreePixmap(self.display, pixmap);
+            }
+            if let Some(gc) = self.gc.get_mut().take() {
+                x11::xlib::XFreeGC(self.display, gc);
+            }
+        }
+    }
+}
+
+fn blend(pixel: u32, background: u32) -> u32 {
+    let alpha = pixel >> 24;
+    let channel = |shift: u32| {
+        let foreground = (pixel >> shift) & 0xff;
+        let back = (background >> shift) & 0xff;
+        ((foreground * alpha + back * (255 - alpha)) / 255) << shift
+    };
+    channel(16) | channel(8) | channel(0)
+}
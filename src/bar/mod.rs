@@ -1,9 +1,13 @@
 mod bar;
 mod blocks;
 pub mod font;
+mod icon;
+mod tray;
 
-pub use bar::Bar;
+pub use bar::{Bar, TagState};
 pub use blocks::{BlockCommand, BlockConfig};
+pub use icon::Icon;
+pub use tray::Systray;
 
 // Bar position (for future use)
 #[derive(Debug, Clone, Copy)]
@@ -0,0 +1,307 @@
+use crate::errors::X11Error;
+use x11rb::connection::Connection;
+use x11rb::protocol::xproto::*;
+use x11rb::rust_connection::RustConnection;
+use x11rb::wrapper::ConnectionExt as _;
+use x11rb::{COPY_DEPTH_FROM_PARENT, CURRENT_TIME, NONE};
+
+const SYSTEM_TRAY_REQUEST_DOCK: u32 = 0;
+const XEMBED_EMBEDDED_NOTIFY: u32 = 0;
+const XEMBED_MAPPED: u32 = 1 << 0;
+/// The only XEmbed protocol version there is.
+const XEMBED_VERSION: u32 = 0;
+const ICON_SPACING: u16 = 2;
+
+struct TrayAtoms {
+    selection: Atom,
+    opcode: Atom,
+    orientation: Atom,
+    manager: Atom,
+    xembed: Atom,
+    xembed_info: Atom,
+}
+
+struct TrayIcon {
+    window: Window,
+    mapped: bool,
+}
+
+/// A freedesktop.org system tray docked into the right edge of one bar.
+///
+/// The tray window is a child of the bar window, so it is hidden and shown
+/// together with the bar and only needs positioning along the bar's width.
+pub struct Systray {
+    window: Window,
+    monitor_index: usize,
+    icons: Vec<TrayIcon>,
+    icon_size: u16,
+    width: u16,
+    background: u32,
+    atoms: TrayAtoms,
+}
+
+impl Systray {
+    /// Creates the tray inside `bar_window` and takes the
+    /// `_NET_SYSTEM_TRAY_S<n>` selection. Returns `None` if another tray
+    /// already owns it.
+    pub fn new(
+        connection: &RustConnection,
+        screen: &Screen,
+        screen_num: usize,
+        bar_window: Window,
+        monitor_index: usize,
+        bar_height: u16,
+        background: u32,
+    ) -> Result<Option<Self>, X11Error> {
+        let intern = |name: &str| -> Result<Atom, X11Error> {
+            Ok(connection
+                .intern_atom(false, name.as_bytes())?
+                .reply()?
+                .atom)
+        };
+        let atoms = TrayAtoms {
+            selection: intern(&format!("_NET_SYSTEM_TRAY_S{}", screen_num))?,
+            opcode: intern("_NET_SYSTEM_TRAY_OPCODE")?,
+            orientation: intern("_NET_SYSTEM_TRAY_ORIENTATION")?,
+            manager: intern("MANAGER")?,
+            xembed: intern("_XEMBED")?,
+            xembed_info: intern("_XEMBED_INFO")?,
+        };
+
+        let owner = connection
+            .get_selection_owner(atoms.selection)?
+            .reply()?
+            .owner;
+        if owner != NONE {
+            eprintln!("Another system tray is already running, not starting ours");
+            return Ok(None);
+        }
+
+        let window = connection.generate_id()?;
+        connection.create_window(
+            COPY_DEPTH_FROM_PARENT,
+            window,
+            bar_window,
+            0,
+            0,
+            1,
+            bar_height,
+            0,
+            WindowClass::INPUT_OUTPUT,
+            screen.root_visual,
+            &CreateWindowAux::new().background_pixel(background),
+        )?;
+
+        // Horizontal orientation.
+        connection.change_property32(
+            PropMode::REPLACE,
+            window,
+            atoms.orientation,
+            AtomEnum::CARDINAL,
+            &[0],
+        )?;
+
+        connection.set_selection_owner(window, atoms.selection, CURRENT_TIME)?;
+        if connection
+            .get_selection_owner(atoms.selection)?
+            .reply()?
+            .owner
+            != window
+        {
+            connection.destroy_window(window)?;
+            eprintln!("Failed to acquire the system tray selection");
+            return Ok(None);
+        }
+
+        let announce = ClientMessageEvent::new(
+            32,
+            screen.root,
+            atoms.manager,
+            [CURRENT_TIME, atoms.selection, window, 0, 0],
+        );
+        connection.send_event(false, screen.root, EventMask::STRUCTURE_NOTIFY, announce)?;
+        connection.flush()?;
+
+        Ok(Some(Systray {
+            window,
+            monitor_index,
+            icons: Vec::new(),
+            icon_size: bar_height,
+            width: 0,
+            background,
+            atoms,
+        }))
+    }
+
+    pub fn window(&self) -> Window {
+        self.window
+    }
+
+    pub fn monitor_index(&self) -> usize {
+        self.monitor_index
+    }
+
+    /// Width currently taken from the right edge of the bar.
+    pub fn width(&self) -> u16 {
+        self.width
+    }
+
+    pub fn contains(&self, window: Window) -> bool {
+        self.icons.iter().any(|icon| icon.window == window)
+    }
+
+    pub fn is_opcode_message(&self, event: &ClientMessageEvent) -> bool {
+        event.type_ == self.atoms.opcode
+    }
+
+    pub fn is_xembed_info(&self, atom: Atom) -> bool {
+        atom == self.atoms.xembed_info
+    }
+
+    /// Handles a `_NET_SYSTEM_TRAY_OPCODE` message. Returns whether an icon
+    /// was docked.
+    pub fn handle_opcode(
+        &mut self,
+        connection: &RustConnection,
+        event: &ClientMessageEvent,
+    ) -> Result<bool, X11Error> {
+        let data = event.data.as_data32();
+        if data[1] != SYSTEM_TRAY_REQUEST_DOCK {
+            return Ok(false);
+        }
+        let icon = data[2];
+        if icon == NONE || self.contains(icon) {
+            return Ok(false);
+        }
+
+        connection.change_window_attributes(
+            icon,
+            &ChangeWindowAttributesAux::new()
+                .event_mask(
+                    EventMask::STRUCTURE_NOTIFY
+                        | EventMask::PROPERTY_CHANGE
+                        | EventMask::RESIZE_REDIRECT,
+                )
+                .background_pixel(self.background),
+        )?;
+        // Keeps the icon alive if we exit without handing it back.
+        connection.change_save_set(SetMode::INSERT, icon)?;
+        connection.reparent_window(icon, self.window, 0, 0)?;
+
+        let mapped = self.is_mapped(connection, icon);
+        let notify = ClientMessageEvent::new(
+            32,
+            icon,
+            self.atoms.xembed,
+            [
+                CURRENT_TIME,
+                XEMBED_EMBEDDED_NOTIFY,
+                0,
+                self.window,
+                XEMBED_VERSION,
+            ],
+        );
+        connection.send_event(false, icon, EventMask::NO_EVENT, notify)?;
+
+        self.icons.push(TrayIcon {
+            window: icon,
+            mapped,
+        });
+        Ok(true)
+    }
+
+    /// Re-reads `_XEMBED_INFO` after the icon changed it. Returns whether
+    /// the icon was shown or hidden.
+    pub fn update_icon(&mut self, connection: &RustConnection, window: Window) -> bool {
+        let mapped = self.is_mapped(connection, window);
+        match self.icons.iter_mut().find(|icon| icon.window == window) {
+            Some(icon) if icon.mapped != mapped => {
+                icon.mapped = mapped;
+                true
+            }
+            _ => false,
+        }
+    }
+
+    /// Forgets an icon that was destroyed or reparented away.
+    pub fn remove(&mut self, window: Window) -> bool {
+        let count = self.icons.len();
+        self.icons.retain(|icon| icon.window != window);
+        self.icons.len() != count
+    }
+
+    /// Lines the visible icons up at the right end of a bar that is
+    /// `bar_width` wide, and returns the width they take up.
+    pub fn layout(&mut self, connection: &RustConnection, bar_width: u16) -> Result<u16, X11Error> {
+        let size = self.icon_size;
+        let mut x = 0;
+        for icon in &self.icons {
+            if icon.mapped {
+                connection.configure_window(
+                    icon.window,
+                    &ConfigureWindowAux::new()
+                        .x(x as i32)
+                        .y(0)
+                        .width(size as u32)
+                        .height(size as u32),
+                )?;
+                connection.map_window(icon.window)?;
+                x += size + ICON_SPACING;
+            } else {
+                connection.unmap_window(icon.window)?;
+            }
+        }
+
+        self.width = x;
+        if self.width == 0 {
+            connection.unmap_window(self.window)?;
+        } else {
+            connection.configure_window(
+                self.window,
+                &ConfigureWindowAux::new()
+                    .x(bar_width.saturating_sub(self.width) as i32)
+                    .y(0)
+                    .width(self.width as u32)
+                    .height(size as u32)
+                    .stack_mode(StackMode::ABOVE),
+            )?;
+            connection.map_window(self.window)?;
+        }
+        connection.flush()?;
+        Ok(self.width)
+    }
+
+    /// Gives up the selection and hands the icons back to the root window,
+    /// e.g. when another tray takes over.
+    pub fn release(&mut self, connection: &RustConnection, root: Window) -> Result<(), X11Error> {
+        for icon in self.icons.drain(..) {
+            connection.unmap_window(icon.window)?;
+            connection.reparent_window(icon.window, root, 0, 0)?;
+        }
+        connection.destroy_window(self.window)?;
+        connection.flush()?;
+        self.width = 0;
+        Ok(())
+    }
+
+    fn is_mapped(&self, connection: &RustConnection, window: Window) -> bool {
+        let info = connection
+            .get_property(
+                false,
+                window,
+                self.atoms.xembed_info,
+                self.atoms.xembed_info,
+                0,
+                2,
+            )
+            .ok()
+            .and_then(|cookie| cookie.reply().ok())
+            .and_then(|reply| reply.value32().map(|values| values.collect::<Vec<u32>>()));
+
+        match info.as_deref() {
+            Some([_, flags, ..]) => flags & XEMBED_MAPPED != 0,
+            // Plenty of icons never set _XEMBED_INFO; show them anyway.
+            _ => true,
+        }
+    }
+}
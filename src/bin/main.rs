@@ -1,5 +1,8 @@
+use oxwm::config::LuaRuntime;
 use oxwm::errors::ConfigError;
+use oxwm::errors::IpcError;
 use oxwm::errors::MainError;
+use oxwm::ipc;
 use std::path::Path;
 use std::path::PathBuf;
 
@@ -19,9 +22,9 @@ fn main() -> Result<(), MainError> {
         Args::Error(e) => return Err(e),
     };
 
-    let (config, config_warning) = load_config(arguments.get(2))?;
+    let (config, lua, config_warning) = load_config(arguments.get(2))?;
 
-    let mut window_manager = match oxwm::window_manager::WindowManager::new(config) {
+    let mut window_manager = match oxwm::window_manager::WindowManager::new(config, lua) {
         Ok(wm) => wm,
         Err(e) => return Err(MainError::CouldNotStartWm(e)),
     };
@@ -39,7 +42,7 @@ fn main() -> Result<(), MainError> {
 
 fn load_config(
     config_path: Option<&String>,
-) -> Result<(oxwm::Config, Option<ConfigError>), MainError> {
+) -> Result<(oxwm::Config, LuaRuntime, Option<ConfigError>), MainError> {
     let path = match config_path {
         None => {
             let config_dir = get_config_path()?;
@@ -57,19 +60,19 @@ fn load_config(
 
     let config_directory = path.parent();
 
-    let (mut config, config_warning) =
-        match oxwm::config::parse_lua_config(&config_string, config_directory) {
-            Ok(config) => (config, None),
+    let ((mut config, lua), config_warning) =
+        match oxwm::config::load_lua_config(&config_string, config_directory) {
+            Ok(loaded) => (loaded, None),
             Err(warning) => {
-                let config = match oxwm::config::parse_lua_config(TEMPLATE, None) {
-                    Ok(c) => c,
+                let loaded = match oxwm::config::load_lua_config(TEMPLATE, None) {
+                    Ok(loaded) => loaded,
                     Err(e) => return Err(MainError::FailedReadConfigTemplate(e)),
                 };
-                (config, Some(warning))
+                (loaded, Some(warning))
             }
         };
     config.path = Some(path);
-    Ok((config, config_warning))
+    Ok((config, lua, config_warning))
 }
 
 fn init_config() -> Result<(), MainError> {
@@ -101,12 +104,20 @@ fn get_config_path() -> Result<PathBuf, MainError> {
 fn print_help() {
     println!("OXWM - A dynamic window manager written in Rust\n");
     println!("USAGE:");
-    println!("    oxwm [OPTIONS]\n");
+    println!("    oxwm [OPTIONS]");
+    println!("    oxwm msg <COMMAND> [ARGS...]\n");
     println!("OPTIONS:");
     println!("    --init              Create default config in ~/.config/oxwm/config.lua");
     println!("    --config <PATH>     Use custom config file");
+    println!("    --check-config [PATH]");
+    println!("                        Validate a config without starting the WM");
     println!("    --version           Print version information");
     println!("    --help              Print this help message\n");
+    println!("MSG:");
+    println!("    Send a command to the running oxwm, e.g. 'oxwm msg view_tag 2'");
+    println!("    Commands are the keybinding action names, exits non-zero on failure");
+    println!("    'oxwm msg get_tree' prints the monitors, tags and clients as JSON");
+    println!("    'oxwm msg subscribe [EVENTS...]' streams events as JSON lines\n");
     println!("CONFIG:");
     println!("    Location: ~/.config/oxwm/config.lua");
     println!("    Edit the config file and use Mod+Shift+R to reload");
@@ -144,6 +155,18 @@ fn process_args() -> Args {
             Ok(_) => Args::Exit,
             Err(e) => Args::Error(e),
         },
+        "msg" => {
+            let mut command: Vec<String> = path.into_iter().collect();
+            command.extend(args);
+            match send_message(&command) {
+                Ok(_) => Args::Exit,
+                Err(e) => Args::Error(e),
+            }
+        }
+        "--check-config" => match check_config(path) {
+            Ok(_) => Args::Exit,
+            Err(e) => Args::Error(e),
+        },
         "--config" => match check_custom_config(path) {
             Ok(p) => Args::Arguments(vec![name, switch, p]),
             Err(e) => Args::Error(e),
@@ -152,6 +175,100 @@ fn process_args() -> Args {
     }
 }
 
+fn check_config(path: Option<String>) -> Result<(), MainError> {
+    let path = match path {
+        Some(p) => PathBuf::from(p),
+        None => get_config_path()?.join(CONFIG_FILE),
+    };
+
+    let config_string = match std::fs::read_to_string(&path) {
+        Ok(c) => c,
+        Err(e) => return Err(MainError::FailedReadConfig(e)),
+    };
+
+    let report = oxwm::config::check_lua_config(&config_string, &path);
+
+    for warning in &report.warnings {
+        eprintln!("warning: {warning}");
+    }
+    for error in &report.errors {
+        eprintln!("error: {error}");
+    }
+
+    if !report.is_ok() {
+        return Err(MainError::ConfigCheckFailed(report.errors.len()));
+    }
+
+    println!(
+        "✓ {} is valid ({} warning(s))",
+        path.display(),
+        report.warnings.len()
+    );
+    Ok(())
+}
+
+fn send_message(command: &[String]) -> Result<(), MainError> {
+    let Some((name, args)) = command.split_first() else {
+        return Err(MainError::NoCommand);
+    };
+
+    let arg = match args {
+        [] => serde_json::Value::Null,
+        [value] => serde_json::Value::from(value.as_str()),
+        values => serde_json::Value::from(values.to_vec()),
+    };
+    let request = serde_json::json!({ "command": name, "arg": arg }).to_string();
+
+    let socket_path = ipc::socket_path().ok_or(MainError::IpcError(IpcError::NoSocketPath))?;
+
+    if name == "subscribe" {
+        return stream_events(&socket_path, &request);
+    }
+
+    let reply = match ipc::send_request(&socket_path, &request) {
+        Ok(r) => r,
+        Err(e) => return Err(MainError::IpcError(e)),
+    };
+
+    if !reply.success {
+        let message = reply
+            .error
+            .unwrap_or_else(|| String::from("Command failed"));
+        return Err(MainError::CommandFailed(message));
+    }
+
+    match reply.data {
+        Some(data) => match serde_json::to_string_pretty(&data) {
+            Ok(text) => println!("{text}"),
+            Err(e) => return Err(MainError::CommandFailed(e.to_string())),
+        },
+        None => println!("{}", reply.to_line().trim_end()),
+    }
+    Ok(())
+}
+
+fn stream_events(socket_path: &Path, request: &str) -> Result<(), MainError> {
+    use std::io::Write;
+
+    let events = match ipc::subscribe(socket_path, request) {
+        Ok(events) => events,
+        Err(e) => return Err(MainError::IpcError(e)),
+    };
+
+    let mut stdout = std::io::stdout();
+    for event in events {
+        let event = match event {
+            Ok(line) => line,
+            Err(e) => return Err(MainError::IpcError(IpcError::Io(e))),
+        };
+        if writeln!(stdout, "{event}").is_err() {
+            break;
+        }
+    }
+
+    Ok(())
+}
+
 fn check_custom_config(path: Option<String>) -> Result<String, MainError> {
     let path = match path {
         Some(p) => p,
@@ -1,3 +1,5 @@
+use crate::bar::Icon;
+use std::rc::Rc;
 use x11rb::protocol::xproto::Window;
 
 pub type TagMask = u32;
@@ -5,6 +7,9 @@ pub type TagMask = u32;
 #[derive(Debug, Clone)]
 pub struct Client {
     pub name: String,
+    /// `WM_CLASS` instance and class, kept up to date from PropertyNotify.
+    pub instance: String,
+    pub class: String,
     pub min_aspect: f32,
     pub max_aspect: f32,
     pub x_position: i16,
@@ -33,6 +38,23 @@ pub struct Client {
     pub never_focus: bool,
     pub old_state: bool,
     pub is_fullscreen: bool,
+    pub is_modal: bool,
+    pub is_sticky: bool,
+    pub is_maximized_vert: bool,
+    pub is_maximized_horz: bool,
+    pub is_shaded: bool,
+    pub skip_taskbar: bool,
+    pub skip_pager: bool,
+    pub is_hidden: bool,
+    pub is_above: bool,
+    pub is_below: bool,
+    /// Set when the client stopped answering `_NET_WM_PING`.
+    pub is_unresponsive: bool,
+    /// Whether `WM_PROTOCOLS` lists `_NET_WM_PING`.
+    pub supports_ping: bool,
+    pub maximize_was_floating: bool,
+    pub maximize_restore: Option<(i16, i16, u16, u16)>,
+    pub icon: Option<Rc<Icon>>,
     pub next: Option<Window>,
     pub stack_next: Option<Window>,
     pub monitor_index: usize,
@@ -43,6 +65,8 @@ impl Client {
     pub fn new(window: Window, monitor_index: usize, tags: TagMask) -> Self {
         Self {
             name: String::new(),
+            instance: String::new(),
+            class: String::new(),
             min_aspect: 0.0,
             max_aspect: 0.0,
             x_position: 0,
@@ -71,6 +95,21 @@ impl Client {
             never_focus: false,
             old_state: false,
             is_fullscreen: false,
+            is_modal: false,
+            is_sticky: false,
+            is_maximized_vert: false,
+            is_maximized_horz: false,
+            is_shaded: false,
+            skip_taskbar: false,
+            skip_pager: false,
+            is_hidden: false,
+            is_above: false,
+            is_below: false,
+            is_unresponsive: false,
+            supports_ping: false,
+            maximize_was_floating: false,
+            maximize_restore: None,
+            icon: None,
             next: None,
             stack_next: None,
             monitor_index,
@@ -78,6 +117,11 @@ impl Client {
         }
     }
 
+    /// Whether the client shows up on a monitor currently viewing `tagset`.
+    pub fn is_visible_on(&self, tagset: TagMask) -> bool {
+        !self.is_hidden && (self.tags & tagset) != 0
+    }
+
     pub fn width_with_border(&self) -> u16 {
         self.width.saturating_add(2 * self.border_width)
     }
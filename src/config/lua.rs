@@ -1,14 +1,26 @@
 use crate::errors::ConfigError;
-use mlua::Lua;
+use crate::keyboard::keysyms::Keysym;
+use mlua::{Function, Lua, Table, Value};
+use std::path::{Path, PathBuf};
 
-use super::lua_api;
+use super::lua_api::{self, SharedBuilder};
+use super::runtime::LuaRuntime;
 
-pub fn parse_lua_config(
-    input: &str,
-    config_dir: Option<&std::path::Path>,
-) -> Result<crate::Config, ConfigError> {
-    let lua = Lua::new();
+/// Outcome of `check_lua_config`: every error and warning found, already
+/// formatted with their `file:line` location when known.
+#[derive(Debug, Default)]
+pub struct ConfigReport {
+    pub errors: Vec<String>,
+    pub warnings: Vec<String>,
+}
 
+impl ConfigReport {
+    pub fn is_ok(&self) -> bool {
+        self.errors.is_empty()
+    }
+}
+
+fn prepare_lua(lua: &Lua, config_dir: Option<&Path>) -> Result<SharedBuilder, ConfigError> {
     if let Some(dir) = config_dir
         && let Some(dir_str) = dir.to_str()
     {
@@ -18,18 +30,155 @@ pub fn parse_lua_config(
             .map_err(|e| ConfigError::LuaError(format!("Failed to set package.path: {}", e)))?;
     }
 
-    let builder = lua_api::register_api(&lua)?;
+    lua_api::register_api(lua)
+}
+
+/// Evaluates the config at `path` without touching X, collecting as many
+/// problems as possible instead of stopping at the first failing call.
+pub fn check_lua_config(input: &str, path: &Path) -> ConfigReport {
+    let mut report = ConfigReport::default();
+    let lua = Lua::new();
+
+    let builder = match prepare_lua(&lua, path.parent()) {
+        Ok(builder) => builder,
+        Err(e) => {
+            report.errors.push(e.to_string());
+            return report;
+        }
+    };
+    builder.borrow_mut().collect_errors = true;
+    if let Err(e) = lua_api::collect_call_errors(&lua, &builder) {
+        report.errors.push(ConfigError::from(e).to_string());
+        return report;
+    }
+
+    let exec_result = lua
+        .load(input)
+        .set_name(format!("@{}", path.display()))
+        .exec();
+
+    let builder_data = builder.borrow();
+    report
+        .errors
+        .extend(builder_data.errors.iter().map(|d| d.to_string()));
+    if let Err(e) = exec_result {
+        report.errors.push(ConfigError::from(e).to_string());
+    }
+
+    report
+        .warnings
+        .extend(builder_data.warnings.iter().map(|d| d.to_string()));
+    report.warnings.extend(duplicate_keybindings(&builder_data));
+
+    report
+}
+
+/// Modifier mask and keysym of every key press in a binding.
+type KeySignature = Vec<(u16, Keysym)>;
+
+fn duplicate_keybindings(builder: &lua_api::ConfigBuilder) -> Vec<String> {
+    let mut seen: Vec<(KeySignature, Option<&String>)> = Vec::new();
+    let mut warnings = Vec::new();
+
+    for (index, binding) in builder.keybindings.iter().enumerate() {
+        let keys: KeySignature = binding
+            .keys
+            .iter()
+            .map(|key| {
+                let mask = key
+                    .modifiers
+                    .iter()
+                    .fold(0u16, |acc, &modifier| acc | u16::from(modifier));
+                (mask, key.keysym)
+            })
+            .collect();
+        let location = builder
+            .keybinding_locations
+            .get(index)
+            .and_then(Option::as_ref);
+
+        if let Some((_, first)) = seen.iter().find(|(existing, _)| *existing == keys) {
+            let here = location.map(|l| format!("{}: ", l)).unwrap_or_default();
+            let previous = first
+                .map(|l| format!(" (first bound at {})", l))
+                .unwrap_or_default();
+            warnings.push(format!("{}duplicate keybinding{}", here, previous));
+        } else {
+            seen.push((keys, location));
+        }
+    }
+
+    warnings
+}
+
+const BUILTIN_MODULES: &[&str] = &[
+    "_G",
+    "coroutine",
+    "debug",
+    "io",
+    "math",
+    "os",
+    "package",
+    "string",
+    "table",
+    "utf8",
+];
+
+/// Resolves the files behind every module the config pulled in through
+/// `require`, so they can be watched alongside the config itself.
+fn required_modules(lua: &Lua) -> Vec<PathBuf> {
+    let Ok(package) = lua.globals().get::<Table>("package") else {
+        return Vec::new();
+    };
+    let (Ok(loaded), Ok(search_path), Ok(searchpath)) = (
+        package.get::<Table>("loaded"),
+        package.get::<String>("path"),
+        package.get::<Function>("searchpath"),
+    ) else {
+        return Vec::new();
+    };
+
+    let mut modules = Vec::new();
+    for (name, _) in loaded.pairs::<String, Value>().flatten() {
+        if BUILTIN_MODULES.contains(&name.as_str()) {
+            continue;
+        }
+        if let Ok(Some(file)) = searchpath.call::<Option<String>>((name, search_path.clone())) {
+            modules.push(PathBuf::from(file));
+        }
+    }
+    modules.sort();
+    modules
+}
+
+pub fn parse_lua_config(
+    input: &str,
+    config_dir: Option<&std::path::Path>,
+) -> Result<crate::Config, ConfigError> {
+    load_lua_config(input, config_dir).map(|(config, _)| config)
+}
+
+/// Like `parse_lua_config`, but also hands back the Lua state the config
+/// ran in so callbacks registered with `oxwm.on` can be called later.
+pub fn load_lua_config(
+    input: &str,
+    config_dir: Option<&std::path::Path>,
+) -> Result<(crate::Config, LuaRuntime), ConfigError> {
+    let lua = Lua::new();
+    let builder = prepare_lua(&lua, config_dir)?;
 
     lua.load(input)
         .exec()
         .map_err(|e| ConfigError::LuaError(format!("{}", e)))?;
 
     let builder_data = builder.borrow().clone();
+    let required_modules = required_modules(&lua);
 
-    Ok(crate::Config {
+    let config = crate::Config {
         border_width: builder_data.border_width,
         border_focused: builder_data.border_focused,
         border_unfocused: builder_data.border_unfocused,
+        border_unresponsive: builder_data.border_unresponsive,
         font: builder_data.font,
         gaps_enabled: builder_data.gaps_enabled,
         smartgaps_enabled: builder_data.smartgaps_enabled,
@@ -43,7 +192,9 @@ pub fn parse_lua_config(
         layout_symbols: builder_data.layout_symbols,
         keybindings: builder_data.keybindings,
         tag_back_and_forth: builder_data.tag_back_and_forth,
+        shared_tags: builder_data.shared_tags,
         window_rules: builder_data.window_rules,
+        activation_policy: builder_data.activation_policy,
         status_blocks: builder_data.status_blocks,
         scheme_normal: builder_data.scheme_normal,
         scheme_occupied: builder_data.scheme_occupied,
@@ -52,6 +203,17 @@ pub fn parse_lua_config(
         autostart: builder_data.autostart,
         auto_tile: builder_data.auto_tile,
         hide_vacant_tags: builder_data.hide_vacant_tags,
+        tag_icons: builder_data.tag_icons,
+        systray_monitor: builder_data.systray_monitor,
+        monitor_profiles: builder_data.monitor_profiles,
+        warp_pointer_to_monitor: builder_data.warp_pointer_to_monitor,
+        auto_reload: builder_data.auto_reload,
+        required_modules,
         path: None,
-    })
+    };
+
+    Ok((
+        config,
+        LuaRuntime::new(lua, builder_data.hooks, builder_data.callbacks),
+    ))
 }
@@ -1,20 +1,42 @@
-use mlua::{Lua, Table, Value};
+use mlua::{Function, Lua, MultiValue, Table, Value};
 use std::cell::RefCell;
 use std::rc::Rc;
 
 use crate::ColorScheme;
 use crate::bar::BlockConfig;
+use crate::client::TagMask;
 use crate::bar::BlockCommand;
+use crate::config::runtime::{ACTION_RUNNER, HookEvent, WmState};
 use crate::errors::ConfigError;
 use crate::keyboard::handlers::{Arg, KeyAction, KeyBinding, KeyPress};
 use crate::keyboard::keysyms::{self, Keysym};
-use x11rb::protocol::xproto::KeyButMask;
+use crate::layout::LayoutType;
+use crate::monitor::{Direction, MonitorProfile, MonitorSelector};
+use x11rb::protocol::xproto::{KeyButMask, Window};
+
+/// A problem found while evaluating the config, tagged with the Lua source
+/// location of the call that caused it.
+#[derive(Debug, Clone)]
+pub struct Diagnostic {
+    pub location: Option<String>,
+    pub message: String,
+}
+
+impl std::fmt::Display for Diagnostic {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match &self.location {
+            Some(location) => write!(f, "{}: {}", location, self.message),
+            None => write!(f, "{}", self.message),
+        }
+    }
+}
 
 #[derive(Clone)]
 pub struct ConfigBuilder {
     pub border_width: u32,
     pub border_focused: u32,
     pub border_unfocused: u32,
+    pub border_unresponsive: u32,
     pub font: String,
     pub gaps_enabled: bool,
     pub smartgaps_enabled: bool,
@@ -28,7 +50,9 @@ pub struct ConfigBuilder {
     pub layout_symbols: Vec<crate::LayoutSymbolOverride>,
     pub keybindings: Vec<KeyBinding>,
     pub tag_back_and_forth: bool,
+    pub shared_tags: crate::SharedTags,
     pub window_rules: Vec<crate::WindowRule>,
+    pub activation_policy: crate::ActivationPolicy,
     pub status_blocks: Vec<BlockConfig>,
     pub scheme_normal: ColorScheme,
     pub scheme_occupied: ColorScheme,
@@ -37,6 +61,21 @@ pub struct ConfigBuilder {
     pub autostart: Vec<String>,
     pub auto_tile: bool,
     pub hide_vacant_tags: bool,
+    pub tag_icons: bool,
+    pub systray_monitor: Vec<MonitorSelector>,
+    pub monitor_profiles: Vec<(MonitorSelector, MonitorProfile)>,
+    pub warp_pointer_to_monitor: bool,
+    pub auto_reload: bool,
+    pub hooks: Vec<(HookEvent, Function)>,
+    /// Functions bound directly to keys, referenced by index from
+    /// `KeyAction::LuaFunction` bindings.
+    pub callbacks: Vec<Function>,
+    /// When set, bad keybindings are recorded in `errors` instead of
+    /// aborting the script, so `--check-config` can report all of them.
+    pub collect_errors: bool,
+    pub errors: Vec<Diagnostic>,
+    pub warnings: Vec<Diagnostic>,
+    pub keybinding_locations: Vec<Option<String>>,
 }
 
 impl Default for ConfigBuilder {
@@ -45,6 +84,7 @@ impl Default for ConfigBuilder {
             border_width: 2,
             border_focused: 0x6dade3,
             border_unfocused: 0xbbbbbb,
+            border_unresponsive: 0xff5555,
             font: "monospace:style=Bold:size=10".to_string(),
             gaps_enabled: true,
             smartgaps_enabled: true,
@@ -58,7 +98,9 @@ impl Default for ConfigBuilder {
             layout_symbols: Vec::new(),
             keybindings: Vec::new(),
             tag_back_and_forth: false,
+            shared_tags: crate::SharedTags::Off,
             window_rules: Vec::new(),
+            activation_policy: crate::ActivationPolicy::default(),
             status_blocks: Vec::new(),
             scheme_normal: ColorScheme {
                 foreground: 0xffffff,
@@ -83,14 +125,26 @@ impl Default for ConfigBuilder {
             autostart: Vec::new(),
             auto_tile: false,
             hide_vacant_tags: false,
+            tag_icons: false,
+            systray_monitor: vec![MonitorSelector::Index(0)],
+            monitor_profiles: Vec::new(),
+            warp_pointer_to_monitor: false,
+            auto_reload: false,
+            hooks: Vec::new(),
+            callbacks: Vec::new(),
+            collect_errors: false,
+            errors: Vec::new(),
+            warnings: Vec::new(),
+            keybinding_locations: Vec::new(),
         }
     }
 }
 
-type SharedBuilder = Rc<RefCell<ConfigBuilder>>;
+pub type SharedBuilder = Rc<RefCell<ConfigBuilder>>;
 
 pub fn register_api(lua: &Lua) -> Result<SharedBuilder, ConfigError> {
     let builder = Rc::new(RefCell::new(ConfigBuilder::default()));
+    lua.set_app_data(WmState::default());
 
     let oxwm_table = lua.create_table()?;
 
@@ -101,7 +155,7 @@ pub fn register_api(lua: &Lua) -> Result<SharedBuilder, ConfigError> {
     register_client_module(lua, &oxwm_table)?;
     register_layout_module(lua, &oxwm_table)?;
     register_tag_module(lua, &oxwm_table, builder.clone())?;
-    register_monitor_module(lua, &oxwm_table)?;
+    register_monitor_module(lua, &oxwm_table, builder.clone())?;
     register_rule_module(lua, &oxwm_table, builder.clone())?;
     register_bar_module(lua, &oxwm_table, builder.clone())?;
     register_misc(lua, &oxwm_table, builder.clone())?;
@@ -129,36 +183,39 @@ fn register_key_module(
 
     let builder_clone = builder.clone();
     let bind = lua.create_function(move |lua, (mods, key, action): (Value, String, Value)| {
-        let modifiers = parse_modifiers_value(lua, mods)?;
-        let keysym = parse_keysym(&key)?;
-        let (key_action, arg) = parse_action_value(lua, action)?;
-
-        let binding = KeyBinding::single_key(modifiers, keysym, key_action, arg);
-        builder_clone.borrow_mut().keybindings.push(binding);
+        let location = caller_location(lua);
+        let binding = (|| {
+            let modifiers = parse_modifiers_value(lua, mods)?;
+            let keysym = parse_keysym(&key)?;
+            let (key_action, arg) = parse_action_value(&builder_clone, action)?;
+            Ok(KeyBinding::single_key(modifiers, keysym, key_action, arg))
+        })();
 
-        Ok(())
+        push_keybinding(&builder_clone, binding, location)
     })?;
 
     let builder_clone = builder.clone();
     let chord = lua.create_function(move |lua, (keys, action): (Table, Value)| {
-        let mut key_presses = Vec::new();
+        let location = caller_location(lua);
+        let binding = (|| {
+            let mut key_presses = Vec::new();
 
-        for i in 1..=keys.len()? {
-            let key_spec: Table = keys.get(i)?;
-            let mods: Value = key_spec.get(1)?;
-            let key: String = key_spec.get(2)?;
+            for i in 1..=keys.len()? {
+                let key_spec: Table = keys.get(i)?;
+                let mods: Value = key_spec.get(1)?;
+                let key: String = key_spec.get(2)?;
 
-            let modifiers = parse_modifiers_value(lua, mods)?;
-            let keysym = parse_keysym(&key)?;
+                let modifiers = parse_modifiers_value(lua, mods)?;
+                let keysym = parse_keysym(&key)?;
 
-            key_presses.push(KeyPress { modifiers, keysym });
-        }
+                key_presses.push(KeyPress { modifiers, keysym });
+            }
 
-        let (key_action, arg) = parse_action_value(lua, action)?;
-        let binding = KeyBinding::new(key_presses, key_action, arg);
-        builder_clone.borrow_mut().keybindings.push(binding);
+            let (key_action, arg) = parse_action_value(&builder_clone, action)?;
+            Ok(KeyBinding::new(key_presses, key_action, arg))
+        })();
 
-        Ok(())
+        push_keybinding(&builder_clone, binding, location)
     })?;
 
     key_table.set("bind", bind)?;
@@ -251,9 +308,17 @@ fn register_border_module(
         Ok(())
     })?;
 
+    let builder_clone = builder.clone();
+    let set_unresponsive_color = lua.create_function(move |_, color: Value| {
+        let color_u32 = parse_color_value(color)?;
+        builder_clone.borrow_mut().border_unresponsive = color_u32;
+        Ok(())
+    })?;
+
     border_table.set("set_width", set_width)?;
     border_table.set("set_focused_color", set_focused_color)?;
     border_table.set("set_unfocused_color", set_unfocused_color)?;
+    border_table.set("set_unresponsive_color", set_unresponsive_color)?;
     parent.set("border", border_table)?;
     Ok(())
 }
@@ -283,6 +348,52 @@ fn register_client_module(lua: &Lua, parent: &Table) -> Result<(), ConfigError>
     client_table.set("focus_stack", focus_stack)?;
     client_table.set("move_stack", move_stack)?;
 
+    let focused = lua.create_function(|lua, ()| {
+        with_state(lua, |state| {
+            state
+                .focused_client()
+                .map(|client| client.to_table(lua))
+                .transpose()
+        })
+    })?;
+    client_table.set("focused", focused)?;
+
+    let list = lua.create_function(|lua, ()| {
+        with_state(lua, |state| {
+            let clients = lua.create_table()?;
+            for client in &state.clients {
+                clients.push(client.to_table(lua)?)?;
+            }
+            Ok(clients)
+        })
+    })?;
+
+    let get_class = lua.create_function(|lua, window: Option<Window>| {
+        with_state(lua, |state| {
+            Ok(state.client(window).map(|c| c.class.clone()))
+        })
+    })?;
+
+    let get_title = lua.create_function(|lua, window: Option<Window>| {
+        with_state(lua, |state| {
+            Ok(state.client(window).map(|c| c.name.clone()))
+        })
+    })?;
+
+    let get_tags = lua.create_function(|lua, window: Option<Window>| {
+        with_state(lua, |state| {
+            state
+                .client(window)
+                .map(|client| tag_indices(lua, client.tags))
+                .transpose()
+        })
+    })?;
+
+    client_table.set("list", list)?;
+    client_table.set("get_class", get_class)?;
+    client_table.set("get_title", get_title)?;
+    client_table.set("get_tags", get_tags)?;
+
     parent.set("client", client_table)?;
     Ok(())
 }
@@ -349,6 +460,25 @@ fn register_tag_module(
         Ok(())
     })?;
 
+    let builder_clone = builder.clone();
+    let set_shared = lua.create_function(move |_, mode: Value| {
+        let shared_tags = match mode {
+            Value::Boolean(false) | Value::Nil => crate::SharedTags::Off,
+            Value::Boolean(true) => crate::SharedTags::Greedy,
+            Value::String(mode) => mode
+                .to_str()?
+                .parse::<crate::SharedTags>()
+                .map_err(|e| mlua::Error::RuntimeError(format!("oxwm.tag.set_shared: {}", e)))?,
+            _ => {
+                return Err(mlua::Error::RuntimeError(
+                    "oxwm.tag.set_shared: expected a boolean or \"greedy\"/\"focus\"".into(),
+                ));
+            }
+        };
+        builder_clone.borrow_mut().shared_tags = shared_tags;
+        Ok(())
+    })?;
+
     tag_table.set("view", view)?;
     tag_table.set("view_next", view_next)?;
     tag_table.set("view_previous", view_previous)?;
@@ -358,27 +488,206 @@ fn register_tag_module(
     tag_table.set("move_to", move_to)?;
     tag_table.set("toggletag", toggletag)?;
     tag_table.set("set_back_and_forth", set_back_and_forth)?;
+    tag_table.set("set_shared", set_shared)?;
+
+    let current = lua.create_function(|lua, ()| {
+        with_state(lua, |state| {
+            Ok(state
+                .selected_monitor()
+                .filter(|monitor| monitor.tagset != 0)
+                .map(|monitor| monitor.tagset.trailing_zeros()))
+        })
+    })?;
+    tag_table.set("current", current)?;
+
+    let occupied = lua.create_function(|lua, monitor: Option<usize>| {
+        with_state(lua, |state| {
+            let monitor = monitor.unwrap_or(state.selected_monitor);
+            tag_indices(lua, state.occupied_tags(monitor))
+        })
+    })?;
+    tag_table.set("occupied", occupied)?;
     parent.set("tag", tag_table)?;
     Ok(())
 }
 
-fn register_monitor_module(lua: &Lua, parent: &Table) -> Result<(), ConfigError> {
+fn register_monitor_module(
+    lua: &Lua,
+    parent: &Table,
+    builder: SharedBuilder,
+) -> Result<(), ConfigError> {
     let monitor_table = lua.create_table()?;
 
-    let focus = lua.create_function(|lua, direction: i64| {
-        create_action_table(lua, "FocusMonitor", Value::Integer(direction))
+    // Either a direction (-1/1) or an output/model name.
+    let focus = lua.create_function(|lua, target: Value| {
+        create_action_table(lua, "FocusMonitor", monitor_target(target)?)
     })?;
 
-    let tag = lua.create_function(|lua, direction: i64| {
-        create_action_table(lua, "TagMonitor", Value::Integer(direction))
+    let tag = lua.create_function(|lua, target: Value| {
+        create_action_table(lua, "TagMonitor", monitor_target(target)?)
+    })?;
+
+    // "left", "right", "up" or "down", by where the monitors sit.
+    let focus_direction = lua.create_function(|lua, direction: String| {
+        create_action_table(lua, "FocusMonitorDirection", direction_arg(lua, direction)?)
+    })?;
+
+    let tag_direction = lua.create_function(|lua, direction: String| {
+        create_action_table(lua, "TagMonitorDirection", direction_arg(lua, direction)?)
+    })?;
+
+    let builder_clone = builder.clone();
+    let set_warp_pointer = lua.create_function(move |_, enabled: bool| {
+        builder_clone.borrow_mut().warp_pointer_to_monitor = enabled;
+        Ok(())
     })?;
 
     monitor_table.set("focus", focus)?;
     monitor_table.set("tag", tag)?;
+    monitor_table.set("focus_direction", focus_direction)?;
+    monitor_table.set("tag_direction", tag_direction)?;
+    monitor_table.set("set_warp_pointer", set_warp_pointer)?;
+
+    let list = lua.create_function(|lua, ()| {
+        with_state(lua, |state| {
+            let monitors = lua.create_table()?;
+            for monitor in &state.monitors {
+                monitors.push(monitor.to_table(lua)?)?;
+            }
+            Ok(monitors)
+        })
+    })?;
+    monitor_table.set("list", list)?;
+
+    let geometry = lua.create_function(|lua, index: Option<usize>| {
+        with_state(lua, |state| {
+            let index = index.unwrap_or(state.selected_monitor);
+            let Some(monitor) = state.monitors.get(index) else {
+                return Ok(None);
+            };
+            let table = lua.create_table()?;
+            table.set("x", monitor.x)?;
+            table.set("y", monitor.y)?;
+            table.set("width", monitor.width)?;
+            table.set("height", monitor.height)?;
+            Ok(Some(table))
+        })
+    })?;
+    monitor_table.set("geometry", geometry)?;
+
+    let builder_clone = builder.clone();
+    let configure = lua.create_function(move |lua, (target, options): (Value, Table)| {
+        warn_unknown_keys(
+            lua,
+            &builder_clone,
+            &options,
+            "oxwm.monitor.configure",
+            &["layout", "mfact", "show_bar", "top_bar", "gaps"],
+        )?;
+
+        let selector = match target {
+            Value::Integer(index) if index >= 0 => MonitorSelector::Index(index as usize),
+            Value::String(name) => MonitorSelector::Name(name.to_str()?.to_string()),
+            _ => {
+                return Err(mlua::Error::RuntimeError(
+                    "oxwm.monitor.configure: expected a monitor index or an output name".into(),
+                ));
+            }
+        };
+
+        let layout = match options.get::<Option<String>>("layout")? {
+            Some(name) => Some(
+                name.parse::<LayoutType>()
+                    .map_err(|e| {
+                        mlua::Error::RuntimeError(format!("oxwm.monitor.configure: {}", e))
+                    })?
+                    .as_str()
+                    .to_string(),
+            ),
+            None => None,
+        };
+
+        let master_factor = options.get::<Option<f32>>("mfact")?;
+        if let Some(mfact) = master_factor
+            && !(0.05..=0.95).contains(&mfact)
+        {
+            return Err(mlua::Error::RuntimeError(
+                "oxwm.monitor.configure: mfact must be between 0.05 and 0.95".into(),
+            ));
+        }
+
+        let (gaps_inner, gaps_outer) = match options.get::<Value>("gaps")? {
+            Value::Nil => (None, None),
+            Value::Boolean(false) => (Some((0, 0)), Some((0, 0))),
+            Value::Table(gaps) => {
+                warn_unknown_keys(
+                    lua,
+                    &builder_clone,
+                    &gaps,
+                    "oxwm.monitor.configure gaps",
+                    &["inner", "outer"],
+                )?;
+                (
+                    parse_gap_pair(gaps.get("inner")?)?,
+                    parse_gap_pair(gaps.get("outer")?)?,
+                )
+            }
+            _ => {
+                return Err(mlua::Error::RuntimeError(
+                    "oxwm.monitor.configure: gaps must be a table or false".into(),
+                ));
+            }
+        };
+
+        let profile = MonitorProfile {
+            layout,
+            master_factor,
+            show_bar: options.get("show_bar")?,
+            top_bar: options.get("top_bar")?,
+            gaps_inner,
+            gaps_outer,
+        };
+        builder_clone
+            .borrow_mut()
+            .monitor_profiles
+            .push((selector, profile));
+        Ok(())
+    })?;
+    monitor_table.set("configure", configure)?;
+
     parent.set("monitor", monitor_table)?;
     Ok(())
 }
 
+/// Reads `n` (both axes) or `{horizontal, vertical}`.
+fn parse_gap_pair(value: Value) -> mlua::Result<Option<(u32, u32)>> {
+    match value {
+        Value::Nil => Ok(None),
+        Value::Integer(gap) if gap >= 0 => Ok(Some((gap as u32, gap as u32))),
+        Value::Table(pair) => Ok(Some((pair.get(1)?, pair.get(2)?))),
+        _ => Err(mlua::Error::RuntimeError(
+            "oxwm.monitor.configure: a gap must be a number or {horizontal, vertical}".into(),
+        )),
+    }
+}
+
+fn direction_arg(lua: &Lua, direction: String) -> mlua::Result<Value> {
+    direction
+        .parse::<Direction>()
+        .map_err(|e| mlua::Error::RuntimeError(format!("oxwm.monitor: {}", e)))?;
+    Ok(Value::String(lua.create_string(&direction)?))
+}
+
+fn monitor_target(target: Value) -> mlua::Result<Value> {
+    match target {
+        Value::Integer(_) | Value::String(_) => Ok(target),
+        Value::Number(n) => Ok(Value::Integer(n as i64)),
+        _ => Err(mlua::Error::RuntimeError(
+            "oxwm.monitor: expected a direction or an output name".into(),
+        )),
+    }
+}
+
 fn register_rule_module(
     lua: &Lua,
     parent: &Table,
@@ -387,13 +696,38 @@ fn register_rule_module(
     let rule_table = lua.create_table()?;
 
     let builder_clone = builder.clone();
-    let add = lua.create_function(move |_, config: Table| {
+    let add = lua.create_function(move |lua, config: Table| {
+        warn_unknown_keys(
+            lua,
+            &builder_clone,
+            &config,
+            "oxwm.rule.add",
+            &[
+                "class",
+                "instance",
+                "title",
+                "floating",
+                "monitor",
+                "focus",
+                "tag",
+                "activation",
+            ],
+        )?;
+
         let class: Option<String> = config.get("class").ok();
         let instance: Option<String> = config.get("instance").ok();
         let title: Option<String> = config.get("title").ok();
         let is_floating: Option<bool> = config.get("floating").ok();
-        let monitor: Option<usize> = config.get("monitor").ok();
+        let monitor = parse_monitor_selectors(config.get("monitor")?, "oxwm.rule.add")?;
         let focus: Option<bool> = config.get("focus").ok();
+        let activation = match config.get::<Option<String>>("activation")? {
+            Some(policy) => Some(
+                policy
+                    .parse::<crate::ActivationPolicy>()
+                    .map_err(|e| mlua::Error::RuntimeError(format!("oxwm.rule.add: {}", e)))?,
+            ),
+            None => None,
+        };
 
         let tags: Option<u32> = if let Ok(tag_index) = config.get::<i32>("tag") {
             if tag_index > 0 {
@@ -413,6 +747,7 @@ fn register_rule_module(
             focus,
             is_floating,
             monitor,
+            activation,
         };
 
         builder_clone.borrow_mut().window_rules.push(rule);
@@ -439,13 +774,18 @@ fn register_bar_module(
 
     let block_table = lua.create_table()?;
 
-    let ram =
-        lua.create_function(|lua, config: Table| create_block_config(lua, config, "Ram", None))?;
+    let builder_clone = builder.clone();
+    let ram = lua.create_function(move |lua, config: Table| {
+        create_block_config(lua, &builder_clone, config, "Ram", None, &[])
+    })?;
 
-    let cpu =
-	lua.create_function(|lua, config: Table| create_block_config(lua, config, "Cpu", None))?;
+    let builder_clone = builder.clone();
+    let cpu = lua.create_function(move |lua, config: Table| {
+        create_block_config(lua, &builder_clone, config, "Cpu", None, &[])
+    })?;
 
-    let datetime = lua.create_function(|lua, config: Table| {
+    let builder_clone = builder.clone();
+    let datetime = lua.create_function(move |lua, config: Table| {
         let date_format: String = config.get("date_format").map_err(|_| {
             mlua::Error::RuntimeError(
                 "oxwm.bar.block.datetime: 'date_format' field is required (e.g., '%H:%M')".into(),
@@ -453,12 +793,15 @@ fn register_bar_module(
         })?;
         create_block_config(
             lua,
+            &builder_clone,
             config,
             "DateTime",
             Some(Value::String(lua.create_string(&date_format)?)),
+            &["date_format"],
         )
     })?;
-    let wifi = lua.create_function(|lua, config: Table| {
+    let builder_clone = builder.clone();
+    let wifi = lua.create_function(move |lua, config: Table| {
     let interface: Option<String> = config.get("interface").unwrap_or(None);
     
     let interface_table = lua.create_table()?;
@@ -466,36 +809,45 @@ fn register_bar_module(
     
     create_block_config(
         lua,
+        &builder_clone,
         config,
         "Wifi",
         Some(Value::Table(interface_table)),
+        &["interface"],
     )
 })?;
-    let shell = lua.create_function(|lua, config: Table| {
+    let builder_clone = builder.clone();
+    let shell = lua.create_function(move |lua, config: Table| {
         let command: String = config.get("command").map_err(|_| {
             mlua::Error::RuntimeError("oxwm.bar.block.shell: 'command' field is required".into())
         })?;
         create_block_config(
             lua,
+            &builder_clone,
             config,
             "Shell",
             Some(Value::String(lua.create_string(&command)?)),
+            &["command"],
         )
     })?;
 
-    let static_block = lua.create_function(|lua, config: Table| {
+    let builder_clone = builder.clone();
+    let static_block = lua.create_function(move |lua, config: Table| {
         let text: String = config.get("text").map_err(|_| {
             mlua::Error::RuntimeError("oxwm.bar.block.static: 'text' field is required".into())
         })?;
         create_block_config(
             lua,
+            &builder_clone,
             config,
             "Static",
             Some(Value::String(lua.create_string(&text)?)),
+            &["text"],
         )
     })?;
 
-    let battery = lua.create_function(|lua, config: Table| {
+    let builder_clone = builder.clone();
+    let battery = lua.create_function(move |lua, config: Table| {
         let charging: String = config.get("charging").map_err(|_| {
             mlua::Error::RuntimeError("oxwm.bar.block.battery: 'charging' field is required".into())
         })?;
@@ -515,7 +867,14 @@ fn register_bar_module(
         formats_table.set("full", full)?;
         formats_table.set("battery_name", battery_name)?;
 
-        create_block_config(lua, config, "Battery", Some(Value::Table(formats_table)))
+        create_block_config(
+            lua,
+            &builder_clone,
+            config,
+            "Battery",
+            Some(Value::Table(formats_table)),
+            &["charging", "discharging", "full", "battery_name"],
+        )
     })?;
     block_table.set("ram", ram)?;
     block_table.set("cpu", cpu)?;
@@ -769,6 +1128,23 @@ fn register_bar_module(
         Ok(())
     })?;
 
+    let builder_clone = builder.clone();
+    let set_tag_icons = lua.create_function(move |_, enabled: bool| {
+        builder_clone.borrow_mut().tag_icons = enabled;
+        Ok(())
+    })?;
+
+    let builder_clone = builder.clone();
+    let set_systray = lua.create_function(move |_, monitor: Value| {
+        let systray_monitor = match monitor {
+            Value::Boolean(false) | Value::Nil => Vec::new(),
+            Value::Boolean(true) => vec![MonitorSelector::Index(0)],
+            other => parse_monitor_selectors(other, "oxwm.bar.set_systray")?,
+        };
+        builder_clone.borrow_mut().systray_monitor = systray_monitor;
+        Ok(())
+    })?;
+
     bar_table.set("set_font", set_font)?;
     bar_table.set("block", block_table)?;
     bar_table.set("add_block", add_block)?; // Deprecated, for backwards compatibility
@@ -778,6 +1154,8 @@ fn register_bar_module(
     bar_table.set("set_scheme_selected", set_scheme_selected)?;
     bar_table.set("set_scheme_urgent", set_scheme_urgent)?;
     bar_table.set("set_hide_vacant_tags", set_hide_vacant_tags)?;
+    bar_table.set("set_tag_icons", set_tag_icons)?;
+    bar_table.set("set_systray", set_systray)?;
     parent.set("bar", bar_table)?;
     Ok(())
 }
@@ -846,6 +1224,34 @@ fn register_misc(lua: &Lua, parent: &Table, builder: SharedBuilder) -> Result<()
         Ok(())
     })?;
 
+    let builder_clone = builder.clone();
+    let set_activation_policy = lua.create_function(move |_, policy: String| {
+        builder_clone.borrow_mut().activation_policy = policy
+            .parse()
+            .map_err(|e| mlua::Error::RuntimeError(format!("oxwm.set_activation_policy: {}", e)))?;
+        Ok(())
+    })?;
+
+    let builder_clone = builder.clone();
+    let on = lua.create_function(move |_, (event, callback): (String, Function)| {
+        let hook = HookEvent::from_name(&event).ok_or_else(|| {
+            let names: Vec<&str> = HookEvent::ALL.iter().map(|e| e.name()).collect();
+            mlua::Error::RuntimeError(format!(
+                "oxwm.on: unknown event '{}'. valid events: {}",
+                event,
+                names.join(", ")
+            ))
+        })?;
+        builder_clone.borrow_mut().hooks.push((hook, callback));
+        Ok(())
+    })?;
+
+    let builder_clone = builder.clone();
+    let auto_reload = lua.create_function(move |_, enabled: bool| {
+        builder_clone.borrow_mut().auto_reload = enabled;
+        Ok(())
+    })?;
+
     parent.set("set_terminal", set_terminal)?;
     parent.set("set_modkey", set_modkey)?;
     parent.set("set_tags", set_tags)?;
@@ -859,6 +1265,9 @@ fn register_misc(lua: &Lua, parent: &Table, builder: SharedBuilder) -> Result<()
     parent.set("show_keybinds", show_keybinds)?;
     parent.set("focus_monitor", focus_monitor)?;
     parent.set("auto_tile", auto_tile)?;
+    parent.set("auto_reload", auto_reload)?;
+    parent.set("set_activation_policy", set_activation_policy)?;
+    parent.set("on", on)?;
     Ok(())
 }
 
@@ -902,17 +1311,154 @@ fn parse_modkey_string(s: &str) -> Result<KeyButMask, ConfigError> {
     }
 }
 
+/// Returns `file:line` of the Lua code that called the current function,
+/// skipping over Rust frames such as the wrappers `collect_call_errors`
+/// installs.
+fn caller_location(lua: &Lua) -> Option<String> {
+    (1..)
+        .map_while(|level| lua.inspect_stack(level))
+        .find(|debug| debug.curr_line() >= 0)
+        .and_then(|debug| {
+            let source = debug.source().short_src?.into_owned();
+            Some(format!("{}:{}", source, debug.curr_line()))
+        })
+}
+
+/// The error a callback raised, without the tracebacks mlua wraps it in.
+fn root_error(err: &mlua::Error) -> &mlua::Error {
+    match err {
+        mlua::Error::CallbackError { cause, .. } => root_error(cause),
+        other => other,
+    }
+}
+
+/// Wraps every function under `oxwm` so a call that fails is recorded as a
+/// diagnostic at the caller's line and the config keeps running, letting
+/// `--check-config` report every problem in one pass.
+pub fn collect_call_errors(lua: &Lua, builder: &SharedBuilder) -> mlua::Result<()> {
+    let oxwm: Table = lua.globals().get("oxwm")?;
+    wrap_functions(lua, builder, &oxwm, "oxwm")
+}
+
+fn wrap_functions(
+    lua: &Lua,
+    builder: &SharedBuilder,
+    table: &Table,
+    path: &str,
+) -> mlua::Result<()> {
+    let entries = table
+        .pairs::<String, Value>()
+        .collect::<mlua::Result<Vec<_>>>()?;
+
+    for (key, value) in entries {
+        let name = format!("{}.{}", path, key);
+        match value {
+            Value::Function(function) => {
+                let builder = builder.clone();
+                let wrapped = lua.create_function(move |lua, args: MultiValue| {
+                    match function.call::<MultiValue>(args) {
+                        Ok(values) => Ok(values),
+                        Err(err) => {
+                            // Our own errors already say what went wrong;
+                            // argument conversion errors don't name the call.
+                            let message = match root_error(&err) {
+                                mlua::Error::RuntimeError(msg) => msg.clone(),
+                                other => format!("{}: {}", name, other),
+                            };
+                            builder.borrow_mut().errors.push(Diagnostic {
+                                location: caller_location(lua),
+                                message,
+                            });
+                            Ok(MultiValue::new())
+                        }
+                    }
+                })?;
+                table.set(key, wrapped)?;
+            }
+            Value::Table(inner) => wrap_functions(lua, builder, &inner, &name)?,
+            _ => {}
+        }
+    }
+    Ok(())
+}
+
+fn push_keybinding(
+    builder: &SharedBuilder,
+    binding: mlua::Result<KeyBinding>,
+    location: Option<String>,
+) -> mlua::Result<()> {
+    let mut builder = builder.borrow_mut();
+    match binding {
+        Ok(binding) => {
+            builder.keybindings.push(binding);
+            builder.keybinding_locations.push(location);
+            Ok(())
+        }
+        Err(err) if builder.collect_errors => {
+            let message = match root_error(&err) {
+                mlua::Error::RuntimeError(msg) => msg.clone(),
+                other => other.to_string(),
+            };
+            builder.errors.push(Diagnostic { location, message });
+            Ok(())
+        }
+        Err(err) => Err(err),
+    }
+}
+
+fn warn_unknown_keys(
+    lua: &Lua,
+    builder: &SharedBuilder,
+    table: &Table,
+    context: &str,
+    allowed: &[&str],
+) -> mlua::Result<()> {
+    let mut unknown = Vec::new();
+    for pair in table.pairs::<Value, Value>() {
+        let (key, _) = pair?;
+        let name = match key {
+            Value::String(s) => s.to_str()?.to_string(),
+            Value::Integer(i) => i.to_string(),
+            other => other.type_name().to_string(),
+        };
+        if !allowed.contains(&name.as_str()) {
+            unknown.push(name);
+        }
+    }
+
+    if unknown.is_empty() {
+        return Ok(());
+    }
+
+    unknown.sort();
+    let location = caller_location(lua);
+    let mut builder = builder.borrow_mut();
+    for name in unknown {
+        builder.warnings.push(Diagnostic {
+            location: location.clone(),
+            message: format!(
+                "{}: unknown key '{}' (expected one of: {})",
+                context,
+                name,
+                allowed.join(", ")
+            ),
+        });
+    }
+    Ok(())
+}
+
 fn parse_keysym(key: &str) -> mlua::Result<Keysym> {
     keysyms::keysym_from_str(key)
         .ok_or_else(|| mlua::Error::RuntimeError(format!("unknown key '{}'. valid keys include: Return, Space, A-Z, 0-9, F1-F12, Left, Right, Up, Down, etc. check oxwm.lua type definitions for the complete list", key)))
 }
 
-fn parse_action_value(_lua: &Lua, value: Value) -> mlua::Result<(KeyAction, Arg)> {
+fn parse_action_value(builder: &SharedBuilder, value: Value) -> mlua::Result<(KeyAction, Arg)> {
     match value {
-        Value::Function(_) => {
-            Err(mlua::Error::RuntimeError(
-                "action must be a function call, not a function reference. did you forget ()? example: oxwm.spawn('st') not oxwm.spawn".into()
-            ))
+        Value::Function(function) => {
+            let mut builder = builder.borrow_mut();
+            let index = builder.callbacks.len() as i32;
+            builder.callbacks.push(function);
+            Ok((KeyAction::LuaFunction, Arg::Int(index)))
         }
         Value::Table(t) => {
             if let Ok(action_name) = t.get::<String>("__action") {
@@ -926,16 +1472,16 @@ fn parse_action_value(_lua: &Lua, value: Value) -> mlua::Result<(KeyAction, Arg)
             }
 
             Err(mlua::Error::RuntimeError(
-                "action must be a table returned by oxwm functions like oxwm.spawn(), oxwm.client.kill(), oxwm.quit(), etc.".into(),
+                "action must be a function or a table returned by oxwm functions like oxwm.spawn(), oxwm.client.kill(), oxwm.quit(), etc.".into(),
             ))
         }
         _ => Err(mlua::Error::RuntimeError(
-            "action must be a table returned by oxwm functions like oxwm.spawn(), oxwm.client.kill(), oxwm.quit(), etc.".into(),
+            "action must be a function or a table returned by oxwm functions like oxwm.spawn(), oxwm.client.kill(), oxwm.quit(), etc.".into(),
         )),
     }
 }
 
-fn string_to_action(s: &str) -> mlua::Result<KeyAction> {
+pub(crate) fn string_to_action(s: &str) -> mlua::Result<KeyAction> {
     match s {
         "Spawn" => Ok(KeyAction::Spawn),
         "SpawnTerminal" => Ok(KeyAction::SpawnTerminal),
@@ -961,6 +1507,8 @@ fn string_to_action(s: &str) -> mlua::Result<KeyAction> {
         "CycleLayout" => Ok(KeyAction::CycleLayout),
         "FocusMonitor" => Ok(KeyAction::FocusMonitor),
         "TagMonitor" => Ok(KeyAction::TagMonitor),
+        "FocusMonitorDirection" => Ok(KeyAction::FocusMonitorDirection),
+        "TagMonitorDirection" => Ok(KeyAction::TagMonitorDirection),
         "ShowKeybindOverlay" => Ok(KeyAction::ShowKeybindOverlay),
         _ => Err(mlua::Error::RuntimeError(format!(
             "unknown action '{}'. this is an internal error, please report it",
@@ -969,7 +1517,7 @@ fn string_to_action(s: &str) -> mlua::Result<KeyAction> {
     }
 }
 
-fn value_to_arg(value: Value) -> mlua::Result<Arg> {
+pub(crate) fn value_to_arg(value: Value) -> mlua::Result<Arg> {
     match value {
         Value::Nil => Ok(Arg::None),
         Value::String(s) => Ok(Arg::Str(s.to_str()?.to_string())),
@@ -987,7 +1535,50 @@ fn value_to_arg(value: Value) -> mlua::Result<Arg> {
     }
 }
 
+/// Turns a tag bitmask into a list of 0-based tag indices.
+fn tag_indices(lua: &Lua, mask: TagMask) -> mlua::Result<Table> {
+    lua.create_sequence_from((0..TagMask::BITS).filter(|tag| mask & (1 << tag) != 0))
+}
+
+fn with_state<R>(lua: &Lua, f: impl FnOnce(&WmState) -> mlua::Result<R>) -> mlua::Result<R> {
+    match lua.app_data_ref::<WmState>() {
+        Some(state) => f(&state),
+        None => f(&WmState::default()),
+    }
+}
+
+/// Reads a monitor index, an output or model name, or a list of those tried
+/// in order.
+fn parse_monitor_selectors(value: Value, context: &str) -> mlua::Result<Vec<MonitorSelector>> {
+    let invalid = || {
+        mlua::Error::RuntimeError(format!(
+            "{}: monitor must be an index, an output name or a list of those",
+            context
+        ))
+    };
+    let parse_one = |value: Value| match value {
+        Value::Integer(index) if index >= 0 => Ok(MonitorSelector::Index(index as usize)),
+        Value::String(name) => Ok(MonitorSelector::Name(name.to_str()?.to_string())),
+        _ => Err(invalid()),
+    };
+
+    match value {
+        Value::Nil => Ok(Vec::new()),
+        Value::Table(list) => list
+            .sequence_values::<Value>()
+            .map(|item| parse_one(item?))
+            .collect(),
+        other => Ok(vec![parse_one(other)?]),
+    }
+}
+
 fn create_action_table(lua: &Lua, action_name: &str, arg: Value) -> mlua::Result<Table> {
+    // Inside a hook or key function the command runs straight away, so the
+    // rest of the function sees its effect.
+    if let Some(run) = lua.named_registry_value::<Option<Function>>(ACTION_RUNNER)? {
+        run.call::<()>((action_name, arg.clone()))?;
+    }
+
     let table = lua.create_table()?;
     table.set("__action", action_name)?;
     table.set("__arg", arg)?;
@@ -1031,10 +1622,17 @@ fn parse_color_value(value: Value) -> mlua::Result<u32> {
 
 fn create_block_config(
     lua: &Lua,
+    builder: &SharedBuilder,
     config: Table,
     block_type: &str,
     arg: Option<Value>,
+    extra_keys: &[&str],
 ) -> mlua::Result<Table> {
+    let mut allowed = vec!["format", "interval", "color", "underline"];
+    allowed.extend_from_slice(extra_keys);
+    let context = format!("oxwm.bar.block.{}", block_type.to_lowercase());
+    warn_unknown_keys(lua, builder, &config, &context, &allowed)?;
+
     let table = lua.create_table()?;
     table.set("__block_type", block_type)?;
 
@@ -1,4 +1,8 @@
 mod lua;
 mod lua_api;
+pub mod runtime;
+mod watcher;
 
-pub use lua::parse_lua_config;
+pub use lua::{ConfigReport, check_lua_config, load_lua_config, parse_lua_config};
+pub use runtime::LuaRuntime;
+pub use watcher::ConfigWatcher;
@@ -0,0 +1,269 @@
+use crate::client::TagMask;
+use crate::config::lua_api::{string_to_action, value_to_arg};
+use crate::keyboard::{Arg, KeyAction};
+use mlua::{Function, Lua, Table, Value};
+use x11rb::protocol::xproto::Window;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum HookEvent {
+    ClientManage,
+    ClientUnmanage,
+    ClientUrgent,
+    FocusChange,
+    TagView,
+    LayoutChange,
+    MonitorChange,
+    TitleChange,
+}
+
+impl HookEvent {
+    pub const ALL: &'static [HookEvent] = &[
+        HookEvent::ClientManage,
+        HookEvent::ClientUnmanage,
+        HookEvent::ClientUrgent,
+        HookEvent::FocusChange,
+        HookEvent::TagView,
+        HookEvent::LayoutChange,
+        HookEvent::MonitorChange,
+        HookEvent::TitleChange,
+    ];
+
+    pub fn name(self) -> &'static str {
+        match self {
+            HookEvent::ClientManage => "client_manage",
+            HookEvent::ClientUnmanage => "client_unmanage",
+            HookEvent::ClientUrgent => "client_urgent",
+            HookEvent::FocusChange => "focus_change",
+            HookEvent::TagView => "tag_view",
+            HookEvent::LayoutChange => "layout_change",
+            HookEvent::MonitorChange => "monitor_change",
+            HookEvent::TitleChange => "title_change",
+        }
+    }
+
+    pub fn from_name(name: &str) -> Option<Self> {
+        Self::ALL.iter().copied().find(|event| event.name() == name)
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct ClientInfo {
+    pub window: Window,
+    pub name: String,
+    pub class: String,
+    pub instance: String,
+    pub tags: TagMask,
+    pub monitor: usize,
+    pub floating: bool,
+    pub fullscreen: bool,
+    pub urgent: bool,
+    pub x: i16,
+    pub y: i16,
+    pub width: u16,
+    pub height: u16,
+}
+
+impl ClientInfo {
+    pub fn to_table(&self, lua: &Lua) -> mlua::Result<Table> {
+        let table = lua.create_table()?;
+        table.set("window", self.window)?;
+        table.set("name", self.name.as_str())?;
+        table.set("class", self.class.as_str())?;
+        table.set("instance", self.instance.as_str())?;
+        table.set("tags", self.tags)?;
+        table.set("monitor", self.monitor)?;
+        table.set("floating", self.floating)?;
+        table.set("fullscreen", self.fullscreen)?;
+        table.set("urgent", self.urgent)?;
+        table.set("x", self.x)?;
+        table.set("y", self.y)?;
+        table.set("width", self.width)?;
+        table.set("height", self.height)?;
+        Ok(table)
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct MonitorInfo {
+    pub index: usize,
+    pub x: i32,
+    pub y: i32,
+    pub width: i32,
+    pub height: i32,
+    pub output: Option<String>,
+    pub model: Option<String>,
+    pub tagset: TagMask,
+    pub layout: String,
+    pub focused: Option<Window>,
+}
+
+impl MonitorInfo {
+    pub fn to_table(&self, lua: &Lua) -> mlua::Result<Table> {
+        let table = lua.create_table()?;
+        table.set("index", self.index)?;
+        table.set("x", self.x)?;
+        table.set("y", self.y)?;
+        table.set("width", self.width)?;
+        table.set("height", self.height)?;
+        table.set("output", self.output.as_deref())?;
+        table.set("model", self.model.as_deref())?;
+        table.set("tagset", self.tagset)?;
+        table.set("layout", self.layout.as_str())?;
+        table.set("focused", self.focused)?;
+        Ok(table)
+    }
+}
+
+#[derive(Debug, Clone)]
+pub enum HookArg {
+    Client(ClientInfo),
+    Monitor(MonitorInfo),
+    None,
+}
+
+impl HookArg {
+    fn to_value(&self, lua: &Lua) -> mlua::Result<Value> {
+        match self {
+            HookArg::Client(client) => client.to_table(lua).map(Value::Table),
+            HookArg::Monitor(monitor) => monitor.to_table(lua).map(Value::Table),
+            HookArg::None => Ok(Value::Nil),
+        }
+    }
+}
+
+/// What the query functions (`oxwm.client.focused()` and friends) see. The
+/// window manager refreshes it before calling into Lua; while the config is
+/// first evaluated it is empty.
+#[derive(Debug, Clone, Default)]
+pub struct WmState {
+    pub clients: Vec<ClientInfo>,
+    pub monitors: Vec<MonitorInfo>,
+    pub selected_monitor: usize,
+    pub focused: Option<Window>,
+}
+
+impl WmState {
+    pub fn focused_client(&self) -> Option<&ClientInfo> {
+        let focused = self.focused?;
+        self.clients.iter().find(|client| client.window == focused)
+    }
+
+    pub fn selected_monitor(&self) -> Option<&MonitorInfo> {
+        self.monitors.get(self.selected_monitor)
+    }
+
+    /// Looks up `window`, falling back to the focused client when `None`.
+    pub fn client(&self, window: Option<Window>) -> Option<&ClientInfo> {
+        match window {
+            Some(window) => self.clients.iter().find(|client| client.window == window),
+            None => self.focused_client(),
+        }
+    }
+
+    /// Tags that hold at least one client on `monitor`.
+    pub fn occupied_tags(&self, monitor: usize) -> TagMask {
+        self.clients
+            .iter()
+            .filter(|client| client.monitor == monitor)
+            .fold(0, |mask, client| mask | client.tags)
+    }
+}
+
+/// Registry slot for the function that runs commands such as
+/// `oxwm.spawn()` while a hook or key function is executing. Outside of
+/// those, e.g. while the config is first evaluated, it is empty and the
+/// commands only build action tables.
+pub(crate) const ACTION_RUNNER: &str = "oxwm_action_runner";
+
+/// The Lua state a config was evaluated in, kept alive so hooks can run
+/// while the window manager is up.
+pub struct LuaRuntime {
+    lua: Lua,
+    hooks: Vec<(HookEvent, Function)>,
+    callbacks: Vec<Function>,
+}
+
+impl LuaRuntime {
+    pub(crate) fn new(
+        lua: Lua,
+        hooks: Vec<(HookEvent, Function)>,
+        callbacks: Vec<Function>,
+    ) -> Self {
+        Self {
+            lua,
+            hooks,
+            callbacks,
+        }
+    }
+
+    pub fn set_state(&self, state: WmState) {
+        self.lua.set_app_data(state);
+    }
+
+    pub fn has_hooks(&self, event: HookEvent) -> bool {
+        self.hooks.iter().any(|(hook, _)| *hook == event)
+    }
+
+    /// Calls every callback registered for `event`, handing each command
+    /// they issue to `run` as it is issued. A failing callback is reported
+    /// and skipped.
+    pub fn run_hooks<R>(&self, event: HookEvent, arg: &HookArg, run: R)
+    where
+        R: FnMut(KeyAction, Arg),
+    {
+        self.with_runner(run, |lua| {
+            let value = arg.to_value(lua)?;
+            for (_, callback) in self.hooks.iter().filter(|(hook, _)| *hook == event) {
+                if let Err(e) = callback.call::<()>(value.clone()) {
+                    eprintln!("Lua hook '{}' failed: {}", event.name(), e);
+                }
+            }
+            Ok(())
+        });
+    }
+
+    /// Calls the function bound to a key with `oxwm.key.bind`, handing each
+    /// command it issues to `run` as it is issued.
+    pub fn run_callback<R>(&self, index: usize, run: R)
+    where
+        R: FnMut(KeyAction, Arg),
+    {
+        let Some(callback) = self.callbacks.get(index) else {
+            eprintln!("No Lua function registered at index {}", index);
+            return;
+        };
+
+        self.with_runner(run, |_| {
+            if let Err(e) = callback.call::<()>(()) {
+                eprintln!("Lua keybinding failed: {}", e);
+            }
+            Ok(())
+        });
+    }
+
+    /// Runs `f` with `run` installed as the action runner. The previous
+    /// runner is put back afterwards, so a command that ends up calling
+    /// into Lua again doesn't cut the outer function off.
+    fn with_runner<R, F>(&self, mut run: R, f: F)
+    where
+        R: FnMut(KeyAction, Arg),
+        F: FnOnce(&Lua) -> mlua::Result<()>,
+    {
+        let result = self.lua.scope(|scope| {
+            let runner = scope.create_function_mut(|_, (name, arg): (String, Value)| {
+                run(string_to_action(&name)?, value_to_arg(arg)?);
+                Ok(())
+            })?;
+
+            let previous: Value = self.lua.named_registry_value(ACTION_RUNNER)?;
+            self.lua.set_named_registry_value(ACTION_RUNNER, runner)?;
+            let result = f(&self.lua);
+            self.lua.set_named_registry_value(ACTION_RUNNER, previous)?;
+            result
+        });
+
+        if let Err(e) = result {
+            eprintln!("Lua runtime error: {}", e);
+        }
+    }
+}
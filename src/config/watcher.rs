@@ -0,0 +1,95 @@
+use inotify::{Inotify, WatchDescriptor, WatchMask};
+use std::collections::{HashMap, HashSet};
+use std::io::ErrorKind;
+use std::path::{Path, PathBuf};
+use std::time::{Duration, Instant};
+
+/// Editors often write a file in several steps, so reloads wait for the
+/// directory to be quiet for this long.
+const SETTLE_DELAY: Duration = Duration::from_millis(250);
+
+/// Watches the config file and every `require`d module for changes.
+///
+/// Directories are watched rather than the files themselves so that editors
+/// which save by renaming a temporary file over the original are noticed.
+pub struct ConfigWatcher {
+    inotify: Inotify,
+    directories: HashMap<WatchDescriptor, PathBuf>,
+    files: HashSet<PathBuf>,
+    buffer: Vec<u8>,
+    pending_since: Option<Instant>,
+}
+
+impl ConfigWatcher {
+    pub fn new(files: &[PathBuf]) -> std::io::Result<Self> {
+        let mut watcher = Self {
+            inotify: Inotify::init()?,
+            directories: HashMap::new(),
+            files: HashSet::new(),
+            buffer: vec![0; 4096],
+            pending_since: None,
+        };
+
+        for file in files {
+            watcher.watch(file)?;
+        }
+
+        Ok(watcher)
+    }
+
+    fn watch(&mut self, file: &Path) -> std::io::Result<()> {
+        let file = file.canonicalize().unwrap_or_else(|_| file.to_path_buf());
+        let Some(directory) = file.parent().map(Path::to_path_buf) else {
+            return Ok(());
+        };
+
+        if !self.directories.values().any(|d| *d == directory) {
+            let descriptor = self.inotify.watches().add(
+                &directory,
+                WatchMask::CLOSE_WRITE | WatchMask::MOVED_TO | WatchMask::CREATE,
+            )?;
+            self.directories.insert(descriptor, directory);
+        }
+
+        self.files.insert(file);
+        Ok(())
+    }
+
+    /// Drains pending inotify events and returns true once a watched file has
+    /// changed and things have settled down.
+    pub fn poll(&mut self) -> bool {
+        loop {
+            let events = match self.inotify.read_events(&mut self.buffer) {
+                Ok(events) => events,
+                Err(e) if e.kind() == ErrorKind::WouldBlock => break,
+                Err(e) => {
+                    eprintln!("Config watcher error: {}", e);
+                    break;
+                }
+            };
+
+            let mut touched = false;
+            for event in events {
+                let (Some(directory), Some(name)) = (self.directories.get(&event.wd), event.name)
+                else {
+                    continue;
+                };
+                if self.files.contains(&directory.join(name)) {
+                    touched = true;
+                }
+            }
+
+            if touched {
+                self.pending_since = Some(Instant::now());
+            }
+        }
+
+        match self.pending_since {
+            Some(since) if since.elapsed() >= SETTLE_DELAY => {
+                self.pending_since = None;
+                true
+            }
+            _ => false,
+        }
+    }
+}
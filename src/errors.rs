@@ -1,4 +1,5 @@
 use std::io;
+use std::path::PathBuf;
 
 #[derive(Debug)]
 pub enum WmError {
@@ -34,6 +35,17 @@ pub enum ConfigError {
     CouldNotReadConfig(std::io::Error),
 }
 
+#[derive(Debug)]
+pub enum IpcError {
+    Io(io::Error),
+    NoSocketPath,
+    AlreadyRunning(PathBuf),
+    PathInUse(PathBuf),
+    InvalidRequest(String),
+    UnknownCommand(String),
+    InvalidArgument { command: String, expected: String },
+}
+
 #[derive(Debug)]
 pub enum BlockError {
     Io(io::Error),
@@ -51,8 +63,12 @@ pub enum MainError {
     FailedReadConfigTemplate(ConfigError),
     CouldNotStartWm(WmError),
     WmError(WmError),
+    IpcError(IpcError),
+    CommandFailed(String),
+    ConfigCheckFailed(usize),
     BadConfigPath,
     NoConfigPath,
+    NoCommand,
     InvalidArguments,
     NoProgramName,
     NoConfigDir,
@@ -110,6 +126,33 @@ impl std::fmt::Display for ConfigError {
     }
 }
 
+impl std::fmt::Display for IpcError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Io(err) => write!(f, "IPC I/O error: {}", err),
+            Self::NoSocketPath => write!(
+                f,
+                "Could not determine the IPC socket path, is DISPLAY set?"
+            ),
+            Self::AlreadyRunning(path) => write!(
+                f,
+                "Another oxwm instance is already listening on {}",
+                path.display()
+            ),
+            Self::PathInUse(path) => write!(
+                f,
+                "{} exists and is not a socket owned by this user",
+                path.display()
+            ),
+            Self::InvalidRequest(msg) => write!(f, "Invalid request: {}", msg),
+            Self::UnknownCommand(command) => write!(f, "Unknown command '{}'", command),
+            Self::InvalidArgument { command, expected } => {
+                write!(f, "{} expects {}", command, expected)
+            }
+        }
+    }
+}
+
 impl std::fmt::Display for BlockError {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         match self {
@@ -146,6 +189,12 @@ impl From<BlockError> for WmError {
     }
 }
 
+impl From<io::Error> for IpcError {
+    fn from(value: io::Error) -> Self {
+        IpcError::Io(value)
+    }
+}
+
 impl From<io::Error> for BlockError {
     fn from(value: io::Error) -> Self {
         BlockError::Io(value)
@@ -211,8 +260,12 @@ impl std::fmt::Debug for MainError {
             }
             FailedReadConfigTemplate(e) => write!(f, "{e}"),
             CouldNotStartWm(e) | WmError(e) => write!(f, "{e}"),
+            IpcError(e) => write!(f, "{e}"),
+            CommandFailed(msg) => write!(f, "{msg}"),
+            ConfigCheckFailed(count) => write!(f, "Config check failed with {count} error(s)"),
             BadConfigPath => write!(f, "Given config path does not exist"),
             NoConfigPath => write!(f, "The --config switch requires a path value"),
+            NoCommand => write!(f, "The msg subcommand requires a command, try --help"),
             InvalidArguments => write!(f, "The arguments given are invalid try --help"),
             NoProgramName => write!(f, "Could not get the program name from the environment"),
             NoConfigDir => write!(f, "Could not get the config dir"),
@@ -0,0 +1,60 @@
+use crate::errors::IpcError;
+use crate::ipc::IpcReply;
+use std::io::{BufRead, BufReader, Write};
+use std::os::unix::net::UnixStream;
+use std::path::Path;
+use std::time::Duration;
+
+const REPLY_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// Sends one request line to a running oxwm and waits for its reply.
+pub fn send_request(path: &Path, request: &str) -> Result<IpcReply, IpcError> {
+    let mut stream = UnixStream::connect(path)?;
+    stream.set_read_timeout(Some(REPLY_TIMEOUT))?;
+
+    stream.write_all(request.trim_end().as_bytes())?;
+    stream.write_all(b"\n")?;
+    stream.flush()?;
+
+    let mut line = String::new();
+    BufReader::new(&stream).read_line(&mut line)?;
+
+    if line.trim().is_empty() {
+        return Err(IpcError::InvalidRequest(
+            "oxwm closed the connection without replying".to_string(),
+        ));
+    }
+
+    serde_json::from_str(&line).map_err(|err| IpcError::InvalidRequest(err.to_string()))
+}
+
+/// Sends a `subscribe` request and returns the stream of event lines that
+/// follows the acknowledgement.
+pub fn subscribe(
+    path: &Path,
+    request: &str,
+) -> Result<impl Iterator<Item = std::io::Result<String>>, IpcError> {
+    let mut stream = UnixStream::connect(path)?;
+    stream.set_read_timeout(Some(REPLY_TIMEOUT))?;
+
+    stream.write_all(request.trim_end().as_bytes())?;
+    stream.write_all(b"\n")?;
+    stream.flush()?;
+
+    let mut reader = BufReader::new(stream);
+    let mut line = String::new();
+    reader.read_line(&mut line)?;
+
+    let reply: IpcReply =
+        serde_json::from_str(&line).map_err(|err| IpcError::InvalidRequest(err.to_string()))?;
+    if !reply.success {
+        return Err(IpcError::InvalidRequest(
+            reply
+                .error
+                .unwrap_or_else(|| String::from("subscription refused")),
+        ));
+    }
+
+    reader.get_ref().set_read_timeout(None)?;
+    Ok(reader.lines())
+}
@@ -0,0 +1,264 @@
+use crate::errors::IpcError;
+use crate::ipc::IpcEvent;
+use crate::keyboard::{Arg, KeyAction};
+use crate::monitor::Direction;
+use serde::{Deserialize, Serialize};
+
+const ACTIONS: &[KeyAction] = &[
+    KeyAction::Spawn,
+    KeyAction::SpawnTerminal,
+    KeyAction::KillClient,
+    KeyAction::FocusStack,
+    KeyAction::MoveStack,
+    KeyAction::Quit,
+    KeyAction::Restart,
+    KeyAction::ViewTag,
+    KeyAction::ViewNextTag,
+    KeyAction::ViewPreviousTag,
+    KeyAction::ViewNextNonEmptyTag,
+    KeyAction::ViewPreviousNonEmptyTag,
+    KeyAction::ToggleView,
+    KeyAction::MoveToTag,
+    KeyAction::ToggleTag,
+    KeyAction::ToggleGaps,
+    KeyAction::ToggleFullScreen,
+    KeyAction::ToggleFloating,
+    KeyAction::ChangeLayout,
+    KeyAction::CycleLayout,
+    KeyAction::FocusMonitor,
+    KeyAction::TagMonitor,
+    KeyAction::FocusMonitorDirection,
+    KeyAction::TagMonitorDirection,
+    KeyAction::ShowKeybindOverlay,
+    KeyAction::SetMasterFactor,
+    KeyAction::IncNumMaster,
+    KeyAction::ScrollLeft,
+    KeyAction::ScrollRight,
+];
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum ArgKind {
+    None,
+    Int,
+    Str,
+    Monitor,
+    Direction,
+    Command,
+}
+
+impl ArgKind {
+    fn of(action: KeyAction) -> Self {
+        match action {
+            KeyAction::FocusStack
+            | KeyAction::MoveStack
+            | KeyAction::ViewTag
+            | KeyAction::ToggleView
+            | KeyAction::MoveToTag
+            | KeyAction::ToggleTag
+            | KeyAction::SetMasterFactor
+            | KeyAction::IncNumMaster => ArgKind::Int,
+            KeyAction::ChangeLayout => ArgKind::Str,
+            KeyAction::FocusMonitor | KeyAction::TagMonitor => ArgKind::Monitor,
+            KeyAction::FocusMonitorDirection | KeyAction::TagMonitorDirection => ArgKind::Direction,
+            KeyAction::Spawn => ArgKind::Command,
+            _ => ArgKind::None,
+        }
+    }
+
+    fn describe(self) -> &'static str {
+        match self {
+            ArgKind::None => "no argument",
+            ArgKind::Int => "an integer argument",
+            ArgKind::Str => "a string argument",
+            ArgKind::Monitor => "a direction or an output name",
+            ArgKind::Direction => "one of left, right, up or down",
+            ArgKind::Command => "a command to run",
+        }
+    }
+}
+
+/// A request received over the control socket.
+#[derive(Debug, Clone)]
+pub enum IpcCommand {
+    Action(KeyAction, Arg),
+    GetTree,
+    /// Turns the connection into an event stream. An empty list means
+    /// every event.
+    Subscribe(Vec<String>),
+}
+
+impl IpcCommand {
+    /// Parses a single request line, either JSON
+    /// (`{"command": "view_tag", "arg": 2}`) or whitespace separated text
+    /// (`view_tag 2`).
+    pub fn parse(line: &str) -> Result<Self, IpcError> {
+        let line = line.trim();
+        if line.is_empty() {
+            return Err(IpcError::InvalidRequest("empty request".to_string()));
+        }
+
+        if line.starts_with('{') {
+            Self::parse_json(line)
+        } else {
+            let mut words = line.split_whitespace();
+            let name = words.next().unwrap_or_default();
+            let args: Vec<String> = words.map(str::to_string).collect();
+            Self::from_text(name, &args)
+        }
+    }
+
+    fn parse_json(line: &str) -> Result<Self, IpcError> {
+        let value: serde_json::Value =
+            serde_json::from_str(line).map_err(|err| IpcError::InvalidRequest(err.to_string()))?;
+
+        let name = value
+            .get("command")
+            .and_then(|command| command.as_str())
+            .ok_or_else(|| IpcError::InvalidRequest("missing \"command\" field".to_string()))?;
+
+        let arg = match value.get("arg") {
+            None | Some(serde_json::Value::Null) => Vec::new(),
+            Some(serde_json::Value::Array(items)) => items
+                .iter()
+                .map(json_to_string)
+                .collect::<Option<Vec<_>>>()
+                .ok_or_else(|| {
+                    IpcError::InvalidRequest("\"arg\" array must hold scalars".to_string())
+                })?,
+            Some(other) => vec![json_to_string(other).ok_or_else(|| {
+                IpcError::InvalidRequest("\"arg\" must be a scalar or an array".to_string())
+            })?],
+        };
+
+        Self::from_text(name, &arg)
+    }
+
+    fn from_text(name: &str, args: &[String]) -> Result<Self, IpcError> {
+        if normalize(name) == "gettree" {
+            if !args.is_empty() {
+                return Err(IpcError::InvalidArgument {
+                    command: name.to_string(),
+                    expected: ArgKind::None.describe().to_string(),
+                });
+            }
+            return Ok(IpcCommand::GetTree);
+        }
+
+        if normalize(name) == "subscribe" {
+            let mut events = Vec::with_capacity(args.len());
+            for event in args {
+                let event = event.to_lowercase().replace('-', "_");
+                if !IpcEvent::NAMES.contains(&event.as_str()) {
+                    return Err(IpcError::InvalidArgument {
+                        command: name.to_string(),
+                        expected: format!("event names from: {}", IpcEvent::NAMES.join(", ")),
+                    });
+                }
+                events.push(event);
+            }
+            return Ok(IpcCommand::Subscribe(events));
+        }
+
+        let action =
+            action_from_name(name).ok_or_else(|| IpcError::UnknownCommand(name.to_string()))?;
+        let kind = ArgKind::of(action);
+
+        let invalid = || IpcError::InvalidArgument {
+            command: name.to_string(),
+            expected: kind.describe().to_string(),
+        };
+
+        let arg = match (kind, args) {
+            (ArgKind::None, []) => Arg::None,
+            (ArgKind::Int, [value]) => Arg::Int(value.parse().map_err(|_| invalid())?),
+            (ArgKind::Str, [value]) => Arg::Str(value.clone()),
+            (ArgKind::Monitor, [value]) => match value.parse() {
+                Ok(direction) => Arg::Int(direction),
+                Err(_) => Arg::Str(value.clone()),
+            },
+            (ArgKind::Direction, [value]) => {
+                value.parse::<Direction>().map_err(|_| invalid())?;
+                Arg::Str(value.clone())
+            }
+            (ArgKind::Command, [value]) => Arg::Str(value.clone()),
+            (ArgKind::Command, [_, ..]) => Arg::Array(args.to_vec()),
+            _ => return Err(invalid()),
+        };
+
+        Ok(IpcCommand::Action(action, arg))
+    }
+}
+
+fn json_to_string(value: &serde_json::Value) -> Option<String> {
+    match value {
+        serde_json::Value::String(s) => Some(s.clone()),
+        serde_json::Value::Number(n) => Some(n.to_string()),
+        serde_json::Value::Bool(b) => Some(b.to_string()),
+        _ => None,
+    }
+}
+
+fn normalize(name: &str) -> String {
+    name.chars()
+        .filter(|c| *c != '_' && *c != '-')
+        .flat_map(char::to_lowercase)
+        .collect()
+}
+
+/// Matches `view_tag`, `view-tag`, `viewtag` and `ViewTag` alike.
+pub fn action_from_name(name: &str) -> Option<KeyAction> {
+    let wanted = normalize(name);
+    ACTIONS
+        .iter()
+        .copied()
+        .find(|action| normalize(&format!("{:?}", action)) == wanted)
+}
+
+/// One reply line sent back for every request.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct IpcReply {
+    pub success: bool,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub data: Option<serde_json::Value>,
+}
+
+impl IpcReply {
+    pub fn ok() -> Self {
+        Self {
+            success: true,
+            error: None,
+            data: None,
+        }
+    }
+
+    pub fn with_data(data: serde_json::Value) -> Self {
+        Self {
+            success: true,
+            error: None,
+            data: Some(data),
+        }
+    }
+
+    pub fn error(message: impl Into<String>) -> Self {
+        Self {
+            success: false,
+            error: Some(message.into()),
+            data: None,
+        }
+    }
+
+    pub fn to_line(&self) -> String {
+        let mut line =
+            serde_json::to_string(self).unwrap_or_else(|_| String::from("{\"success\":false}"));
+        line.push('\n');
+        line
+    }
+}
+
+impl From<IpcError> for IpcReply {
+    fn from(err: IpcError) -> Self {
+        IpcReply::error(err.to_string())
+    }
+}
@@ -0,0 +1,74 @@
+use crate::client::TagMask;
+use serde::Serialize;
+use x11rb::protocol::xproto::Window;
+
+/// State changes pushed to subscribed IPC clients, one JSON object per line.
+#[derive(Debug, Clone, Serialize)]
+#[serde(tag = "event", rename_all = "snake_case")]
+pub enum IpcEvent {
+    Tag {
+        monitor: usize,
+        tagset: TagMask,
+    },
+    Focus {
+        monitor: usize,
+        window: Option<Window>,
+    },
+    Manage {
+        window: Window,
+        monitor: usize,
+        tags: TagMask,
+    },
+    Unmanage {
+        window: Window,
+    },
+    Layout {
+        monitor: usize,
+        layout: String,
+    },
+    Title {
+        window: Window,
+        name: String,
+    },
+    Urgent {
+        window: Window,
+        urgent: bool,
+    },
+    ConfigReload {
+        success: bool,
+        #[serde(skip_serializing_if = "Option::is_none")]
+        error: Option<String>,
+    },
+}
+
+impl IpcEvent {
+    pub const NAMES: &'static [&'static str] = &[
+        "tag",
+        "focus",
+        "manage",
+        "unmanage",
+        "layout",
+        "title",
+        "urgent",
+        "config_reload",
+    ];
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            IpcEvent::Tag { .. } => "tag",
+            IpcEvent::Focus { .. } => "focus",
+            IpcEvent::Manage { .. } => "manage",
+            IpcEvent::Unmanage { .. } => "unmanage",
+            IpcEvent::Layout { .. } => "layout",
+            IpcEvent::Title { .. } => "title",
+            IpcEvent::Urgent { .. } => "urgent",
+            IpcEvent::ConfigReload { .. } => "config_reload",
+        }
+    }
+
+    pub fn to_line(&self) -> String {
+        let mut line = serde_json::to_string(self).unwrap_or_default();
+        line.push('\n');
+        line
+    }
+}
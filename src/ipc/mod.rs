@@ -0,0 +1,35 @@
+use std::path::PathBuf;
+
+pub mod client;
+pub mod command;
+pub mod event;
+pub mod server;
+pub mod tree;
+
+pub use client::{send_request, subscribe};
+pub use command::{IpcCommand, IpcReply};
+pub use event::IpcEvent;
+pub use server::{ClientId, IpcServer};
+pub use tree::Tree;
+
+pub const SOCKET_ENV: &str = "OXWM_SOCKET";
+
+/// Resolves the control socket for the current display.
+///
+/// `$OXWM_SOCKET` wins if set, otherwise the socket lives in
+/// `$XDG_RUNTIME_DIR` (or the temp dir) as `oxwm-$DISPLAY.sock`.
+pub fn socket_path() -> Option<PathBuf> {
+    if let Ok(path) = std::env::var(SOCKET_ENV)
+        && !path.is_empty()
+    {
+        return Some(PathBuf::from(path));
+    }
+
+    let display = std::env::var("DISPLAY").ok().filter(|d| !d.is_empty())?;
+    let runtime_dir = std::env::var_os("XDG_RUNTIME_DIR")
+        .map(PathBuf::from)
+        .unwrap_or_else(std::env::temp_dir);
+
+    let display = display.replace('/', "_");
+    Some(runtime_dir.join(format!("oxwm-{}.sock", display)))
+}
@@ -0,0 +1,234 @@
+use crate::errors::IpcError;
+use crate::ipc::IpcEvent;
+use std::collections::HashMap;
+use std::fs::Permissions;
+use std::io::{ErrorKind, Read, Write};
+use std::os::unix::fs::{FileTypeExt, MetadataExt, PermissionsExt};
+use std::os::unix::net::{UnixListener, UnixStream};
+use std::path::{Path, PathBuf};
+
+const MAX_LINE_LENGTH: usize = 64 * 1024;
+const MAX_PENDING_OUTPUT: usize = 1024 * 1024;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct ClientId(u64);
+
+struct Connection {
+    stream: UnixStream,
+    read_buffer: Vec<u8>,
+    write_buffer: Vec<u8>,
+    subscriptions: Option<Vec<String>>,
+    eof: bool,
+    closed: bool,
+}
+
+impl Connection {
+    fn new(stream: UnixStream) -> Self {
+        Self {
+            stream,
+            read_buffer: Vec::new(),
+            write_buffer: Vec::new(),
+            subscriptions: None,
+            eof: false,
+            closed: false,
+        }
+    }
+
+    fn read_lines(&mut self, lines: &mut Vec<String>) {
+        let mut chunk = [0u8; 4096];
+        while !self.eof && !self.closed {
+            match self.stream.read(&mut chunk) {
+                Ok(0) => {
+                    self.eof = true;
+                    break;
+                }
+                Ok(n) => self.read_buffer.extend_from_slice(&chunk[..n]),
+                Err(e) if e.kind() == ErrorKind::WouldBlock => break,
+                Err(e) if e.kind() == ErrorKind::Interrupted => continue,
+                Err(_) => {
+                    self.closed = true;
+                    break;
+                }
+            }
+        }
+
+        while let Some(newline) = self.read_buffer.iter().position(|&b| b == b'\n') {
+            let line: Vec<u8> = self.read_buffer.drain(..=newline).collect();
+            let line = String::from_utf8_lossy(&line[..newline]);
+            if !line.trim().is_empty() {
+                lines.push(line.into_owned());
+            }
+        }
+
+        // A peer that shuts down its write side without a trailing newline
+        // still gets its last request handled.
+        if self.eof && !self.read_buffer.is_empty() {
+            let line = String::from_utf8_lossy(&self.read_buffer).into_owned();
+            self.read_buffer.clear();
+            if !line.trim().is_empty() {
+                lines.push(line);
+            }
+        }
+
+        if self.read_buffer.len() > MAX_LINE_LENGTH {
+            self.closed = true;
+        }
+    }
+
+    /// Subscribers stay connected after shutting down their write side.
+    fn is_finished(&self) -> bool {
+        self.closed || (self.eof && self.subscriptions.is_none() && self.write_buffer.is_empty())
+    }
+
+    fn wants(&self, event: &IpcEvent) -> bool {
+        match &self.subscriptions {
+            Some(events) => events.is_empty() || events.iter().any(|name| name == event.name()),
+            None => false,
+        }
+    }
+
+    fn flush(&mut self) {
+        while !self.write_buffer.is_empty() {
+            match self.stream.write(&self.write_buffer) {
+                Ok(0) => {
+                    self.closed = true;
+                    return;
+                }
+                Ok(n) => {
+                    self.write_buffer.drain(..n);
+                }
+                Err(e) if e.kind() == ErrorKind::WouldBlock => return,
+                Err(e) if e.kind() == ErrorKind::Interrupted => continue,
+                Err(_) => {
+                    self.closed = true;
+                    return;
+                }
+            }
+        }
+    }
+}
+
+/// Non-blocking line based control socket, polled from the main loop.
+pub struct IpcServer {
+    listener: UnixListener,
+    path: PathBuf,
+    connections: HashMap<ClientId, Connection>,
+    next_id: u64,
+}
+
+impl IpcServer {
+    pub fn bind(path: &Path) -> Result<Self, IpcError> {
+        if let Ok(metadata) = std::fs::symlink_metadata(path) {
+            if UnixStream::connect(path).is_ok() {
+                return Err(IpcError::AlreadyRunning(path.to_path_buf()));
+            }
+            // Only clear away a stale socket of our own, so a mistyped
+            // $OXWM_SOCKET can't delete somebody's file.
+            let uid = unsafe { libc::getuid() };
+            if !metadata.file_type().is_socket() || metadata.uid() != uid {
+                return Err(IpcError::PathInUse(path.to_path_buf()));
+            }
+            std::fs::remove_file(path)?;
+        }
+
+        if let Some(parent) = path.parent() {
+            std::fs::create_dir_all(parent)?;
+        }
+
+        let listener = UnixListener::bind(path)?;
+        // Anyone who can connect can spawn commands as us, and the socket
+        // may live in the shared temp dir.
+        std::fs::set_permissions(path, Permissions::from_mode(0o600))?;
+        listener.set_nonblocking(true)?;
+
+        Ok(Self {
+            listener,
+            path: path.to_path_buf(),
+            connections: HashMap::new(),
+            next_id: 0,
+        })
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    /// Accepts new connections, flushes pending replies and returns every
+    /// complete request line received since the last call.
+    pub fn poll(&mut self) -> Vec<(ClientId, String)> {
+        loop {
+            match self.listener.accept() {
+                Ok((stream, _)) => {
+                    if stream.set_nonblocking(true).is_err() {
+                        continue;
+                    }
+                    let id = ClientId(self.next_id);
+                    self.next_id += 1;
+                    self.connections.insert(id, Connection::new(stream));
+                }
+                Err(e) if e.kind() == ErrorKind::Interrupted => continue,
+                Err(_) => break,
+            }
+        }
+
+        // Finished connections are pruned before reading so a request that
+        // arrives together with EOF still gets its reply queued.
+        self.connections
+            .retain(|_, connection| !connection.is_finished());
+
+        let mut requests = Vec::new();
+        for (&id, connection) in self.connections.iter_mut() {
+            connection.flush();
+            let mut lines = Vec::new();
+            connection.read_lines(&mut lines);
+            requests.extend(lines.into_iter().map(|line| (id, line)));
+        }
+
+        requests
+    }
+
+    /// Queues a reply for `client`. Clients that stop reading are dropped
+    /// once their backlog grows too large, so a stalled subscriber can never
+    /// hold up the event loop.
+    pub fn send(&mut self, client: ClientId, line: &str) {
+        let Some(connection) = self.connections.get_mut(&client) else {
+            return;
+        };
+
+        connection.write_buffer.extend_from_slice(line.as_bytes());
+        connection.flush();
+
+        if connection.write_buffer.len() > MAX_PENDING_OUTPUT {
+            self.connections.remove(&client);
+        }
+    }
+
+    pub fn subscribe(&mut self, client: ClientId, events: Vec<String>) {
+        if let Some(connection) = self.connections.get_mut(&client) {
+            connection.subscriptions = Some(events);
+        }
+    }
+
+    /// Queues `event` for every interested subscriber without blocking.
+    pub fn broadcast(&mut self, event: &IpcEvent) {
+        if !self.connections.values().any(|c| c.wants(event)) {
+            return;
+        }
+
+        let line = event.to_line();
+        self.connections.retain(|_, connection| {
+            if !connection.wants(event) {
+                return true;
+            }
+            connection.write_buffer.extend_from_slice(line.as_bytes());
+            connection.flush();
+            !connection.closed && connection.write_buffer.len() <= MAX_PENDING_OUTPUT
+        });
+    }
+}
+
+impl Drop for IpcServer {
+    fn drop(&mut self) {
+        let _ = std::fs::remove_file(&self.path);
+    }
+}
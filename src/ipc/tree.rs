@@ -0,0 +1,91 @@
+use crate::client::{Client, TagMask};
+use crate::monitor::Monitor;
+use serde::Serialize;
+use x11rb::protocol::xproto::Window;
+
+/// Snapshot of the window manager state returned by `get_tree`.
+///
+/// The node types spell out the fields scripts may rely on, so the internal
+/// `Client` and `Monitor` structs can change without breaking them.
+#[derive(Debug, Serialize)]
+pub struct Tree<'a> {
+    pub tags: &'a [String],
+    pub selected_monitor: usize,
+    pub focused: Option<Window>,
+    pub monitors: Vec<MonitorNode>,
+    pub clients: Vec<ClientNode>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct MonitorNode {
+    pub index: usize,
+    pub output: Option<String>,
+    pub model: Option<String>,
+    pub x: i32,
+    pub y: i32,
+    pub width: i32,
+    pub height: i32,
+    pub tagset: TagMask,
+    pub selected_client: Option<Window>,
+    pub layout: String,
+    pub master_factor: f32,
+    pub num_master: i32,
+    pub scroll_offset: i32,
+}
+
+impl MonitorNode {
+    pub fn new(index: usize, monitor: &Monitor, layout: String) -> Self {
+        Self {
+            index,
+            output: monitor.output.clone(),
+            model: monitor.model.clone(),
+            x: monitor.screen_x,
+            y: monitor.screen_y,
+            width: monitor.screen_width,
+            height: monitor.screen_height,
+            tagset: monitor.get_selected_tag(),
+            selected_client: monitor.selected_client,
+            layout,
+            master_factor: monitor.master_factor,
+            num_master: monitor.num_master,
+            scroll_offset: monitor.scroll_offset,
+        }
+    }
+}
+
+#[derive(Debug, Serialize)]
+pub struct ClientNode {
+    pub window: Window,
+    pub name: String,
+    pub class: String,
+    pub instance: String,
+    pub monitor: usize,
+    pub tags: TagMask,
+    pub is_floating: bool,
+    pub is_fullscreen: bool,
+    pub is_urgent: bool,
+    pub x: i16,
+    pub y: i16,
+    pub width: u16,
+    pub height: u16,
+}
+
+impl ClientNode {
+    pub fn new(client: &Client) -> Self {
+        Self {
+            window: client.window,
+            name: client.name.clone(),
+            class: client.class.clone(),
+            instance: client.instance.clone(),
+            monitor: client.monitor_index,
+            tags: client.tags,
+            is_floating: client.is_floating,
+            is_fullscreen: client.is_fullscreen,
+            is_urgent: client.is_urgent,
+            x: client.x_position,
+            y: client.y_position,
+            width: client.width,
+            height: client.height,
+        }
+    }
+}
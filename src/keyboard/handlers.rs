@@ -38,11 +38,16 @@ pub enum KeyAction {
     CycleLayout,
     FocusMonitor,
     TagMonitor,
+    FocusMonitorDirection,
+    TagMonitorDirection,
     ShowKeybindOverlay,
     SetMasterFactor,
     IncNumMaster,
     ScrollLeft,
     ScrollRight,
+    /// Calls a Lua function bound with `oxwm.key.bind`. The argument is its
+    /// index in the runtime's callback list.
+    LuaFunction,
     None,
 }
 
@@ -338,15 +343,19 @@ fn handle_next_key(
     }
 }
 
-pub fn handle_spawn_action(action: KeyAction, arg: &Arg, selected_monitor: usize) -> Result<()> {
+pub fn handle_spawn_action(
+    action: KeyAction,
+    arg: &Arg,
+    selected_monitor: usize,
+) -> Result<Option<crate::signal::Launch>> {
     if let KeyAction::Spawn = action {
         match arg {
             Arg::Str(command) => {
-                crate::signal::spawn_detached(command);
+                return Ok(crate::signal::spawn_detached(command));
             }
             Arg::Array(command) => {
                 let Some((cmd, args)) = command.split_first() else {
-                    return Ok(());
+                    return Ok(None);
                 };
 
                 let mut args_vec: Vec<String> = args.to_vec();
@@ -360,11 +369,11 @@ pub fn handle_spawn_action(action: KeyAction, arg: &Arg, selected_monitor: usize
                 }
 
                 let args_str: Vec<&str> = args_vec.iter().map(|s| s.as_str()).collect();
-                crate::signal::spawn_detached_with_args(cmd, &args_str);
+                return Ok(crate::signal::spawn_detached_with_args(cmd, &args_str));
             }
             _ => {}
         }
     }
 
-    Ok(())
+    Ok(None)
 }
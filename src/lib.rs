@@ -1,10 +1,13 @@
+use crate::monitor::{Monitor, MonitorProfile, MonitorSelector};
 use std::path::PathBuf;
+use std::str::FromStr;
 
 pub mod animations;
 pub mod bar;
 pub mod client;
 pub mod config;
 pub mod errors;
+pub mod ipc;
 pub mod keyboard;
 pub mod layout;
 pub mod monitor;
@@ -15,6 +18,7 @@ pub mod tab_bar;
 pub mod window_manager;
 
 pub mod prelude {
+    pub use crate::ActivationPolicy;
     pub use crate::ColorScheme;
     pub use crate::LayoutSymbolOverride;
     pub use crate::WindowRule;
@@ -29,6 +33,49 @@ pub struct LayoutSymbolOverride {
     pub symbol: String,
 }
 
+/// What to do when a client asks to be activated through
+/// `_NET_ACTIVE_WINDOW`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum ActivationPolicy {
+    /// Only mark the client urgent.
+    #[default]
+    Urgent,
+    /// View the client's tag on its monitor and focus it.
+    Focus,
+    /// Focus the client if it is already visible, otherwise mark it urgent.
+    FocusIfVisible,
+    /// Move the client to the current tag and monitor and focus it.
+    Bring,
+}
+
+impl ActivationPolicy {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Self::Urgent => "urgent",
+            Self::Focus => "focus",
+            Self::FocusIfVisible => "focus_if_visible",
+            Self::Bring => "bring",
+        }
+    }
+}
+
+impl FromStr for ActivationPolicy {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, String> {
+        match s.to_lowercase().as_str() {
+            "urgent" => Ok(Self::Urgent),
+            "focus" => Ok(Self::Focus),
+            "focus_if_visible" => Ok(Self::FocusIfVisible),
+            "bring" => Ok(Self::Bring),
+            _ => Err(format!(
+                "Invalid activation policy: {} (expected urgent, focus, focus_if_visible or bring)",
+                s
+            )),
+        }
+    }
+}
+
 #[derive(Debug, Clone)]
 pub struct WindowRule {
     pub class: Option<String>,
@@ -37,7 +84,10 @@ pub struct WindowRule {
     pub tags: Option<u32>,
     pub focus: Option<bool>,
     pub is_floating: Option<bool>,
-    pub monitor: Option<usize>,
+    /// Monitors to try in order; the first one connected wins. Empty leaves
+    /// the window on the monitor it would open on anyway.
+    pub monitor: Vec<MonitorSelector>,
+    pub activation: Option<ActivationPolicy>,
 }
 
 impl WindowRule {
@@ -58,6 +108,42 @@ impl WindowRule {
     }
 }
 
+/// How tags relate across monitors.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum SharedTags {
+    /// Every monitor has its own, unrelated set of tags.
+    #[default]
+    Off,
+    /// Tags are shared and each is shown on at most one monitor. Viewing a
+    /// tag shown elsewhere swaps it with the current one.
+    Greedy,
+    /// Like `Greedy`, but viewing a tag shown elsewhere focuses the monitor
+    /// showing it instead.
+    Focus,
+}
+
+impl SharedTags {
+    pub fn is_enabled(&self) -> bool {
+        *self != Self::Off
+    }
+}
+
+impl FromStr for SharedTags {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, String> {
+        match s.to_lowercase().as_str() {
+            "off" => Ok(Self::Off),
+            "greedy" => Ok(Self::Greedy),
+            "focus" => Ok(Self::Focus),
+            _ => Err(format!(
+                "Invalid shared tags mode: {} (expected off, greedy or focus)",
+                s
+            )),
+        }
+    }
+}
+
 #[derive(Debug, Clone)]
 pub struct Config {
     // Meta
@@ -67,6 +153,7 @@ pub struct Config {
     pub border_width: u32,
     pub border_focused: u32,
     pub border_unfocused: u32,
+    pub border_unresponsive: u32,
     pub font: String,
 
     // Gaps
@@ -83,6 +170,7 @@ pub struct Config {
 
     // Tags
     pub tags: Vec<String>,
+    pub shared_tags: SharedTags,
 
     // Layout symbol overrides
     pub layout_symbols: Vec<LayoutSymbolOverride>,
@@ -93,6 +181,7 @@ pub struct Config {
 
     // Window rules
     pub window_rules: Vec<WindowRule>,
+    pub activation_policy: ActivationPolicy,
 
     // Status bar
     pub status_blocks: Vec<crate::bar::BlockConfig>,
@@ -106,6 +195,32 @@ pub struct Config {
     pub autostart: Vec<String>,
     pub auto_tile: bool,
     pub hide_vacant_tags: bool,
+    /// Show the icons of each tag's clients next to its label in the bar.
+    pub tag_icons: bool,
+    /// Monitors whose bar may host the system tray, tried in order. Empty
+    /// disables the tray.
+    pub systray_monitor: Vec<MonitorSelector>,
+
+    // Monitors
+    /// Profiles from `oxwm.monitor.configure`, in the order they were set.
+    pub monitor_profiles: Vec<(MonitorSelector, MonitorProfile)>,
+    /// Move the pointer to the middle of a monitor focused from the keyboard.
+    pub warp_pointer_to_monitor: bool,
+
+    // Live reload
+    pub auto_reload: bool,
+    pub required_modules: Vec<PathBuf>,
+}
+
+impl Config {
+    /// The profile set last for the monitor at `index`, by index or by name.
+    pub fn monitor_profile(&self, index: usize, monitor: &Monitor) -> Option<&MonitorProfile> {
+        self.monitor_profiles
+            .iter()
+            .rev()
+            .find(|(selector, _)| selector.matches(index, monitor))
+            .map(|(_, profile)| profile)
+    }
 }
 
 #[derive(Debug, Clone, Copy)]
@@ -131,6 +246,7 @@ impl Default for Config {
             border_width: 2,
             border_focused: 0x6dade3,
             border_unfocused: 0xbbbbbb,
+            border_unresponsive: 0xff5555,
             font: "monospace:size=10".to_string(),
             gaps_enabled: false,
             smartgaps_enabled: true,
@@ -144,6 +260,7 @@ impl Default for Config {
                 .into_iter()
                 .map(String::from)
                 .collect(),
+            shared_tags: SharedTags::Off,
             layout_symbols: vec![],
             keybindings: vec![
                 KeyBinding::single_key(
@@ -327,6 +444,7 @@ impl Default for Config {
             ],
             tag_back_and_forth: false,
             window_rules: vec![],
+            activation_policy: ActivationPolicy::default(),
             status_blocks: vec![crate::bar::BlockConfig {
                 format: "{}".to_string(),
                 command: crate::bar::BlockCommand::DateTime("%a, %b %d - %-I:%M %P".to_string()),
@@ -357,6 +475,12 @@ impl Default for Config {
             autostart: vec![],
             auto_tile: false,
             hide_vacant_tags: false,
+            tag_icons: false,
+            systray_monitor: vec![MonitorSelector::Index(0)],
+            monitor_profiles: Vec::new(),
+            warp_pointer_to_monitor: false,
+            auto_reload: false,
+            required_modules: Vec::new(),
         }
     }
 }
@@ -1,11 +1,197 @@
+use crate::Config;
 use crate::client::TagMask;
 use crate::errors::WmError;
+use std::str::FromStr;
+use x11rb::protocol::randr::{self, ConnectionExt as _};
 use x11rb::protocol::xinerama::ConnectionExt as _;
-use x11rb::protocol::xproto::{Screen, Window};
+use x11rb::protocol::xproto::{AtomEnum, ConnectionExt as _, Screen, Window};
 use x11rb::rust_connection::RustConnection;
 
 type WmResult<T> = Result<T, WmError>;
 
+const DEFAULT_MASTER_FACTOR: f32 = 0.55;
+const DEFAULT_NUM_MASTER: i32 = 1;
+
+/// Space a dock reserves along the screen edges, as read from
+/// `_NET_WM_STRUT_PARTIAL` (or `_NET_WM_STRUT`, which covers whole edges).
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct Strut {
+    pub left: u32,
+    pub right: u32,
+    pub top: u32,
+    pub bottom: u32,
+    pub left_start_y: u32,
+    pub left_end_y: u32,
+    pub right_start_y: u32,
+    pub right_end_y: u32,
+    pub top_start_x: u32,
+    pub top_end_x: u32,
+    pub bottom_start_x: u32,
+    pub bottom_end_x: u32,
+}
+
+impl Strut {
+    /// Builds a strut from the property's CARDINAL values. Four values are
+    /// the legacy `_NET_WM_STRUT`, twelve the partial form.
+    pub fn from_cardinals(values: &[u32]) -> Option<Self> {
+        match values.len() {
+            12.. => Some(Self {
+                left: values[0],
+                right: values[1],
+                top: values[2],
+                bottom: values[3],
+                left_start_y: values[4],
+                left_end_y: values[5],
+                right_start_y: values[6],
+                right_end_y: values[7],
+                top_start_x: values[8],
+                top_end_x: values[9],
+                bottom_start_x: values[10],
+                bottom_end_x: values[11],
+            }),
+            4..12 => Some(Self {
+                left: values[0],
+                right: values[1],
+                top: values[2],
+                bottom: values[3],
+                left_end_y: u32::MAX,
+                right_end_y: u32::MAX,
+                top_end_x: u32::MAX,
+                bottom_end_x: u32::MAX,
+                ..Self::default()
+            }),
+            _ => None,
+        }
+    }
+}
+
+/// Picks a monitor by index or by name, where the name is either the RandR
+/// output (`eDP-1`, `DP-2`) or the model from the display's EDID.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum MonitorSelector {
+    Index(usize),
+    Name(String),
+}
+
+impl MonitorSelector {
+    pub fn matches(&self, index: usize, monitor: &Monitor) -> bool {
+        match self {
+            MonitorSelector::Index(wanted) => *wanted == index,
+            MonitorSelector::Name(name) => [&monitor.output, &monitor.model]
+                .into_iter()
+                .flatten()
+                .any(|candidate| candidate.eq_ignore_ascii_case(name)),
+        }
+    }
+
+    /// Returns the monitor picked by the first selector that matches one,
+    /// so later entries act as fallbacks for outputs that aren't connected.
+    pub fn resolve(selectors: &[MonitorSelector], monitors: &[Monitor]) -> Option<usize> {
+        selectors.iter().find_map(|selector| {
+            monitors
+                .iter()
+                .enumerate()
+                .find(|(index, monitor)| selector.matches(*index, monitor))
+                .map(|(index, _)| index)
+        })
+    }
+}
+
+/// A side of the focused monitor, for moving between monitors by where they
+/// sit rather than by index.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Direction {
+    Left,
+    Right,
+    Up,
+    Down,
+}
+
+impl FromStr for Direction {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, String> {
+        match s.to_lowercase().as_str() {
+            "left" => Ok(Self::Left),
+            "right" => Ok(Self::Right),
+            "up" => Ok(Self::Up),
+            "down" => Ok(Self::Down),
+            _ => Err(format!(
+                "Invalid direction: {} (expected left, right, up or down)",
+                s
+            )),
+        }
+    }
+}
+
+impl Direction {
+    /// Returns the monitor nearest to `from` on this side of it. Monitors
+    /// lined up with `from` across the direction of travel win over closer
+    /// ones that are off to the side, so an L-shaped layout behaves the way
+    /// it looks.
+    pub fn neighbour(self, monitors: &[Monitor], from: usize) -> Option<usize> {
+        let current = monitors.get(from)?;
+        let (center_x, center_y) = current.center();
+
+        monitors
+            .iter()
+            .enumerate()
+            .filter(|(index, _)| *index != from)
+            .filter_map(|(index, monitor)| {
+                let (x, y) = monitor.center();
+                // The candidate counts once its middle is past our edge.
+                let (ahead, distance) = match self {
+                    Direction::Left => (x < current.screen_x, center_x - x),
+                    Direction::Right => {
+                        (x >= current.screen_x + current.screen_width, x - center_x)
+                    }
+                    Direction::Up => (y < current.screen_y, center_y - y),
+                    Direction::Down => {
+                        (y >= current.screen_y + current.screen_height, y - center_y)
+                    }
+                };
+                let offset = match self {
+                    Direction::Left | Direction::Right => gap(
+                        (current.screen_y, current.screen_height),
+                        (monitor.screen_y, monitor.screen_height),
+                    ),
+                    Direction::Up | Direction::Down => gap(
+                        (current.screen_x, current.screen_width),
+                        (monitor.screen_x, monitor.screen_width),
+                    ),
+                };
+                ahead.then_some((offset, distance, index))
+            })
+            .min()
+            .map(|(_, _, index)| index)
+    }
+}
+
+/// Distance between two spans on one axis, zero when they overlap or touch.
+fn gap((start, length): (i32, i32), (other_start, other_length): (i32, i32)) -> i32 {
+    (other_start - (start + length))
+        .max(start - (other_start + other_length))
+        .max(0)
+}
+
+/// Per-monitor overrides set with `oxwm.monitor.configure`. Anything left
+/// unset falls back to the global config.
+#[derive(Debug, Clone, Default)]
+pub struct MonitorProfile {
+    pub layout: Option<String>,
+    pub master_factor: Option<f32>,
+    pub show_bar: Option<bool>,
+    pub top_bar: Option<bool>,
+    /// Inner gaps as (horizontal, vertical).
+    pub gaps_inner: Option<(u32, u32)>,
+    /// Outer gaps as (horizontal, vertical).
+    pub gaps_outer: Option<(u32, u32)>,
+}
+
+fn overlaps(start: u32, end: u32, from: i32, length: i32) -> bool {
+    (start as i64) < (from as i64 + length as i64) && (end as i64) >= from as i64
+}
+
 #[derive(Debug, Clone)]
 pub struct Pertag {
     pub current_tag: usize,
@@ -67,14 +253,18 @@ pub struct Monitor {
     pub layout_indices: [usize; 2],
     pub scroll_offset: i32,
     pub pertag: Option<Pertag>,
+    /// RandR output driving this monitor, e.g. `eDP-1`.
+    pub output: Option<String>,
+    /// Model name from the display's EDID.
+    pub model: Option<String>,
 }
 
 impl Monitor {
     pub fn new(x: i32, y: i32, width: u32, height: u32) -> Self {
         Self {
             layout_symbol: String::from("[]"),
-            master_factor: 0.55,
-            num_master: 1,
+            master_factor: DEFAULT_MASTER_FACTOR,
+            num_master: DEFAULT_NUM_MASTER,
             monitor_number: 0,
             bar_y_position: 0,
             screen_x: x,
@@ -101,19 +291,67 @@ impl Monitor {
             layout_indices: [0, 1],
             scroll_offset: 0,
             pertag: None,
+            output: None,
+            model: None,
         }
     }
 
-    pub fn init_pertag(&mut self, num_tags: usize, default_layout: &str) {
+    /// Resets the monitor's layout state to its profile, or to the global
+    /// defaults when no profile matches.
+    pub fn init_pertag(&mut self, config: &Config, index: usize) {
+        let profile = config
+            .monitor_profile(index, self)
+            .cloned()
+            .unwrap_or_default();
+        self.apply_profile(config, index);
+        self.master_factor = profile.master_factor.unwrap_or(DEFAULT_MASTER_FACTOR);
+        self.num_master = DEFAULT_NUM_MASTER;
+        self.show_bar = profile.show_bar.unwrap_or(true);
         self.pertag = Some(Pertag::new(
-            num_tags,
+            config.tags.len(),
             self.num_master,
             self.master_factor,
             self.show_bar,
-            default_layout,
+            profile.layout.as_deref().unwrap_or("tiling"),
         ));
     }
 
+    /// Applies the parts of the profile that aren't tracked per tag, which
+    /// are safe to refresh without touching the user's layout state.
+    pub fn apply_profile(&mut self, config: &Config, index: usize) {
+        let profile = config
+            .monitor_profile(index, self)
+            .cloned()
+            .unwrap_or_default();
+        let (inner_horizontal, inner_vertical) = profile
+            .gaps_inner
+            .unwrap_or((config.gap_inner_horizontal, config.gap_inner_vertical));
+        let (outer_horizontal, outer_vertical) = profile
+            .gaps_outer
+            .unwrap_or((config.gap_outer_horizontal, config.gap_outer_vertical));
+        self.gap_inner_horizontal = inner_horizontal as i32;
+        self.gap_inner_vertical = inner_vertical as i32;
+        self.gap_outer_horizontal = outer_horizontal as i32;
+        self.gap_outer_vertical = outer_vertical as i32;
+        self.top_bar = profile.top_bar.unwrap_or(true);
+    }
+
+    /// Where a bar `bar_height` tall sits on this monitor.
+    pub fn bar_y(&self, bar_height: u16) -> i32 {
+        if self.top_bar {
+            self.screen_y
+        } else {
+            self.screen_y + self.screen_height - bar_height as i32
+        }
+    }
+
+    pub fn center(&self) -> (i32, i32) {
+        (
+            self.screen_x + self.screen_width / 2,
+            self.screen_y + self.screen_height / 2,
+        )
+    }
+
     pub fn contains_point(&self, x: i32, y: i32) -> bool {
         x >= self.screen_x
             && x < self.screen_x + self.screen_width
@@ -121,15 +359,169 @@ impl Monitor {
             && y < self.screen_y + self.screen_height
     }
 
+    /// Resets the window area to the full monitor, then shrinks it by the
+    /// part of every strut that falls on this monitor. Struts are measured
+    /// from the edges of the root window.
+    pub fn apply_struts<'a>(
+        &mut self,
+        struts: impl IntoIterator<Item = &'a Strut>,
+        root_width: i32,
+        root_height: i32,
+    ) {
+        let (x, y) = (self.screen_x, self.screen_y);
+        let (width, height) = (self.screen_width, self.screen_height);
+        let (mut left, mut right, mut top, mut bottom) = (0, 0, 0, 0);
+
+        for strut in struts {
+            if strut.left > 0 && overlaps(strut.left_start_y, strut.left_end_y, y, height) {
+                left = left.max(strut.left as i32 - x);
+            }
+            if strut.right > 0 && overlaps(strut.right_start_y, strut.right_end_y, y, height) {
+                right = right.max(x + width - (root_width - strut.right as i32));
+            }
+            if strut.top > 0 && overlaps(strut.top_start_x, strut.top_end_x, x, width) {
+                top = top.max(strut.top as i32 - y);
+            }
+            if strut.bottom > 0 && overlaps(strut.bottom_start_x, strut.bottom_end_x, x, width) {
+                bottom = bottom.max(y + height - (root_height - strut.bottom as i32));
+            }
+        }
+
+        let left = left.clamp(0, width);
+        let right = right.clamp(0, width - left);
+        let top = top.clamp(0, height);
+        let bottom = bottom.clamp(0, height - top);
+
+        self.window_area_x = x + left;
+        self.window_area_y = y + top;
+        self.window_area_width = width - left - right;
+        self.window_area_height = height - top - bottom;
+    }
+
+    pub fn has_same_geometry(&self, other: &Monitor) -> bool {
+        self.screen_x == other.screen_x
+            && self.screen_y == other.screen_y
+            && self.screen_width == other.screen_width
+            && self.screen_height == other.screen_height
+    }
+
     pub fn get_selected_tag(&self) -> TagMask {
         self.tagset[self.selected_tags_index]
     }
 }
 
+/// Asks RandR to report screen, output and CRTC changes on `root`. Returns
+/// false when the server lacks RandR 1.2, in which case a root
+/// `ConfigureNotify` is the only sign that the monitors changed.
+pub fn watch_output_changes(connection: &RustConnection, root: Window) -> bool {
+    let supported = connection
+        .randr_query_version(1, 2)
+        .ok()
+        .and_then(|cookie| cookie.reply().ok())
+        .is_some_and(|reply| (reply.major_version, reply.minor_version) >= (1, 2));
+    if !supported {
+        return false;
+    }
+
+    connection
+        .randr_select_input(
+            root,
+            randr::NotifyMask::SCREEN_CHANGE
+                | randr::NotifyMask::OUTPUT_CHANGE
+                | randr::NotifyMask::CRTC_CHANGE,
+        )
+        .is_ok()
+}
+
+/// A connected RandR output and the area of the CRTC showing it.
+struct Output {
+    name: String,
+    model: Option<String>,
+    x: i32,
+    y: i32,
+    width: i32,
+    height: i32,
+}
+
+fn connected_outputs(connection: &RustConnection, root: Window) -> Vec<Output> {
+    let Some(resources) = connection
+        .randr_get_screen_resources_current(root)
+        .ok()
+        .and_then(|cookie| cookie.reply().ok())
+    else {
+        return Vec::new();
+    };
+    let edid_atom = connection
+        .intern_atom(true, b"EDID")
+        .ok()
+        .and_then(|cookie| cookie.reply().ok())
+        .map(|reply| reply.atom)
+        .unwrap_or(x11rb::NONE);
+
+    let mut outputs = Vec::new();
+    for &output in &resources.outputs {
+        let Some(info) = connection
+            .randr_get_output_info(output, resources.config_timestamp)
+            .ok()
+            .and_then(|cookie| cookie.reply().ok())
+        else {
+            continue;
+        };
+        if info.connection != randr::Connection::CONNECTED || info.crtc == x11rb::NONE {
+            continue;
+        }
+        let Some(crtc) = connection
+            .randr_get_crtc_info(info.crtc, resources.config_timestamp)
+            .ok()
+            .and_then(|cookie| cookie.reply().ok())
+        else {
+            continue;
+        };
+
+        let model = if edid_atom == x11rb::NONE {
+            None
+        } else {
+            connection
+                .randr_get_output_property(output, edid_atom, AtomEnum::ANY, 0, 32, false, false)
+                .ok()
+                .and_then(|cookie| cookie.reply().ok())
+                .and_then(|reply| edid_model(&reply.data))
+        };
+
+        outputs.push(Output {
+            name: String::from_utf8_lossy(&info.name).into_owned(),
+            model,
+            x: crtc.x as i32,
+            y: crtc.y as i32,
+            width: crtc.width as i32,
+            height: crtc.height as i32,
+        });
+    }
+    outputs
+}
+
+/// Reads the display name descriptor (tag 0xFC) from a base EDID block.
+fn edid_model(edid: &[u8]) -> Option<String> {
+    const HEADER: [u8; 8] = [0x00, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0x00];
+    if edid.len() < 128 || edid[..8] != HEADER {
+        return None;
+    }
+
+    edid[54..126]
+        .chunks_exact(18)
+        .find(|descriptor| descriptor[..3] == [0, 0, 0] && descriptor[3] == 0xfc)
+        .map(|descriptor| {
+            let text = &descriptor[5..];
+            let end = text.iter().position(|&b| b == b'\n').unwrap_or(text.len());
+            String::from_utf8_lossy(&text[..end]).trim().to_string()
+        })
+        .filter(|model| !model.is_empty())
+}
+
 pub fn detect_monitors(
     connection: &RustConnection,
     screen: &Screen,
-    _root: Window,
+    root: Window,
 ) -> WmResult<Vec<Monitor>> {
     let fallback_monitors = || {
         vec![Monitor::new(
@@ -194,5 +586,19 @@ pub fn detect_monitors(
         other => other,
     });
 
+    // Mirrored outputs share a CRTC area; the first one names the monitor.
+    let outputs = connected_outputs(connection, root);
+    for monitor in &mut monitors {
+        if let Some(output) = outputs.iter().find(|output| {
+            output.x == monitor.screen_x
+                && output.y == monitor.screen_y
+                && output.width == monitor.screen_width
+                && output.height == monitor.screen_height
+        }) {
+            monitor.output = Some(output.name.clone());
+            monitor.model = output.model.clone();
+        }
+    }
+
     Ok(monitors)
 }
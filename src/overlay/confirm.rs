@@ -0,0 +1,115 @@
+use super::{Overlay, OverlayBase};
+use crate::bar::font::Font;
+use crate::errors::X11Error;
+use x11rb::connection::Connection;
+use x11rb::protocol::xproto::*;
+use x11rb::rust_connection::RustConnection;
+
+const PADDING: i16 = 20;
+const LINE_SPACING: i16 = 5;
+const BORDER_WIDTH: u16 = 2;
+const BORDER_COLOR: u32 = 0xffb86c;
+
+/// A yes/no prompt. The window manager grabs the keyboard while it is shown
+/// and decides what the answer means.
+pub struct ConfirmOverlay {
+    base: OverlayBase,
+    lines: Vec<String>,
+}
+
+impl ConfirmOverlay {
+    pub fn new(
+        connection: &RustConnection,
+        screen: &Screen,
+        screen_num: usize,
+        display: *mut x11::xlib::Display,
+    ) -> Result<Self, X11Error> {
+        let base = OverlayBase::new(
+            connection,
+            screen,
+            screen_num,
+            display,
+            400,
+            100,
+            BORDER_WIDTH,
+            BORDER_COLOR,
+            0x1a1a1a,
+            0xffffff,
+        )?;
+
+        Ok(ConfirmOverlay {
+            base,
+            lines: Vec::new(),
+        })
+    }
+
+    pub fn show_prompt(
+        &mut self,
+        connection: &RustConnection,
+        font: &Font,
+        message: &str,
+        monitor_x: i16,
+        monitor_y: i16,
+        screen_width: u16,
+        screen_height: u16,
+    ) -> Result<(), X11Error> {
+        self.lines = message.lines().map(str::to_string).collect();
+        self.lines.push(String::new());
+        self.lines
+            .push("Press y to confirm, Escape to cancel.".to_string());
+
+        let content_width = self
+            .lines
+            .iter()
+            .map(|line| font.text_width(line))
+            .max()
+            .unwrap_or(0);
+
+        let width = content_width + (PADDING as u16 * 2);
+        let line_height = font.height() + LINE_SPACING as u16;
+        let height = (self.lines.len() as u16 * line_height) + (PADDING as u16 * 2);
+
+        let x = monitor_x + (screen_width.saturating_sub(width) / 2) as i16;
+        let y = monitor_y + (screen_height.saturating_sub(height) / 2) as i16;
+
+        self.base.configure(connection, x, y, width, height)?;
+        self.base.is_visible = true;
+        self.draw(connection, font)?;
+        self.base.show(connection)?;
+        Ok(())
+    }
+}
+
+impl Overlay for ConfirmOverlay {
+    fn window(&self) -> Window {
+        self.base.window
+    }
+
+    fn is_visible(&self) -> bool {
+        self.base.is_visible
+    }
+
+    fn hide(&mut self, connection: &RustConnection) -> Result<(), X11Error> {
+        self.base.hide(connection)?;
+        self.lines.clear();
+        Ok(())
+    }
+
+    fn draw(&self, connection: &RustConnection, font: &Font) -> Result<(), X11Error> {
+        if !self.base.is_visible {
+            return Ok(());
+        }
+        self.base.draw_background(connection)?;
+        let line_height = font.height() + LINE_SPACING as u16;
+        let mut y = PADDING + font.ascent();
+        for line in &self.lines {
+            self.base
+                .font_draw
+                .draw_text(font, self.base.foreground_color, PADDING, y, line);
+            y += line_height as i16;
+        }
+        connection.flush()?;
+        self.base.font_draw.sync();
+        Ok(())
+    }
+}
@@ -237,10 +237,19 @@ impl KeybindOverlay {
             KeyAction::CycleLayout => "Cycle Through Layouts".to_string(),
             KeyAction::FocusMonitor => "Focus Next Monitor".to_string(),
             KeyAction::TagMonitor => "Send Window to Monitor".to_string(),
+            KeyAction::FocusMonitorDirection => match &binding.arg {
+                Arg::Str(direction) => format!("Focus Monitor ({})", direction),
+                _ => "Focus Monitor in Direction".to_string(),
+            },
+            KeyAction::TagMonitorDirection => match &binding.arg {
+                Arg::Str(direction) => format!("Send Window to Monitor ({})", direction),
+                _ => "Send Window to Monitor in Direction".to_string(),
+            },
             KeyAction::SetMasterFactor => "Adjust Master Area Size".to_string(),
             KeyAction::IncNumMaster => "Adjust Number of Master Windows".to_string(),
             KeyAction::ScrollLeft => "Scroll Layout Left".to_string(),
             KeyAction::ScrollRight => "Scroll Layout Right".to_string(),
+            KeyAction::LuaFunction => "Run Lua Function".to_string(),
             KeyAction::None => "No Action".to_string(),
         }
     }
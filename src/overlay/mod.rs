@@ -5,9 +5,11 @@ use x11rb::connection::Connection;
 use x11rb::protocol::xproto::*;
 use x11rb::rust_connection::RustConnection;
 
+pub mod confirm;
 pub mod error;
 pub mod keybind;
 
+pub use confirm::ConfirmOverlay;
 pub use error::ErrorOverlay;
 pub use keybind::KeybindOverlay;
 
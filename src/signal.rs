@@ -1,19 +1,91 @@
 use std::process::{Command, Stdio};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::time::{SystemTime, UNIX_EPOCH};
 
-pub fn spawn_detached(cmd: &str) {
-    if let Ok(mut child) = Command::new("sh")
+/// A command started by [`spawn_detached`], used to recognise its windows
+/// when they map.
+#[derive(Debug, Clone)]
+pub struct Launch {
+    /// Passed to the command as `DESKTOP_STARTUP_ID`. Toolkits copy it into
+    /// `_NET_STARTUP_ID` on the windows they open.
+    pub startup_id: String,
+    /// PID of the backgrounded command, if the shell reported one.
+    pub pid: Option<u32>,
+}
+
+pub fn spawn_detached(cmd: &str) -> Option<Launch> {
+    let startup_id = new_startup_id();
+    let output = Command::new("sh")
         .arg("-c")
-        .arg(format!("({}) &", cmd))
+        .arg(format!("({}) >/dev/null 2>&1 </dev/null & echo $!", cmd))
+        .env("DESKTOP_STARTUP_ID", &startup_id)
         .stdin(Stdio::null())
-        .stdout(Stdio::null())
+        .stdout(Stdio::piped())
         .stderr(Stdio::null())
-        .spawn()
-    {
-        let _ = child.wait();
+        .output()
+        .ok()?;
+
+    let pid = String::from_utf8_lossy(&output.stdout).trim().parse().ok();
+    Some(Launch { startup_id, pid })
+}
+
+fn new_startup_id() -> String {
+    static COUNTER: AtomicU64 = AtomicU64::new(0);
+    let millis = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|elapsed| elapsed.as_millis())
+        .unwrap_or(0);
+    format!(
+        "oxwm-{}-{}-{}",
+        std::process::id(),
+        COUNTER.fetch_add(1, Ordering::Relaxed),
+        millis
+    )
+}
+
+/// Whether `pid` is `ancestor` or one of its descendants, following parent
+/// PIDs through `/proc`.
+pub fn is_descendant_of(pid: u32, ancestor: u32) -> bool {
+    let mut current = pid;
+    // Bounded in case of a cycle from PID reuse.
+    for _ in 0..32 {
+        if current == ancestor {
+            return true;
+        }
+        if current <= 1 {
+            return false;
+        }
+        match parent_pid(current) {
+            Some(parent) => current = parent,
+            None => return false,
+        }
+    }
+    false
+}
+
+fn parent_pid(pid: u32) -> Option<u32> {
+    let stat = std::fs::read_to_string(format!("/proc/{}/stat", pid)).ok()?;
+    // The command name may contain spaces and parentheses, so skip past the
+    // last ')' before splitting: "<pid> (<comm>) <state> <ppid> ...".
+    let (_, fields) = stat.rsplit_once(')')?;
+    fields.split_whitespace().nth(1)?.parse().ok()
+}
+
+/// Sends SIGKILL to a local process.
+pub fn kill_process(pid: u32) -> std::io::Result<()> {
+    // 0 and negative pids address whole process groups.
+    let pid = libc::pid_t::try_from(pid)
+        .ok()
+        .filter(|&pid| pid > 0)
+        .ok_or_else(|| std::io::Error::from(std::io::ErrorKind::InvalidInput))?;
+    if unsafe { libc::kill(pid, libc::SIGKILL) } == 0 {
+        Ok(())
+    } else {
+        Err(std::io::Error::last_os_error())
     }
 }
 
-pub fn spawn_detached_with_args(program: &str, args: &[&str]) {
+pub fn spawn_detached_with_args(program: &str, args: &[&str]) -> Option<Launch> {
     let escaped_args: Vec<String> = args.iter().map(|a| shell_escape(a)).collect();
     let full_cmd = if escaped_args.is_empty() {
         program.to_string()
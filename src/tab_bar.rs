@@ -1,4 +1,5 @@
 use crate::ColorScheme;
+use crate::bar::Icon;
 use crate::bar::font::{DrawingSurface, Font};
 use crate::errors::X11Error;
 use crate::layout::tabbed::TAB_BAR_HEIGHT;
@@ -100,7 +101,7 @@ impl TabBar {
         &mut self,
         connection: &RustConnection,
         font: &Font,
-        windows: &[(Window, String)],
+        windows: &[(Window, String, Option<&Icon>)],
         focused_window: Option<Window>,
     ) -> Result<(), X11Error> {
         connection.change_gc(
@@ -132,7 +133,7 @@ impl TabBar {
         let tab_width = self.width / windows.len() as u16;
         let mut x_position: i16 = 0;
 
-        for (index, &(window, ref title)) in windows.iter().enumerate() {
+        for (index, &(window, ref title, icon)) in windows.iter().enumerate() {
             let is_focused = Some(window) == focused_window;
             let scheme = if is_focused {
                 &self.scheme_selected
@@ -146,12 +147,40 @@ impl TabBar {
                 title.clone()
             };
 
+            if scheme.background != self.scheme_normal.background {
+                unsafe {
+                    let gc =
+                        x11::xlib::XCreateGC(self.display, self.surface.pixmap(), 0, std::ptr::null_mut());
+                    x11::xlib::XSetForeground(self.display, gc, scheme.background as u64);
+                    x11::xlib::XFillRectangle(
+                        self.display,
+                        self.surface.pixmap(),
+                        gc,
+                        x_position as i32,
+                        0,
+                        tab_width as u32,
+                        self.height as u32,
+                    );
+                    x11::xlib::XFreeGC(self.display, gc);
+                }
+            }
+
+            let icon_gap = 6;
+            let icon_width = icon.map_or(0, |icon| icon.size() + icon_gap);
+
             let text_width = font.text_width(&display_title);
-            let text_x = x_position + ((tab_width.saturating_sub(text_width)) / 2) as i16;
+            let content_width = icon_width + text_width;
+            let content_x = x_position + ((tab_width.saturating_sub(content_width)) / 2) as i16;
+            let text_x = content_x + icon_width as i16;
 
             let top_padding = 6;
             let text_y = top_padding + font.ascent();
 
+            if let Some(icon) = icon {
+                let icon_y = (self.height.saturating_sub(icon.size()) / 2) as i16;
+                icon.draw(self.surface.pixmap(), content_x, icon_y, scheme.background);
+            }
+
             self.surface
                 .font_draw()
                 .draw_text(font, scheme.foreground, text_x, text_y, &display_title);
@@ -250,6 +279,13 @@ impl TabBar {
         Ok(())
     }
 
+    pub fn destroy(&self, connection: &RustConnection) -> Result<(), X11Error> {
+        connection.free_gc(self.graphics_context)?;
+        connection.destroy_window(self.window)?;
+        connection.flush()?;
+        Ok(())
+    }
+
     pub fn hide(&self, connection: &RustConnection) -> Result<(), X11Error> {
         connection.unmap_window(self.window)?;
         connection.flush()?;
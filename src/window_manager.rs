@@ -1,15 +1,26 @@
+use crate::ActivationPolicy;
 use crate::Config;
+use crate::SharedTags;
 use crate::animations::{AnimationConfig, ScrollAnimation};
-use crate::bar::Bar;
+use crate::bar::{Bar, Icon, Systray, TagState};
 use crate::client::{Client, TagMask};
-use crate::errors::{ConfigError, WmError};
+use crate::config::runtime::{ClientInfo, HookArg, HookEvent, MonitorInfo, WmState};
+use crate::config::{ConfigWatcher, LuaRuntime};
+use crate::errors::{ConfigError, IpcError, WmError};
+use crate::ipc::tree::{ClientNode, MonitorNode};
+use crate::ipc::{IpcCommand, IpcEvent, IpcReply, IpcServer, Tree};
 use crate::keyboard::{self, Arg, KeyAction, handlers};
 use crate::layout::GapConfig;
 use crate::layout::tiling::TilingLayout;
 use crate::layout::{Layout, LayoutBox, LayoutType, layout_from_str, next_layout};
-use crate::monitor::{Monitor, detect_monitors};
-use crate::overlay::{ErrorOverlay, KeybindOverlay, Overlay};
+use crate::monitor::{
+    Direction, Monitor, MonitorSelector, Strut, detect_monitors, watch_output_changes,
+};
+use crate::overlay::{ConfirmOverlay, ErrorOverlay, KeybindOverlay, Overlay};
+use crate::signal::Launch;
 use std::collections::{HashMap, HashSet};
+use std::rc::Rc;
+use std::time::{Duration, Instant};
 use x11rb::cursor::Handle as CursorHandle;
 
 use x11rb::connection::Connection;
@@ -22,6 +33,33 @@ enum Control {
     Quit,
 }
 
+/// Windows that are mapped but never become clients, chosen by their
+/// `_NET_WM_WINDOW_TYPE`.
+#[derive(Debug, Clone, Copy)]
+enum UnmanagedWindow {
+    /// Panels such as polybar, kept above and reserving their struts.
+    Dock(Strut),
+    /// Desktop icons/wallpaper windows, kept below everything.
+    Desktop,
+    /// Notifications and tooltips, kept above everything.
+    Notification,
+}
+
+/// Where a command was spawned, so its windows open there even if the user
+/// has switched tags or monitors by the time they map.
+struct PendingLaunch {
+    launch: Launch,
+    monitor_index: usize,
+    tags: TagMask,
+    started: Instant,
+    /// Set once a window was placed by this launch's PID, so later windows
+    /// of a long-lived process don't keep following it.
+    matched: bool,
+}
+
+/// How long a launch keeps claiming new windows.
+const LAUNCH_TIMEOUT: Duration = Duration::from_secs(30);
+
 pub fn tag_mask(tag: usize) -> TagMask {
     1 << tag
 }
@@ -40,14 +78,52 @@ struct AtomCache {
     wm_delete_window: Atom,
     net_wm_state: Atom,
     net_wm_state_fullscreen: Atom,
+    net_wm_state_modal: Atom,
+    net_wm_state_sticky: Atom,
+    net_wm_state_maximized_vert: Atom,
+    net_wm_state_maximized_horz: Atom,
+    net_wm_state_shaded: Atom,
+    net_wm_state_skip_taskbar: Atom,
+    net_wm_state_skip_pager: Atom,
+    net_wm_state_hidden: Atom,
+    net_wm_state_above: Atom,
+    net_wm_state_below: Atom,
+    net_wm_state_demands_attention: Atom,
     net_wm_window_type: Atom,
     net_wm_window_type_dialog: Atom,
     wm_name: Atom,
     net_wm_name: Atom,
+    net_wm_icon: Atom,
     utf8_string: Atom,
     net_active_window: Atom,
     wm_take_focus: Atom,
+    net_wm_ping: Atom,
+    net_wm_pid: Atom,
+    net_startup_id: Atom,
+    wm_client_machine: Atom,
     net_client_list: Atom,
+    net_client_list_stacking: Atom,
+    net_workarea: Atom,
+    net_desktop_geometry: Atom,
+    net_desktop_viewport: Atom,
+    net_number_of_desktops: Atom,
+    net_desktop_names: Atom,
+    net_wm_desktop: Atom,
+    net_wm_window_type_dock: Atom,
+    net_wm_window_type_splash: Atom,
+    net_wm_window_type_utility: Atom,
+    net_wm_window_type_toolbar: Atom,
+    net_wm_window_type_menu: Atom,
+    net_wm_window_type_notification: Atom,
+    net_wm_window_type_tooltip: Atom,
+    net_wm_window_type_desktop: Atom,
+    net_wm_strut: Atom,
+    net_wm_strut_partial: Atom,
+    net_wm_moveresize: Atom,
+    net_moveresize_window: Atom,
+    net_close_window: Atom,
+    net_request_frame_extents: Atom,
+    net_frame_extents: Atom,
 }
 
 impl AtomCache {
@@ -94,6 +170,61 @@ impl AtomCache {
             .reply()?
             .atom;
 
+        let net_wm_state_modal = connection
+            .intern_atom(false, b"_NET_WM_STATE_MODAL")?
+            .reply()?
+            .atom;
+
+        let net_wm_state_sticky = connection
+            .intern_atom(false, b"_NET_WM_STATE_STICKY")?
+            .reply()?
+            .atom;
+
+        let net_wm_state_maximized_vert = connection
+            .intern_atom(false, b"_NET_WM_STATE_MAXIMIZED_VERT")?
+            .reply()?
+            .atom;
+
+        let net_wm_state_maximized_horz = connection
+            .intern_atom(false, b"_NET_WM_STATE_MAXIMIZED_HORZ")?
+            .reply()?
+            .atom;
+
+        let net_wm_state_shaded = connection
+            .intern_atom(false, b"_NET_WM_STATE_SHADED")?
+            .reply()?
+            .atom;
+
+        let net_wm_state_skip_taskbar = connection
+            .intern_atom(false, b"_NET_WM_STATE_SKIP_TASKBAR")?
+            .reply()?
+            .atom;
+
+        let net_wm_state_skip_pager = connection
+            .intern_atom(false, b"_NET_WM_STATE_SKIP_PAGER")?
+            .reply()?
+            .atom;
+
+        let net_wm_state_hidden = connection
+            .intern_atom(false, b"_NET_WM_STATE_HIDDEN")?
+            .reply()?
+            .atom;
+
+        let net_wm_state_above = connection
+            .intern_atom(false, b"_NET_WM_STATE_ABOVE")?
+            .reply()?
+            .atom;
+
+        let net_wm_state_below = connection
+            .intern_atom(false, b"_NET_WM_STATE_BELOW")?
+            .reply()?
+            .atom;
+
+        let net_wm_state_demands_attention = connection
+            .intern_atom(false, b"_NET_WM_STATE_DEMANDS_ATTENTION")?
+            .reply()?
+            .atom;
+
         let net_wm_window_type = connection
             .intern_atom(false, b"_NET_WM_WINDOW_TYPE")?
             .reply()?
@@ -109,6 +240,10 @@ impl AtomCache {
             .intern_atom(false, b"_NET_WM_NAME")?
             .reply()?
             .atom;
+        let net_wm_icon = connection
+            .intern_atom(false, b"_NET_WM_ICON")?
+            .reply()?
+            .atom;
         let utf8_string = connection.intern_atom(false, b"UTF8_STRING")?.reply()?.atom;
         let net_active_window = connection
             .intern_atom(false, b"_NET_ACTIVE_WINDOW")?
@@ -120,11 +255,137 @@ impl AtomCache {
             .reply()?
             .atom;
 
+        let net_wm_ping = connection
+            .intern_atom(false, b"_NET_WM_PING")?
+            .reply()?
+            .atom;
+
+        let net_wm_pid = connection.intern_atom(false, b"_NET_WM_PID")?.reply()?.atom;
+        let net_startup_id = connection
+            .intern_atom(false, b"_NET_STARTUP_ID")?
+            .reply()?
+            .atom;
+
+        let wm_client_machine = connection
+            .intern_atom(false, b"WM_CLIENT_MACHINE")?
+            .reply()?
+            .atom;
+
         let net_client_list = connection
             .intern_atom(false, b"_NET_CLIENT_LIST")?
             .reply()?
             .atom;
 
+        let net_client_list_stacking = connection
+            .intern_atom(false, b"_NET_CLIENT_LIST_STACKING")?
+            .reply()?
+            .atom;
+
+        let net_workarea = connection
+            .intern_atom(false, b"_NET_WORKAREA")?
+            .reply()?
+            .atom;
+
+        let net_desktop_geometry = connection
+            .intern_atom(false, b"_NET_DESKTOP_GEOMETRY")?
+            .reply()?
+            .atom;
+
+        let net_desktop_viewport = connection
+            .intern_atom(false, b"_NET_DESKTOP_VIEWPORT")?
+            .reply()?
+            .atom;
+
+        let net_number_of_desktops = connection
+            .intern_atom(false, b"_NET_NUMBER_OF_DESKTOPS")?
+            .reply()?
+            .atom;
+
+        let net_desktop_names = connection
+            .intern_atom(false, b"_NET_DESKTOP_NAMES")?
+            .reply()?
+            .atom;
+
+        let net_wm_desktop = connection
+            .intern_atom(false, b"_NET_WM_DESKTOP")?
+            .reply()?
+            .atom;
+
+        let net_wm_window_type_dock = connection
+            .intern_atom(false, b"_NET_WM_WINDOW_TYPE_DOCK")?
+            .reply()?
+            .atom;
+
+        let net_wm_window_type_splash = connection
+            .intern_atom(false, b"_NET_WM_WINDOW_TYPE_SPLASH")?
+            .reply()?
+            .atom;
+
+        let net_wm_window_type_utility = connection
+            .intern_atom(false, b"_NET_WM_WINDOW_TYPE_UTILITY")?
+            .reply()?
+            .atom;
+
+        let net_wm_window_type_toolbar = connection
+            .intern_atom(false, b"_NET_WM_WINDOW_TYPE_TOOLBAR")?
+            .reply()?
+            .atom;
+
+        let net_wm_window_type_menu = connection
+            .intern_atom(false, b"_NET_WM_WINDOW_TYPE_MENU")?
+            .reply()?
+            .atom;
+
+        let net_wm_window_type_notification = connection
+            .intern_atom(false, b"_NET_WM_WINDOW_TYPE_NOTIFICATION")?
+            .reply()?
+            .atom;
+
+        let net_wm_window_type_tooltip = connection
+            .intern_atom(false, b"_NET_WM_WINDOW_TYPE_TOOLTIP")?
+            .reply()?
+            .atom;
+
+        let net_wm_window_type_desktop = connection
+            .intern_atom(false, b"_NET_WM_WINDOW_TYPE_DESKTOP")?
+            .reply()?
+            .atom;
+
+        let net_wm_strut = connection
+            .intern_atom(false, b"_NET_WM_STRUT")?
+            .reply()?
+            .atom;
+
+        let net_wm_strut_partial = connection
+            .intern_atom(false, b"_NET_WM_STRUT_PARTIAL")?
+            .reply()?
+            .atom;
+
+        let net_wm_moveresize = connection
+            .intern_atom(false, b"_NET_WM_MOVERESIZE")?
+            .reply()?
+            .atom;
+
+        let net_moveresize_window = connection
+            .intern_atom(false, b"_NET_MOVERESIZE_WINDOW")?
+            .reply()?
+            .atom;
+
+        let net_close_window = connection
+            .intern_atom(false, b"_NET_CLOSE_WINDOW")?
+            .reply()?
+            .atom;
+
+        let net_request_frame_extents = connection
+            .intern_atom(false, b"_NET_REQUEST_FRAME_EXTENTS")?
+            .reply()?
+            .atom;
+
+        let net_frame_extents = connection
+            .intern_atom(false, b"_NET_FRAME_EXTENTS")?
+            .reply()?
+            .atom;
+
         Ok(Self {
             net_supported,
             net_supporting_wm_check,
@@ -135,14 +396,52 @@ impl AtomCache {
             wm_delete_window,
             net_wm_state,
             net_wm_state_fullscreen,
+            net_wm_state_modal,
+            net_wm_state_sticky,
+            net_wm_state_maximized_vert,
+            net_wm_state_maximized_horz,
+            net_wm_state_shaded,
+            net_wm_state_skip_taskbar,
+            net_wm_state_skip_pager,
+            net_wm_state_hidden,
+            net_wm_state_above,
+            net_wm_state_below,
+            net_wm_state_demands_attention,
             net_wm_window_type,
             net_wm_window_type_dialog,
             wm_name,
             net_wm_name,
+            net_wm_icon,
             utf8_string,
             net_active_window,
             wm_take_focus,
+            net_wm_ping,
+            net_wm_pid,
+            net_startup_id,
+            wm_client_machine,
             net_client_list,
+            net_client_list_stacking,
+            net_workarea,
+            net_desktop_geometry,
+            net_desktop_viewport,
+            net_number_of_desktops,
+            net_desktop_names,
+            net_wm_desktop,
+            net_wm_window_type_dock,
+            net_wm_window_type_splash,
+            net_wm_window_type_utility,
+            net_wm_window_type_toolbar,
+            net_wm_window_type_menu,
+            net_wm_window_type_notification,
+            net_wm_window_type_tooltip,
+            net_wm_window_type_desktop,
+            net_wm_strut,
+            net_wm_strut_partial,
+            net_wm_moveresize,
+            net_moveresize_window,
+            net_close_window,
+            net_request_frame_extents,
+            net_frame_extents,
         })
     }
 }
@@ -161,8 +460,8 @@ pub struct WindowManager {
     floating_windows: HashSet<Window>,
     fullscreen_windows: HashSet<Window>,
     bars: Vec<Bar>,
+    systray: Option<Systray>,
     tab_bars: Vec<crate::tab_bar::TabBar>,
-    show_bar: bool,
     monitors: Vec<Monitor>,
     selected_monitor: usize,
     atoms: AtomCache,
@@ -175,14 +474,70 @@ pub struct WindowManager {
     error_message: Option<String>,
     overlay: ErrorOverlay,
     keybind_overlay: KeybindOverlay,
+    confirm_overlay: ConfirmOverlay,
+    /// Unresponsive client waiting for the user to confirm a SIGKILL.
+    pending_kill: Option<Window>,
+    /// `_NET_WM_PING`s that haven't been answered yet.
+    pending_pings: HashMap<Window, Instant>,
+    launches: Vec<PendingLaunch>,
     scroll_animation: ScrollAnimation,
     animation_config: AnimationConfig,
+    ipc: Option<IpcServer>,
+    config_watcher: Option<ConfigWatcher>,
+    /// Shared so Lua functions can run commands against `self` while the
+    /// runtime itself is borrowed.
+    lua: Rc<LuaRuntime>,
+    pending_hooks: Vec<(HookEvent, HookArg)>,
+    /// Window named in the last `Focus` event, so each change is sent once.
+    reported_focus: Option<Window>,
+    last_selected_monitor: usize,
+    unmanaged: HashMap<Window, UnmanagedWindow>,
+    /// Last `_NET_WORKAREA` rectangle written, to avoid rewriting it on
+    /// every layout pass.
+    published_workarea: Option<[u32; 4]>,
+    /// Set by RandR and root resize events; monitors are re-detected once
+    /// the event queue is drained, since one change sends several of them.
+    monitors_dirty: bool,
 }
 
 type WmResult<T> = Result<T, WmError>;
 
+/// `_NET_WM_MOVERESIZE_CANCEL`.
+const MOVERESIZE_CANCEL: u32 = 11;
+
+/// The edges an interactive resize moves.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+struct ResizeEdges {
+    left: bool,
+    top: bool,
+    right: bool,
+    bottom: bool,
+}
+
+impl ResizeEdges {
+    /// Resizing with the mouse binding drags the bottom-right corner.
+    const BOTTOM_RIGHT: Self = Self {
+        left: false,
+        top: false,
+        right: true,
+        bottom: true,
+    };
+
+    /// Maps a `_NET_WM_MOVERESIZE` direction (0 = top-left, going clockwise
+    /// to 7 = left) to its edges.
+    fn from_moveresize(direction: u32) -> Self {
+        let edge = |directions: [u32; 3]| directions.contains(&direction);
+        Self {
+            left: edge([0, 6, 7]),
+            top: edge([0, 1, 2]),
+            right: edge([2, 3, 4]),
+            bottom: edge([4, 5, 6]),
+        }
+    }
+}
+
 impl WindowManager {
-    pub fn new(config: Config) -> WmResult<Self> {
+    pub fn new(config: Config, lua: LuaRuntime) -> WmResult<Self> {
         let (connection, screen_number) = x11rb::connect(None)?;
         let root = connection.setup().roots[screen_number].root;
         let screen = connection.setup().roots[screen_number].clone();
@@ -203,6 +558,7 @@ impl WindowManager {
                     .event_mask(
                         EventMask::SUBSTRUCTURE_REDIRECT
                             | EventMask::SUBSTRUCTURE_NOTIFY
+                            | EventMask::STRUCTURE_NOTIFY
                             | EventMask::PROPERTY_CHANGE
                             | EventMask::KEY_PRESS
                             | EventMask::BUTTON_PRESS
@@ -246,9 +602,13 @@ impl WindowManager {
             )?;
         }
 
+        if !watch_output_changes(&connection, root) {
+            eprintln!("RandR 1.2 unavailable, monitors are only re-detected on root resize");
+        }
+
         let mut monitors = detect_monitors(&connection, &screen, root)?;
-        for monitor in monitors.iter_mut() {
-            monitor.init_pertag(config.tags.len(), "tiling");
+        for (index, monitor) in monitors.iter_mut().enumerate() {
+            monitor.init_pertag(&config, index);
         }
 
         let display = unsafe { x11::xlib::XOpenDisplay(std::ptr::null()) };
@@ -268,12 +628,27 @@ impl WindowManager {
                 display,
                 &font,
                 monitor.screen_x as i16,
-                monitor.screen_y as i16,
+                monitor.bar_y(Bar::height_for(&font)) as i16,
                 monitor.screen_width as u16,
             )?;
             bars.push(bar);
         }
 
+        let systray = match MonitorSelector::resolve(&config.systray_monitor, &monitors)
+            .and_then(|index| Some((index, bars.get(index)?)))
+        {
+            Some((index, bar)) => Systray::new(
+                &connection,
+                &screen,
+                screen_number,
+                bar.window(),
+                index,
+                bar.height(),
+                config.scheme_normal.background,
+            )?,
+            None => None,
+        };
+
         let bar_height = font.height() as f32 * 1.4;
         let mut tab_bars = Vec::new();
         for monitor in monitors.iter() {
@@ -303,13 +678,50 @@ impl WindowManager {
             atoms.net_supporting_wm_check,
             atoms.net_wm_state,
             atoms.net_wm_state_fullscreen,
+            atoms.net_wm_state_modal,
+            atoms.net_wm_state_sticky,
+            atoms.net_wm_state_maximized_vert,
+            atoms.net_wm_state_maximized_horz,
+            atoms.net_wm_state_shaded,
+            atoms.net_wm_state_skip_taskbar,
+            atoms.net_wm_state_skip_pager,
+            atoms.net_wm_state_hidden,
+            atoms.net_wm_state_above,
+            atoms.net_wm_state_below,
+            atoms.net_wm_state_demands_attention,
             atoms.net_wm_window_type,
             atoms.net_wm_window_type_dialog,
             atoms.net_active_window,
             atoms.net_wm_name,
+            atoms.net_wm_icon,
             atoms.net_current_desktop,
             atoms.net_client_info,
             atoms.net_client_list,
+            atoms.net_wm_ping,
+            atoms.net_wm_pid,
+            atoms.net_startup_id,
+            atoms.net_client_list_stacking,
+            atoms.net_workarea,
+            atoms.net_desktop_geometry,
+            atoms.net_desktop_viewport,
+            atoms.net_number_of_desktops,
+            atoms.net_desktop_names,
+            atoms.net_wm_desktop,
+            atoms.net_wm_window_type_dock,
+            atoms.net_wm_window_type_splash,
+            atoms.net_wm_window_type_utility,
+            atoms.net_wm_window_type_toolbar,
+            atoms.net_wm_window_type_menu,
+            atoms.net_wm_window_type_notification,
+            atoms.net_wm_window_type_tooltip,
+            atoms.net_wm_window_type_desktop,
+            atoms.net_wm_strut,
+            atoms.net_wm_strut_partial,
+            atoms.net_wm_moveresize,
+            atoms.net_moveresize_window,
+            atoms.net_close_window,
+            atoms.net_request_frame_extents,
+            atoms.net_frame_extents,
         ];
         let supported_bytes: Vec<u8> = supported_atoms
             .iter()
@@ -382,6 +794,22 @@ impl WindowManager {
         let keybind_overlay =
             KeybindOverlay::new(&connection, &screen, screen_number, display, config.modkey)?;
 
+        let confirm_overlay = ConfirmOverlay::new(&connection, &screen, screen_number, display)?;
+
+        let ipc = match crate::ipc::socket_path() {
+            Some(path) => match IpcServer::bind(&path) {
+                Ok(server) => Some(server),
+                Err(err) => {
+                    eprintln!("IPC disabled: {}", err);
+                    None
+                }
+            },
+            None => {
+                eprintln!("IPC disabled: {}", IpcError::NoSocketPath);
+                None
+            }
+        };
+
         let mut window_manager = Self {
             config,
             connection,
@@ -391,13 +819,17 @@ impl WindowManager {
             screen,
             windows: Vec::new(),
             clients: HashMap::new(),
-            layout: Box::new(TilingLayout),
+            layout: monitors
+                .first()
+                .and_then(|monitor| monitor.pertag.as_ref())
+                .and_then(|pertag| layout_from_str(&pertag.layouts[pertag.current_tag]).ok())
+                .unwrap_or_else(|| Box::new(TilingLayout)),
             gaps_enabled,
             floating_windows: HashSet::new(),
             fullscreen_windows: HashSet::new(),
             bars,
+            systray,
             tab_bars,
-            show_bar: true,
             monitors,
             selected_monitor: 0,
             atoms,
@@ -410,15 +842,35 @@ impl WindowManager {
             error_message: None,
             overlay,
             keybind_overlay,
+            confirm_overlay,
+            pending_kill: None,
+            pending_pings: HashMap::new(),
+            launches: Vec::new(),
             scroll_animation: ScrollAnimation::new(),
             animation_config: AnimationConfig::default(),
+            ipc,
+            config_watcher: None,
+            lua: Rc::new(lua),
+            pending_hooks: Vec::new(),
+            reported_focus: None,
+            last_selected_monitor: 0,
+            unmanaged: HashMap::new(),
+            published_workarea: None,
+            monitors_dirty: false,
         };
 
         for tab_bar in &window_manager.tab_bars {
             tab_bar.hide(&window_manager.connection)?;
         }
 
+        window_manager.refresh_config_watcher();
+        window_manager.update_desktop_info()?;
         window_manager.scan_existing_windows()?;
+        if window_manager.config.shared_tags.is_enabled() {
+            window_manager.spread_shared_tags();
+            window_manager.assign_shared_clients();
+            window_manager.apply_layout()?;
+        }
         window_manager.update_bar()?;
         window_manager.run_autostart_commands();
 
@@ -461,11 +913,13 @@ impl WindowManager {
 
         let config_dir = lua_path.parent();
 
-        let new_config = crate::config::parse_lua_config(&config_str, config_dir)?;
+        let (new_config, lua) = crate::config::load_lua_config(&config_str, config_dir)?;
 
         let lua_path = self.config.path.take();
 
         self.config = new_config;
+        self.lua = Rc::new(lua);
+        self.pending_hooks.clear();
         self.config.path = lua_path;
         self.error_message = None;
 
@@ -476,333 +930,982 @@ impl WindowManager {
         Ok(())
     }
 
-    fn scan_existing_windows(&mut self) -> WmResult<()> {
-        let tree = self.connection.query_tree(self.root)?.reply()?;
-        let net_client_info = self.atoms.net_client_info;
-        let wm_state_atom = self.atoms.wm_state;
-
-        for &window in &tree.children {
-            if self.bars.iter().any(|bar| bar.window() == window) {
-                continue;
+    fn reload_config(&mut self) -> WmResult<()> {
+        match self.try_reload_config() {
+            Ok(()) => {
+                self.gaps_enabled = self.config.gaps_enabled;
+                self.error_message = None;
+                if let Err(error) = self.overlay.hide(&self.connection) {
+                    eprintln!("Failed to hide overlay after config reload: {:?}", error);
+                }
+                self.refresh_config_watcher();
+                self.apply_monitor_profiles()?;
+                if self.config.shared_tags.is_enabled() {
+                    self.spread_shared_tags();
+                    self.assign_shared_clients();
+                    self.focus(None)?;
+                }
+                // The tag count may have changed, so _NET_WORKAREA needs one
+                // entry per desktop again even if the area itself didn't move.
+                self.published_workarea = None;
+                self.update_desktop_info()?;
+                self.grab_keys()?;
+                self.apply_layout()?;
+                self.update_bar()?;
+                self.emit(IpcEvent::ConfigReload {
+                    success: true,
+                    error: None,
+                });
+            }
+            Err(err) => {
+                eprintln!("Config reload error: {}", err);
+                self.error_message = Some(err.to_string());
+                self.emit(IpcEvent::ConfigReload {
+                    success: false,
+                    error: Some(err.to_string()),
+                });
+                let monitor = &self.monitors[self.selected_monitor];
+                let monitor_x = monitor.screen_x as i16;
+                let monitor_y = monitor.screen_y as i16;
+                let screen_width = monitor.screen_width as u16;
+                let screen_height = monitor.screen_height as u16;
+                match self.overlay.show_error(
+                    &self.connection,
+                    &self.font,
+                    err,
+                    monitor_x,
+                    monitor_y,
+                    screen_width,
+                    screen_height,
+                ) {
+                    Ok(()) => eprintln!("Error modal displayed"),
+                    Err(e) => eprintln!("Failed to show error modal: {:?}", e),
+                }
             }
+        }
+        Ok(())
+    }
 
-            let Ok(attrs) = self.connection.get_window_attributes(window)?.reply() else {
-                continue;
-            };
-
-            if attrs.override_redirect {
-                continue;
+    /// Refreshes each monitor's gaps and bar position from its profile,
+    /// leaving its per-tag layout state alone.
+    fn apply_monitor_profiles(&mut self) -> WmResult<()> {
+        for (index, monitor) in self.monitors.iter_mut().enumerate() {
+            monitor.apply_profile(&self.config, index);
+            if let Some(bar) = self.bars.get_mut(index) {
+                bar.reposition(
+                    &self.connection,
+                    self.display,
+                    monitor.screen_x as i16,
+                    monitor.bar_y(bar.height()) as i16,
+                    monitor.screen_width as u16,
+                )?;
             }
+        }
+        Ok(())
+    }
 
-            if attrs.map_state == MapState::VIEWABLE {
-                let _tag = self.get_saved_tag(window, net_client_info)?;
-                self.windows.push(window);
-                continue;
-            }
+    fn refresh_config_watcher(&mut self) {
+        self.config_watcher = None;
 
-            if attrs.map_state == MapState::UNMAPPED {
-                let has_wm_state = self
-                    .connection
-                    .get_property(false, window, wm_state_atom, AtomEnum::ANY, 0, 2)?
-                    .reply()
-                    .is_ok_and(|prop| !prop.value.is_empty());
+        if !self.config.auto_reload {
+            return;
+        }
+        let Some(path) = self.config.path.clone() else {
+            return;
+        };
 
-                if !has_wm_state {
-                    continue;
-                }
+        let mut files = vec![path];
+        files.extend(self.config.required_modules.iter().cloned());
 
-                let has_wm_class = self
-                    .connection
-                    .get_property(false, window, AtomEnum::WM_CLASS, AtomEnum::STRING, 0, 1024)?
-                    .reply()
-                    .is_ok_and(|prop| !prop.value.is_empty());
+        match ConfigWatcher::new(&files) {
+            Ok(watcher) => self.config_watcher = Some(watcher),
+            Err(e) => eprintln!("Failed to watch config for changes: {}", e),
+        }
+    }
 
-                if has_wm_class {
-                    let _tag = self.get_saved_tag(window, net_client_info)?;
-                    self.connection.map_window(window)?;
-                    self.windows.push(window);
+    fn run_action(&mut self, action: KeyAction, arg: &Arg) -> WmResult<Control> {
+        match action {
+            KeyAction::Quit => return Ok(Control::Quit),
+            KeyAction::Restart => self.reload_config()?,
+            KeyAction::LuaFunction => {
+                if let Arg::Int(index) = arg {
+                    return self.run_lua_function(*index as usize);
                 }
             }
+            _ => self.handle_key_action(action, arg)?,
         }
+        Ok(Control::Continue)
+    }
 
-        if let Some(&first) = self.windows.first() {
-            self.focus(Some(first))?;
+    fn run_lua_function(&mut self, index: usize) -> WmResult<Control> {
+        self.publish_lua_state();
+        let lua = Rc::clone(&self.lua);
+        let mut outcome = Ok(Control::Continue);
+        lua.run_callback(index, |action, arg| {
+            self.run_lua_action(&mut outcome, action, &arg)
+        });
+        outcome
+    }
+
+    /// Runs a command issued from Lua and republishes the state, so the rest
+    /// of the Lua function sees its effect. After an error or a quit the
+    /// remaining commands are skipped.
+    fn run_lua_action(&mut self, outcome: &mut WmResult<Control>, action: KeyAction, arg: &Arg) {
+        if !matches!(outcome, Ok(Control::Continue)) {
+            return;
         }
+        *outcome = self.run_action(action, arg);
+        self.publish_lua_state();
+    }
 
-        self.apply_layout()?;
-        Ok(())
+    fn publish_lua_state(&self) {
+        let clients = self
+            .windows
+            .iter()
+            .filter_map(|&window| self.client_info(window))
+            .collect();
+        let monitors = (0..self.monitors.len())
+            .filter_map(|index| self.monitor_info(index))
+            .collect();
+
+        self.lua.set_state(WmState {
+            clients,
+            monitors,
+            selected_monitor: self.selected_monitor,
+            focused: self
+                .monitors
+                .get(self.selected_monitor)
+                .and_then(|m| m.selected_client),
+        });
     }
 
-    fn get_saved_tag(&self, window: Window, net_client_info: Atom) -> WmResult<TagMask> {
-        match self
-            .connection
-            .get_property(false, window, net_client_info, AtomEnum::CARDINAL, 0, 2)?
-            .reply()
-        {
-            Ok(prop) if prop.value.len() >= 4 => {
-                let tags = u32::from_ne_bytes([
-                    prop.value[0],
-                    prop.value[1],
-                    prop.value[2],
-                    prop.value[3],
-                ]);
+    fn poll_ipc(&mut self) -> WmResult<Control> {
+        let Some(ipc) = self.ipc.as_mut() else {
+            return Ok(Control::Continue);
+        };
 
-                if tags != 0 && tags < (1 << self.config.tags.len()) {
-                    return Ok(tags);
+        let mut control = Control::Continue;
+        for (client, line) in ipc.poll() {
+            let (reply, result) = match IpcCommand::parse(&line) {
+                Ok(IpcCommand::Subscribe(events)) => {
+                    if let Some(ipc) = self.ipc.as_mut() {
+                        ipc.subscribe(client, events);
+                    }
+                    (IpcReply::ok(), Control::Continue)
                 }
+                Ok(command) => match self.handle_ipc_command(command) {
+                    Ok((reply, result)) => (reply, result),
+                    Err(err) => (IpcReply::error(err.to_string()), Control::Continue),
+                },
+                Err(err) => (IpcReply::from(err), Control::Continue),
+            };
+
+            if let Some(ipc) = self.ipc.as_mut() {
+                ipc.send(client, &reply.to_line());
             }
-            Ok(_) => {}
-            Err(e) => {
-                eprintln!("No _NET_CLIENT_INFO property ({})", e);
+
+            if matches!(result, Control::Quit) {
+                control = Control::Quit;
+                break;
             }
         }
 
-        Ok(self
-            .monitors
-            .get(self.selected_monitor)
-            .map(|m| m.tagset[m.selected_tags_index])
-            .unwrap_or(tag_mask(0)))
+        Ok(control)
     }
 
-    fn save_client_tag(&self, window: Window, tag: TagMask) -> WmResult<()> {
-        let net_client_info = self.atoms.net_client_info;
+    fn handle_ipc_command(&mut self, command: IpcCommand) -> WmResult<(IpcReply, Control)> {
+        match command {
+            IpcCommand::Action(action, arg) => {
+                if let Err(message) = self.validate_ipc_action(action, &arg) {
+                    return Ok((IpcReply::error(message), Control::Continue));
+                }
 
-        let bytes = tag.to_ne_bytes().to_vec();
+                let control = self.run_action(action, &arg)?;
+                self.connection.flush()?;
 
-        self.connection.change_property(
-            PropMode::REPLACE,
-            window,
-            net_client_info,
-            AtomEnum::CARDINAL,
-            32,
-            1,
-            &bytes,
-        )?;
+                if action == KeyAction::Restart
+                    && let Some(message) = &self.error_message
+                {
+                    return Ok((IpcReply::error(message.clone()), control));
+                }
 
-        self.connection.flush()?;
-        Ok(())
+                Ok((IpcReply::ok(), control))
+            }
+            IpcCommand::Subscribe(_) => Ok((IpcReply::ok(), Control::Continue)),
+            IpcCommand::GetTree => match serde_json::to_value(self.build_tree()) {
+                Ok(tree) => Ok((IpcReply::with_data(tree), Control::Continue)),
+                Err(err) => Ok((IpcReply::error(err.to_string()), Control::Continue)),
+            },
+        }
     }
 
-    fn set_wm_state(&self, window: Window, state: u32) -> WmResult<()> {
-        let wm_state_atom = self.atoms.wm_state;
+    fn emit(&mut self, event: IpcEvent) {
+        self.queue_hook_for(&event);
+        if let Some(ipc) = self.ipc.as_mut() {
+            ipc.broadcast(&event);
+        }
+    }
 
-        let data = [state, 0u32];
-        let bytes: Vec<u8> = data.iter().flat_map(|&v| v.to_ne_bytes()).collect();
+    /// Lua hooks piggyback on the IPC events. `client_unmanage` is the
+    /// exception: it is queued by `remove_window` while the client still
+    /// exists.
+    fn queue_hook_for(&mut self, event: &IpcEvent) {
+        let hook = match event {
+            IpcEvent::Tag { .. } => HookEvent::TagView,
+            IpcEvent::Layout { .. } => HookEvent::LayoutChange,
+            IpcEvent::Focus { .. } => HookEvent::FocusChange,
+            IpcEvent::Manage { .. } => HookEvent::ClientManage,
+            IpcEvent::Title { .. } => HookEvent::TitleChange,
+            IpcEvent::Urgent { .. } => HookEvent::ClientUrgent,
+            IpcEvent::Unmanage { .. } | IpcEvent::ConfigReload { .. } => return,
+        };
 
-        self.connection.change_property(
-            PropMode::REPLACE,
-            window,
-            wm_state_atom,
-            wm_state_atom,
-            32,
-            2,
-            &bytes,
-        )?;
+        if !self.lua.has_hooks(hook) {
+            return;
+        }
 
-        self.connection.flush()?;
-        Ok(())
+        let arg = match *event {
+            IpcEvent::Tag { monitor, .. } | IpcEvent::Layout { monitor, .. } => {
+                self.monitor_hook_arg(monitor)
+            }
+            IpcEvent::Focus { window, .. } => self.client_hook_arg(window),
+            IpcEvent::Manage { window, .. }
+            | IpcEvent::Title { window, .. }
+            | IpcEvent::Urgent { window, .. } => self.client_hook_arg(Some(window)),
+            IpcEvent::Unmanage { .. } | IpcEvent::ConfigReload { .. } => HookArg::None,
+        };
+        self.pending_hooks.push((hook, arg));
     }
 
-    fn update_client_list(&self) -> WmResult<()> {
-        let window_bytes: Vec<u8> = self
-            .windows
-            .iter()
-            .flat_map(|window| window.to_ne_bytes())
-            .collect();
-
-        self.connection.change_property(
-            PropMode::REPLACE,
-            self.root,
-            self.atoms.net_client_list,
-            AtomEnum::WINDOW,
-            32,
-            self.windows.len() as u32,
-            &window_bytes,
-        )?;
+    fn client_hook_arg(&self, window: Option<Window>) -> HookArg {
+        window
+            .and_then(|window| self.client_info(window))
+            .map_or(HookArg::None, HookArg::Client)
+    }
 
-        Ok(())
+    fn monitor_hook_arg(&self, index: usize) -> HookArg {
+        self.monitor_info(index)
+            .map_or(HookArg::None, HookArg::Monitor)
     }
 
-    pub fn run(&mut self) -> WmResult<()> {
-        println!("oxwm started on display {}", self.screen_number);
+    fn client_info(&self, window: Window) -> Option<ClientInfo> {
+        let client = self.clients.get(&window)?;
+        Some(ClientInfo {
+            window,
+            name: client.name.clone(),
+            class: client.class.clone(),
+            instance: client.instance.clone(),
+            tags: client.tags,
+            monitor: client.monitor_index,
+            floating: client.is_floating,
+            fullscreen: client.is_fullscreen,
+            urgent: client.is_urgent,
+            x: client.x_position,
+            y: client.y_position,
+            width: client.width,
+            height: client.height,
+        })
+    }
 
-        self.grab_keys()?;
-        self.update_bar()?;
+    fn monitor_info(&self, index: usize) -> Option<MonitorInfo> {
+        let monitor = self.monitors.get(index)?;
+        Some(MonitorInfo {
+            index,
+            x: monitor.screen_x,
+            y: monitor.screen_y,
+            width: monitor.screen_width,
+            height: monitor.screen_height,
+            output: monitor.output.clone(),
+            model: monitor.model.clone(),
+            tagset: monitor.tagset[monitor.selected_tags_index],
+            layout: self.monitor_layout_name(index),
+            focused: monitor.selected_client,
+        })
+    }
 
-        let mut last_bar_update = std::time::Instant::now();
-        const BAR_UPDATE_INTERVAL_MS: u64 = 100;
+    /// Runs queued Lua hooks and the commands they issue. Commands can
+    /// trigger further hooks, so this is bounded to stop a callback that
+    /// reacts to its own effects from spinning forever.
+    fn run_pending_hooks(&mut self) -> WmResult<Control> {
+        const MAX_HOOK_ROUNDS: usize = 16;
 
-        loop {
-            match self.connection.poll_for_event_with_sequence()? {
-                Some((event, _sequence)) => {
-                    if matches!(self.handle_event(event)?, Control::Quit) {
-                        return Ok(());
-                    }
-                }
-                None => {
-                    if last_bar_update.elapsed().as_millis() >= BAR_UPDATE_INTERVAL_MS as u128 {
-                        if let Some(bar) = self.bars.get_mut(self.selected_monitor) {
-                            bar.update_blocks();
-                        }
-                        if self.bars.iter().any(|bar| bar.needs_redraw()) {
-                            self.update_bar()?;
-                        }
-                        last_bar_update = std::time::Instant::now();
-                    }
+        if self.selected_monitor != self.last_selected_monitor {
+            self.last_selected_monitor = self.selected_monitor;
+            let monitor = self.selected_monitor;
+            if self.lua.has_hooks(HookEvent::MonitorChange) {
+                let arg = self.monitor_hook_arg(monitor);
+                self.pending_hooks.push((HookEvent::MonitorChange, arg));
+            }
+        }
 
-                    self.tick_animations()?;
+        for _ in 0..MAX_HOOK_ROUNDS {
+            if self.pending_hooks.is_empty() {
+                return Ok(Control::Continue);
+            }
 
-                    self.connection.flush()?;
-                    std::thread::sleep(std::time::Duration::from_millis(16));
+            for (hook, arg) in std::mem::take(&mut self.pending_hooks) {
+                self.publish_lua_state();
+                let lua = Rc::clone(&self.lua);
+                let mut outcome = Ok(Control::Continue);
+                lua.run_hooks(hook, &arg, |action, action_arg| {
+                    self.run_lua_action(&mut outcome, action, &action_arg)
+                });
+                if matches!(outcome?, Control::Quit) {
+                    return Ok(Control::Quit);
                 }
             }
         }
-    }
 
-    fn toggle_floating(&mut self) -> WmResult<()> {
-        let focused = self
-            .monitors
-            .get(self.selected_monitor)
-            .and_then(|m| m.selected_client);
+        if !self.pending_hooks.is_empty() {
+            eprintln!("Lua hooks kept triggering each other, dropping the rest");
+            self.pending_hooks.clear();
+        }
+        Ok(Control::Continue)
+    }
 
-        if focused.is_none() {
-            return Ok(());
+    fn emit_layout_if_changed(&mut self, previous: &str) {
+        let layout = self.layout.name();
+        if layout != previous {
+            self.emit(IpcEvent::Layout {
+                monitor: self.selected_monitor,
+                layout: layout.to_string(),
+            });
         }
-        let focused = focused.unwrap();
+    }
 
-        if let Some(client) = self.clients.get(&focused)
-            && client.is_fullscreen
-        {
-            return Ok(());
+    fn emit_tag(&mut self) {
+        if let Some(monitor) = self.monitors.get(self.selected_monitor) {
+            let tagset = monitor.tagset[monitor.selected_tags_index];
+            self.emit(IpcEvent::Tag {
+                monitor: self.selected_monitor,
+                tagset,
+            });
         }
+    }
 
-        let (is_fixed, x, y, w, h) = if let Some(client) = self.clients.get(&focused) {
-            (
-                client.is_fixed,
-                client.x_position as i32,
-                client.y_position as i32,
-                client.width as u32,
-                client.height as u32,
-            )
-        } else {
-            return Ok(());
+    /// Makes `index` the selected monitor. `self.layout` always belongs to
+    /// the selected monitor, so it is swapped for the one `index` uses.
+    fn select_monitor(&mut self, index: usize) {
+        self.selected_monitor = index;
+        let Some(name) = self
+            .monitors
+            .get(index)
+            .and_then(|monitor| monitor.pertag.as_ref())
+            .and_then(|pertag| pertag.layouts.get(pertag.current_tag))
+        else {
+            return;
         };
+        if let Ok(layout) = layout_from_str(name)
+            && layout.name() != self.layout.name()
+        {
+            self.layout = layout;
+        }
+    }
 
-        let was_floating = self.floating_windows.contains(&focused);
+    fn has_tabbed_monitor(&self) -> bool {
+        (0..self.monitors.len())
+            .any(|index| self.monitor_layout(index).name() == LayoutType::Tabbed.as_str())
+    }
 
-        if was_floating {
-            self.floating_windows.remove(&focused);
-            if let Some(client) = self.clients.get_mut(&focused) {
-                client.is_floating = false;
-            }
-        } else {
-            self.floating_windows.insert(focused);
-            if let Some(client) = self.clients.get_mut(&focused) {
-                client.is_floating = is_fixed || !client.is_floating;
-            }
+    fn monitor_layout(&self, index: usize) -> LayoutBox {
+        layout_from_str(&self.monitor_layout_name(index)).unwrap_or_else(|_| Box::new(TilingLayout))
+    }
 
-            self.connection.configure_window(
-                focused,
-                &ConfigureWindowAux::new()
-                    .x(x)
-                    .y(y)
-                    .width(w)
-                    .height(h)
-                    .stack_mode(StackMode::ABOVE),
-            )?;
+    /// The gaps on `monitor`, or none while gaps are toggled off.
+    fn monitor_gaps(&self, monitor: &Monitor) -> GapConfig {
+        if !self.gaps_enabled {
+            return GapConfig {
+                inner_horizontal: 0,
+                inner_vertical: 0,
+                outer_horizontal: 0,
+                outer_vertical: 0,
+            };
+        }
+        GapConfig {
+            inner_horizontal: monitor.gap_inner_horizontal as u32,
+            inner_vertical: monitor.gap_inner_vertical as u32,
+            outer_horizontal: monitor.gap_outer_horizontal as u32,
+            outer_vertical: monitor.gap_outer_vertical as u32,
         }
-
-        self.apply_layout()?;
-        Ok(())
     }
 
-    fn set_master_factor(&mut self, delta: f32) -> WmResult<()> {
-        if let Some(monitor) = self.monitors.get_mut(self.selected_monitor) {
-            let new_mfact = (monitor.master_factor + delta).clamp(0.05, 0.95);
-            monitor.master_factor = new_mfact;
-            if let Some(ref mut pertag) = monitor.pertag {
-                pertag.master_factors[pertag.current_tag] = new_mfact;
-            }
-            self.apply_layout()?;
+    /// Height the bar takes from the monitor's window area, or 0 while it
+    /// is hidden there.
+    fn bar_space(&self, monitor_index: usize) -> i32 {
+        match (
+            self.monitors.get(monitor_index),
+            self.bars.get(monitor_index),
+        ) {
+            (Some(monitor), Some(bar)) if monitor.show_bar => bar.height() as i32,
+            _ => 0,
         }
-        Ok(())
     }
 
-    fn inc_num_master(&mut self, delta: i32) -> WmResult<()> {
-        if let Some(monitor) = self.monitors.get_mut(self.selected_monitor) {
-            let new_nmaster = (monitor.num_master + delta).max(0);
-            monitor.num_master = new_nmaster;
-            if let Some(ref mut pertag) = monitor.pertag {
-                pertag.num_masters[pertag.current_tag] = new_nmaster;
-            }
-            self.apply_layout()?;
+    fn monitor_layout_name(&self, index: usize) -> String {
+        if index == self.selected_monitor {
+            return self.layout.name().to_string();
         }
-        Ok(())
+        self.monitors
+            .get(index)
+            .and_then(|monitor| monitor.pertag.as_ref())
+            .and_then(|pertag| pertag.layouts.get(pertag.current_tag))
+            .cloned()
+            .unwrap_or_else(|| self.layout.name().to_string())
     }
 
-    fn tick_animations(&mut self) -> WmResult<()> {
-        if self.scroll_animation.is_active() {
-            if let Some(new_offset) = self.scroll_animation.update() {
-                if let Some(m) = self.monitors.get_mut(self.selected_monitor) {
-                    m.scroll_offset = new_offset;
-                }
-                self.apply_layout()?;
-                self.update_bar()?;
-            }
+    fn build_tree(&self) -> Tree<'_> {
+        let monitors = self
+            .monitors
+            .iter()
+            .enumerate()
+            .map(|(index, monitor)| {
+                MonitorNode::new(index, monitor, self.monitor_layout_name(index))
+            })
+            .collect();
+
+        let clients = self
+            .windows
+            .iter()
+            .filter_map(|window| self.clients.get(window))
+            .map(ClientNode::new)
+            .collect();
+
+        Tree {
+            tags: &self.config.tags,
+            selected_monitor: self.selected_monitor,
+            focused: self
+                .monitors
+                .get(self.selected_monitor)
+                .and_then(|m| m.selected_client),
+            monitors,
+            clients,
         }
-        Ok(())
     }
 
-    fn scroll_layout(&mut self, direction: i32) -> WmResult<()> {
-        if self.layout.name() != "scrolling" {
-            return Ok(());
+    fn validate_ipc_action(&self, action: KeyAction, arg: &Arg) -> Result<(), String> {
+        match (action, arg) {
+            (
+                KeyAction::ViewTag
+                | KeyAction::ToggleView
+                | KeyAction::MoveToTag
+                | KeyAction::ToggleTag,
+                Arg::Int(index),
+            ) if *index < 0 || *index as usize >= self.config.tags.len() => Err(format!(
+                "tag index {} out of range (0-{})",
+                index,
+                self.config.tags.len().saturating_sub(1)
+            )),
+            (KeyAction::ChangeLayout, Arg::Str(name)) if layout_from_str(name).is_err() => {
+                Err(format!("unknown layout '{}'", name))
+            }
+            _ => Ok(()),
         }
+    }
 
-        let monitor_index = self.selected_monitor;
-        let monitor = match self.monitors.get(monitor_index) {
-            Some(m) => m.clone(),
-            None => return Ok(()),
-        };
+    fn scan_existing_windows(&mut self) -> WmResult<()> {
+        let tree = self.connection.query_tree(self.root)?.reply()?;
+        let net_client_info = self.atoms.net_client_info;
+        let wm_state_atom = self.atoms.wm_state;
 
-        let visible_count = if monitor.num_master > 0 {
-            monitor.num_master as usize
-        } else {
-            2
-        };
+        for &window in &tree.children {
+            if self.bars.iter().any(|bar| bar.window() == window) {
+                continue;
+            }
 
-        let mut tiled_count = 0;
-        let mut current = self.next_tiled(monitor.clients_head, &monitor);
-        while let Some(window) = current {
-            tiled_count += 1;
-            if let Some(client) = self.clients.get(&window) {
-                current = self.next_tiled(client.next, &monitor);
-            } else {
-                break;
+            let Ok(attrs) = self.connection.get_window_attributes(window)?.reply() else {
+                continue;
+            };
+
+            if attrs.override_redirect {
+                continue;
             }
-        }
 
-        if tiled_count <= visible_count {
-            if let Some(m) = self.monitors.get_mut(monitor_index) {
-                m.scroll_offset = 0;
+            if attrs.map_state == MapState::VIEWABLE
+                && let Some(kind) = self.unmanaged_kind(window)
+            {
+                self.map_unmanaged(window, kind)?;
+                continue;
             }
-            return Ok(());
-        }
 
-        let outer_gap = if self.gaps_enabled {
-            self.config.gap_outer_vertical
-        } else {
-            0
-        };
-        let inner_gap = if self.gaps_enabled {
-            self.config.gap_inner_vertical
-        } else {
-            0
-        };
+            if attrs.map_state == MapState::VIEWABLE {
+                let _tag = self.get_saved_tag(window, net_client_info)?;
+                self.windows.push(window);
+                continue;
+            }
 
-        let available_width = monitor.screen_width - 2 * outer_gap as i32;
-        let total_inner_gaps = inner_gap as i32 * (visible_count - 1) as i32;
-        let window_width = (available_width - total_inner_gaps) / visible_count as i32;
-        let scroll_amount = window_width + inner_gap as i32;
+            if attrs.map_state == MapState::UNMAPPED {
+                let has_wm_state = self
+                    .connection
+                    .get_property(false, window, wm_state_atom, AtomEnum::ANY, 0, 2)?
+                    .reply()
+                    .is_ok_and(|prop| !prop.value.is_empty());
+
+                if !has_wm_state {
+                    continue;
+                }
+
+                let has_wm_class = self
+                    .connection
+                    .get_property(false, window, AtomEnum::WM_CLASS, AtomEnum::STRING, 0, 1024)?
+                    .reply()
+                    .is_ok_and(|prop| !prop.value.is_empty());
+
+                if has_wm_class {
+                    let _tag = self.get_saved_tag(window, net_client_info)?;
+                    self.connection.map_window(window)?;
+                    self.windows.push(window);
+                }
+            }
+        }
+
+        if let Some(&first) = self.windows.first() {
+            self.focus(Some(first))?;
+        }
+
+        self.apply_layout()?;
+        Ok(())
+    }
+
+    fn get_saved_tag(&self, window: Window, net_client_info: Atom) -> WmResult<TagMask> {
+        match self
+            .connection
+            .get_property(false, window, net_client_info, AtomEnum::CARDINAL, 0, 2)?
+            .reply()
+        {
+            Ok(prop) if prop.value.len() >= 4 => {
+                let tags = u32::from_ne_bytes([
+                    prop.value[0],
+                    prop.value[1],
+                    prop.value[2],
+                    prop.value[3],
+                ]);
+
+                if tags != 0 && tags < (1 << self.config.tags.len()) {
+                    return Ok(tags);
+                }
+            }
+            Ok(_) => {}
+            Err(e) => {
+                eprintln!("No _NET_CLIENT_INFO property ({})", e);
+            }
+        }
+
+        Ok(self
+            .monitors
+            .get(self.selected_monitor)
+            .map(|m| m.tagset[m.selected_tags_index])
+            .unwrap_or(tag_mask(0)))
+    }
+
+    fn save_client_tag(&self, window: Window, tag: TagMask) -> WmResult<()> {
+        let net_client_info = self.atoms.net_client_info;
+
+        let bytes = tag.to_ne_bytes().to_vec();
+
+        self.connection.change_property(
+            PropMode::REPLACE,
+            window,
+            net_client_info,
+            AtomEnum::CARDINAL,
+            32,
+            1,
+            &bytes,
+        )?;
+
+        // EWMH only knows one desktop per window, so a client on several
+        // tags reports the lowest one, and one on every tag is sticky.
+        let all_tags = tag_mask(self.config.tags.len()) - 1;
+        let desktop = if tag & all_tags == all_tags {
+            0xFFFFFFFF
+        } else {
+            tag.trailing_zeros()
+        };
+
+        self.connection.change_property(
+            PropMode::REPLACE,
+            window,
+            self.atoms.net_wm_desktop,
+            AtomEnum::CARDINAL,
+            32,
+            1,
+            &desktop.to_ne_bytes(),
+        )?;
+
+        self.connection.flush()?;
+        Ok(())
+    }
+
+    fn set_wm_state(&self, window: Window, state: u32) -> WmResult<()> {
+        let wm_state_atom = self.atoms.wm_state;
+
+        let data = [state, 0u32];
+        let bytes: Vec<u8> = data.iter().flat_map(|&v| v.to_ne_bytes()).collect();
+
+        self.connection.change_property(
+            PropMode::REPLACE,
+            window,
+            wm_state_atom,
+            wm_state_atom,
+            32,
+            2,
+            &bytes,
+        )?;
+
+        self.connection.flush()?;
+        Ok(())
+    }
+
+    fn update_desktop_info(&self) -> WmResult<()> {
+        let count = self.config.tags.len() as u32;
+        self.connection.change_property(
+            PropMode::REPLACE,
+            self.root,
+            self.atoms.net_number_of_desktops,
+            AtomEnum::CARDINAL,
+            32,
+            1,
+            &count.to_ne_bytes(),
+        )?;
+
+        let names: Vec<u8> = self
+            .config
+            .tags
+            .iter()
+            .flat_map(|name| name.bytes().chain(std::iter::once(0)))
+            .collect();
+        self.connection.change_property(
+            PropMode::REPLACE,
+            self.root,
+            self.atoms.net_desktop_names,
+            self.atoms.utf8_string,
+            8,
+            names.len() as u32,
+            &names,
+        )?;
+
+        // oxwm has no large desktops, so every desktop is exactly the root
+        // window with its viewport at the origin.
+        let geometry = [
+            self.screen.width_in_pixels as u32,
+            self.screen.height_in_pixels as u32,
+        ];
+        let geometry_bytes: Vec<u8> = geometry.iter().flat_map(|v| v.to_ne_bytes()).collect();
+        self.connection.change_property(
+            PropMode::REPLACE,
+            self.root,
+            self.atoms.net_desktop_geometry,
+            AtomEnum::CARDINAL,
+            32,
+            2,
+            &geometry_bytes,
+        )?;
+
+        let viewport = vec![0u8; count as usize * 2 * 4];
+        self.connection.change_property(
+            PropMode::REPLACE,
+            self.root,
+            self.atoms.net_desktop_viewport,
+            AtomEnum::CARDINAL,
+            32,
+            count * 2,
+            &viewport,
+        )?;
+
+        self.save_selected_tags()
+    }
+
+    /// Publishes the selected monitor's usable area (its window area minus
+    /// the bar) as `_NET_WORKAREA` for every desktop. EWMH has no way to
+    /// describe per-monitor work areas, so this follows the focused monitor.
+    fn update_workarea(&mut self) -> WmResult<()> {
+        let Some(monitor) = self.monitors.get(self.selected_monitor) else {
+            return Ok(());
+        };
+
+        let bar_height = self.bar_space(self.selected_monitor);
+        let bar_offset = if monitor.top_bar { bar_height } else { 0 };
+        let workarea = [
+            monitor.window_area_x.max(0) as u32,
+            (monitor.window_area_y + bar_offset).max(0) as u32,
+            monitor.window_area_width.max(0) as u32,
+            (monitor.window_area_height - bar_height).max(0) as u32,
+        ];
+        if self.published_workarea == Some(workarea) {
+            return Ok(());
+        }
+        self.published_workarea = Some(workarea);
+
+        let count = self.config.tags.len();
+        let bytes: Vec<u8> = workarea
+            .iter()
+            .cycle()
+            .take(count * 4)
+            .flat_map(|v| v.to_ne_bytes())
+            .collect();
+        self.connection.change_property(
+            PropMode::REPLACE,
+            self.root,
+            self.atoms.net_workarea,
+            AtomEnum::CARDINAL,
+            32,
+            count as u32 * 4,
+            &bytes,
+        )?;
+        Ok(())
+    }
+
+    fn update_client_list(&self) -> WmResult<()> {
+        let window_bytes: Vec<u8> = self
+            .windows
+            .iter()
+            .flat_map(|window| window.to_ne_bytes())
+            .collect();
+
+        self.connection.change_property(
+            PropMode::REPLACE,
+            self.root,
+            self.atoms.net_client_list,
+            AtomEnum::WINDOW,
+            32,
+            self.windows.len() as u32,
+            &window_bytes,
+        )?;
+
+        self.update_client_list_stacking()
+    }
+
+    /// Publishes `_NET_CLIENT_LIST_STACKING`, bottom-most client first.
+    fn update_client_list_stacking(&self) -> WmResult<()> {
+        let stacking: Vec<Window> = (0..self.monitors.len())
+            .flat_map(|monitor_index| self.stacking_order(monitor_index).into_iter().rev())
+            .collect();
+        let window_bytes: Vec<u8> = stacking
+            .iter()
+            .flat_map(|window| window.to_ne_bytes())
+            .collect();
+
+        self.connection.change_property(
+            PropMode::REPLACE,
+            self.root,
+            self.atoms.net_client_list_stacking,
+            AtomEnum::WINDOW,
+            32,
+            stacking.len() as u32,
+            &window_bytes,
+        )?;
+
+        Ok(())
+    }
+
+    pub fn run(&mut self) -> WmResult<()> {
+        println!("oxwm started on display {}", self.screen_number);
+
+        self.grab_keys()?;
+        self.update_bar()?;
+
+        let mut last_bar_update = std::time::Instant::now();
+        const BAR_UPDATE_INTERVAL_MS: u64 = 100;
+
+        let mut last_ping = std::time::Instant::now();
+        const PING_INTERVAL_MS: u64 = 5000;
+
+        loop {
+            match self.connection.poll_for_event_with_sequence()? {
+                Some((event, _sequence)) => {
+                    if matches!(self.handle_event(event)?, Control::Quit) {
+                        return Ok(());
+                    }
+                }
+                None => {
+                    if self.monitors_dirty {
+                        self.monitors_dirty = false;
+                        self.update_monitors()?;
+                    }
+
+                    if last_bar_update.elapsed().as_millis() >= BAR_UPDATE_INTERVAL_MS as u128 {
+                        if let Some(bar) = self.bars.get_mut(self.selected_monitor) {
+                            bar.update_blocks();
+                        }
+                        if self.bars.iter().any(|bar| bar.needs_redraw()) {
+                            self.update_bar()?;
+                        }
+                        last_bar_update = std::time::Instant::now();
+                    }
+
+                    if last_ping.elapsed().as_millis() >= PING_INTERVAL_MS as u128 {
+                        self.ping_clients()?;
+                        last_ping = std::time::Instant::now();
+                    }
+
+                    if matches!(self.run_pending_hooks()?, Control::Quit) {
+                        return Ok(());
+                    }
+
+                    if matches!(self.poll_ipc()?, Control::Quit) {
+                        return Ok(());
+                    }
+
+                    if self
+                        .config_watcher
+                        .as_mut()
+                        .is_some_and(|watcher| watcher.poll())
+                    {
+                        self.reload_config()?;
+                    }
+
+                    self.tick_animations()?;
+
+                    self.connection.flush()?;
+                    std::thread::sleep(std::time::Duration::from_millis(16));
+                }
+            }
+        }
+    }
+
+    fn toggle_floating(&mut self) -> WmResult<()> {
+        let focused = self
+            .monitors
+            .get(self.selected_monitor)
+            .and_then(|m| m.selected_client);
+
+        if focused.is_none() {
+            return Ok(());
+        }
+        let focused = focused.unwrap();
+
+        if let Some(client) = self.clients.get(&focused)
+            && client.is_fullscreen
+        {
+            return Ok(());
+        }
+
+        let (is_fixed, x, y, w, h) = if let Some(client) = self.clients.get(&focused) {
+            (
+                client.is_fixed,
+                client.x_position as i32,
+                client.y_position as i32,
+                client.width as u32,
+                client.height as u32,
+            )
+        } else {
+            return Ok(());
+        };
+
+        let was_floating = self.floating_windows.contains(&focused);
+
+        if was_floating {
+            self.floating_windows.remove(&focused);
+            if let Some(client) = self.clients.get_mut(&focused) {
+                client.is_floating = false;
+            }
+        } else {
+            self.floating_windows.insert(focused);
+            if let Some(client) = self.clients.get_mut(&focused) {
+                client.is_floating = is_fixed || !client.is_floating;
+            }
+
+            self.connection.configure_window(
+                focused,
+                &ConfigureWindowAux::new()
+                    .x(x)
+                    .y(y)
+                    .width(w)
+                    .height(h)
+                    .stack_mode(StackMode::ABOVE),
+            )?;
+        }
+
+        self.apply_layout()?;
+        Ok(())
+    }
+
+    fn set_master_factor(&mut self, delta: f32) -> WmResult<()> {
+        if let Some(monitor) = self.monitors.get_mut(self.selected_monitor) {
+            let new_mfact = (monitor.master_factor + delta).clamp(0.05, 0.95);
+            monitor.master_factor = new_mfact;
+            if let Some(ref mut pertag) = monitor.pertag {
+                pertag.master_factors[pertag.current_tag] = new_mfact;
+            }
+            self.apply_layout()?;
+        }
+        Ok(())
+    }
+
+    fn inc_num_master(&mut self, delta: i32) -> WmResult<()> {
+        if let Some(monitor) = self.monitors.get_mut(self.selected_monitor) {
+            let new_nmaster = (monitor.num_master + delta).max(0);
+            monitor.num_master = new_nmaster;
+            if let Some(ref mut pertag) = monitor.pertag {
+                pertag.num_masters[pertag.current_tag] = new_nmaster;
+            }
+            self.apply_layout()?;
+        }
+        Ok(())
+    }
+
+    fn tick_animations(&mut self) -> WmResult<()> {
+        if self.scroll_animation.is_active() {
+            if let Some(new_offset) = self.scroll_animation.update() {
+                if let Some(m) = self.monitors.get_mut(self.selected_monitor) {
+                    m.scroll_offset = new_offset;
+                }
+                self.apply_layout()?;
+                self.update_bar()?;
+            }
+        }
+        Ok(())
+    }
+
+    fn scroll_layout(&mut self, direction: i32) -> WmResult<()> {
+        if self.layout.name() != "scrolling" {
+            return Ok(());
+        }
+
+        let monitor_index = self.selected_monitor;
+        let monitor = match self.monitors.get(monitor_index) {
+            Some(m) => m.clone(),
+            None => return Ok(()),
+        };
+
+        let visible_count = if monitor.num_master > 0 {
+            monitor.num_master as usize
+        } else {
+            2
+        };
+
+        let mut tiled_count = 0;
+        let mut current = self.next_tiled(monitor.clients_head, &monitor);
+        while let Some(window) = current {
+            tiled_count += 1;
+            if let Some(client) = self.clients.get(&window) {
+                current = self.next_tiled(client.next, &monitor);
+            } else {
+                break;
+            }
+        }
+
+        if tiled_count <= visible_count {
+            if let Some(m) = self.monitors.get_mut(monitor_index) {
+                m.scroll_offset = 0;
+            }
+            return Ok(());
+        }
+
+        let outer_gap = if self.gaps_enabled {
+            monitor.gap_outer_vertical as u32
+        } else {
+            0
+        };
+        let inner_gap = if self.gaps_enabled {
+            monitor.gap_inner_vertical as u32
+        } else {
+            0
+        };
+
+        let available_width = monitor.screen_width - 2 * outer_gap as i32;
+        let total_inner_gaps = inner_gap as i32 * (visible_count - 1) as i32;
+        let window_width = (available_width - total_inner_gaps) / visible_count as i32;
+        let scroll_amount = window_width + inner_gap as i32;
 
         let total_width = tiled_count as i32 * window_width + (tiled_count - 1) as i32 * inner_gap as i32;
         let max_scroll = (total_width - available_width).max(0);
@@ -838,12 +1941,12 @@ impl WindowManager {
         };
 
         let outer_gap = if self.gaps_enabled {
-            self.config.gap_outer_vertical
+            monitor.gap_outer_vertical as u32
         } else {
             0
         };
         let inner_gap = if self.gaps_enabled {
-            self.config.gap_inner_vertical
+            monitor.gap_inner_vertical as u32
         } else {
             0
         };
@@ -903,7 +2006,6 @@ impl WindowManager {
     fn toggle_bar(&mut self) -> WmResult<()> {
         if let Some(monitor) = self.monitors.get_mut(self.selected_monitor) {
             monitor.show_bar = !monitor.show_bar;
-            self.show_bar = monitor.show_bar;
             if let Some(ref mut pertag) = monitor.pertag {
                 pertag.show_bars[pertag.current_tag] = monitor.show_bar;
             }
@@ -913,11 +2015,12 @@ impl WindowManager {
         Ok(())
     }
 
-    fn get_layout_symbol(&self) -> String {
-        let layout_name = self.layout.name();
+    fn get_layout_symbol(&self, monitor_index: usize) -> String {
+        let layout = self.monitor_layout(monitor_index);
+        let layout_name = layout.name();
 
         if layout_name == "scrolling" {
-            if let Some(monitor) = self.monitors.get(self.selected_monitor) {
+            if let Some(monitor) = self.monitors.get(monitor_index) {
                 let visible_count = if monitor.num_master > 0 {
                     monitor.num_master as usize
                 } else {
@@ -937,12 +2040,12 @@ impl WindowManager {
 
                 if tiled_count > 0 {
                     let outer_gap = if self.gaps_enabled {
-                        self.config.gap_outer_vertical
+                        monitor.gap_outer_vertical as u32
                     } else {
                         0
                     };
                     let inner_gap = if self.gaps_enabled {
-                        self.config.gap_inner_vertical
+                        monitor.gap_inner_vertical as u32
                     } else {
                         0
                     };
@@ -973,7 +2076,7 @@ impl WindowManager {
             .iter()
             .find(|l| l.name == layout_name)
             .map(|l| l.symbol.clone())
-            .unwrap_or_else(|| self.layout.symbol().to_string())
+            .unwrap_or_else(|| layout.symbol().to_string())
     }
 
     fn get_keychord_indicator(&self) -> Option<String> {
@@ -1020,15 +2123,20 @@ impl WindowManager {
     }
 
     fn update_bar(&mut self) -> WmResult<()> {
-        let layout_symbol = self.get_layout_symbol();
+        let layout_symbols: Vec<String> = (0..self.monitors.len())
+            .map(|monitor_index| self.get_layout_symbol(monitor_index))
+            .collect();
         let keychord_indicator = self.get_keychord_indicator();
+        // Shared tags belong to no monitor in particular, so every bar shows
+        // all of their clients.
+        let shared = self.config.shared_tags.is_enabled();
 
         for (monitor_index, monitor) in self.monitors.iter().enumerate() {
             if let Some(bar) = self.bars.get_mut(monitor_index) {
                 let mut occupied_tags: TagMask = 0;
                 let mut urgent_tags: TagMask = 0;
                 for client in self.clients.values() {
-                    if client.monitor_index == monitor_index {
+                    if shared || client.monitor_index == monitor_index {
                         occupied_tags |= client.tags;
                         if client.is_urgent {
                             urgent_tags |= client.tags;
@@ -1036,28 +2144,73 @@ impl WindowManager {
                     }
                 }
 
+                let mut tag_icons: Vec<Vec<&Icon>> = Vec::new();
+                if self.config.tag_icons {
+                    tag_icons.resize_with(self.config.tags.len(), Vec::new);
+                    for client in self.windows.iter().filter_map(|w| self.clients.get(w)) {
+                        if (!shared && client.monitor_index != monitor_index) || client.skip_taskbar
+                        {
+                            continue;
+                        }
+                        let Some(icon) = &client.icon else {
+                            continue;
+                        };
+                        for (tag_index, icons) in tag_icons.iter_mut().enumerate() {
+                            if client.tags & tag_mask(tag_index) != 0 {
+                                icons.push(icon);
+                            }
+                        }
+                    }
+                }
+
+                let elsewhere_tags: TagMask = if shared {
+                    self.monitors
+                        .iter()
+                        .enumerate()
+                        .filter(|(other, _)| *other != monitor_index)
+                        .fold(0, |tags, (_, other)| tags | other.get_selected_tag())
+                } else {
+                    0
+                };
+
                 let draw_blocks = monitor_index == self.selected_monitor;
                 bar.invalidate();
                 bar.draw(
                     &self.connection,
                     &self.font,
                     self.display,
-                    monitor.tagset[monitor.selected_tags_index],
-                    occupied_tags,
-                    urgent_tags,
+                    &TagState {
+                        current: monitor.tagset[monitor.selected_tags_index],
+                        occupied: occupied_tags,
+                        urgent: urgent_tags,
+                        elsewhere: elsewhere_tags,
+                        icons: &tag_icons,
+                    },
                     draw_blocks,
-                    &layout_symbol,
+                    &layout_symbols[monitor_index],
                     keychord_indicator.as_deref(),
                 )?;
             }
         }
-        Ok(())
+        Ok(())
+    }
+
+    /// Lays the tray icons out again and makes room for them in the bar.
+    fn update_systray(&mut self) -> WmResult<()> {
+        let Some(tray) = &mut self.systray else {
+            return Ok(());
+        };
+        if let Some(bar) = self.bars.get_mut(tray.monitor_index()) {
+            let width = tray.layout(&self.connection, bar.width())?;
+            bar.set_tray_width(width);
+        }
+        self.update_bar()
     }
 
     fn update_tab_bars(&mut self) -> WmResult<()> {
         for (monitor_index, monitor) in self.monitors.iter().enumerate() {
             if let Some(tab_bar) = self.tab_bars.get_mut(monitor_index) {
-                let visible_windows: Vec<(Window, String)> = self
+                let visible_windows: Vec<(Window, String, Option<&Icon>)> = self
                     .windows
                     .iter()
                     .filter_map(|&window| {
@@ -1068,8 +2221,8 @@ impl WindowManager {
                             {
                                 return None;
                             }
-                            if (client.tags & monitor.tagset[monitor.selected_tags_index]) != 0 {
-                                return Some((window, client.name.clone()));
+                            if client.is_visible_on(monitor.tagset[monitor.selected_tags_index]) {
+                                return Some((window, client.name.clone(), client.icon.as_deref()));
                             }
                         }
                         None
@@ -1091,9 +2244,13 @@ impl WindowManager {
 
     fn handle_key_action(&mut self, action: KeyAction, arg: &Arg) -> WmResult<()> {
         match action {
-            KeyAction::Spawn => handlers::handle_spawn_action(action, arg, self.selected_monitor)?,
+            KeyAction::Spawn => {
+                let launch = handlers::handle_spawn_action(action, arg, self.selected_monitor)?;
+                self.record_launch(launch);
+            }
             KeyAction::SpawnTerminal => {
-                crate::signal::spawn_detached(&self.config.terminal);
+                let launch = crate::signal::spawn_detached(&self.config.terminal);
+                self.record_launch(launch);
             }
             KeyAction::KillClient => {
                 if let Some(focused) = self
@@ -1101,7 +2258,16 @@ impl WindowManager {
                     .get(self.selected_monitor)
                     .and_then(|m| m.selected_client)
                 {
-                    self.kill_client(focused)?;
+                    let unresponsive = self
+                        .clients
+                        .get(&focused)
+                        .is_some_and(|c| c.is_unresponsive);
+                    if unresponsive {
+                        self.confirm_force_kill(focused)?;
+                    } else {
+                        self.kill_client(focused)?;
+                        self.ping_client(focused)?;
+                    }
                 }
             }
             KeyAction::ToggleFullScreen => {
@@ -1112,6 +2278,7 @@ impl WindowManager {
                 if let Arg::Str(layout_name) = arg {
                     match layout_from_str(layout_name) {
                         Ok(layout) => {
+                            let previous_layout = self.layout.name();
                             self.layout = layout;
                             if let Some(monitor) = self.monitors.get_mut(self.selected_monitor) {
                                 if let Some(ref mut pertag) = monitor.pertag {
@@ -1121,6 +2288,7 @@ impl WindowManager {
                             if layout_name != "normie" && layout_name != "floating" {
                                 self.floating_windows.clear();
                             }
+                            self.emit_layout_if_changed(previous_layout);
                             self.apply_layout()?;
                             self.update_bar()?;
                             self.restack()?;
@@ -1143,6 +2311,7 @@ impl WindowManager {
                         if next_name != "normie" && next_name != "floating" {
                             self.floating_windows.clear();
                         }
+                        self.emit_layout_if_changed(current_name);
                         self.apply_layout()?;
                         self.update_bar()?;
                         self.restack()?;
@@ -1234,13 +2403,53 @@ impl WindowManager {
                 self.restack()?;
             }
             KeyAction::FocusMonitor => {
-                if let Arg::Int(direction) = arg {
-                    self.focus_monitor(*direction)?;
+                let previous_monitor = self.selected_monitor;
+                match arg {
+                    Arg::Int(direction) => self.focus_monitor(*direction)?,
+                    Arg::Str(name) => {
+                        if let Some(index) = self.monitor_by_name(name) {
+                            self.focus_monitor_index(index)?;
+                        }
+                    }
+                    _ => {}
+                }
+                if self.selected_monitor != previous_monitor {
+                    self.warp_pointer_to_monitor(self.selected_monitor)?;
                 }
             }
-            KeyAction::TagMonitor => {
-                if let Arg::Int(direction) = arg {
-                    self.send_window_to_adjacent_monitor(*direction)?;
+            KeyAction::TagMonitor => match arg {
+                Arg::Int(direction) => self.send_window_to_adjacent_monitor(*direction)?,
+                Arg::Str(name) => {
+                    if let Some(index) = self.monitor_by_name(name)
+                        && let Some(window) = self
+                            .monitors
+                            .get(self.selected_monitor)
+                            .and_then(|m| m.selected_client)
+                    {
+                        self.move_window_to_monitor(window, index)?;
+                    }
+                }
+                _ => {}
+            },
+            KeyAction::FocusMonitorDirection => {
+                if let Arg::Str(direction) = arg
+                    && let Ok(direction) = direction.parse::<Direction>()
+                    && let Some(index) = direction.neighbour(&self.monitors, self.selected_monitor)
+                {
+                    self.focus_monitor_index(index)?;
+                    self.warp_pointer_to_monitor(index)?;
+                }
+            }
+            KeyAction::TagMonitorDirection => {
+                if let Arg::Str(direction) = arg
+                    && let Ok(direction) = direction.parse::<Direction>()
+                    && let Some(index) = direction.neighbour(&self.monitors, self.selected_monitor)
+                    && let Some(window) = self
+                        .monitors
+                        .get(self.selected_monitor)
+                        .and_then(|m| m.selected_client)
+                {
+                    self.move_window_to_monitor(window, index)?;
                 }
             }
             KeyAction::ShowKeybindOverlay => {
@@ -1271,6 +2480,8 @@ impl WindowManager {
             KeyAction::ScrollRight => {
                 self.scroll_layout(1)?;
             }
+            // Needs to be able to quit, so it is dispatched by run_action.
+            KeyAction::LuaFunction => {}
             KeyAction::None => {}
         }
         Ok(())
@@ -1282,7 +2493,7 @@ impl WindowManager {
             let selected_tags = monitor
                 .map(|m| m.tagset[m.selected_tags_index])
                 .unwrap_or(0);
-            (client.tags & selected_tags) != 0
+            client.is_visible_on(selected_tags)
         } else {
             false
         }
@@ -1294,8 +2505,7 @@ impl WindowManager {
             let mut current = monitor.clients_head;
             while let Some(window) = current {
                 if let Some(client) = self.clients.get(&window) {
-                    let visible_tags = client.tags & monitor.tagset[monitor.selected_tags_index];
-                    if visible_tags != 0 {
+                    if client.is_visible_on(monitor.tagset[monitor.selected_tags_index]) {
                         result.push(window);
                     }
                     current = client.next;
@@ -1313,8 +2523,7 @@ impl WindowManager {
             let mut current = monitor.clients_head;
             while let Some(window) = current {
                 if let Some(client) = self.clients.get(&window) {
-                    let visible_tags = client.tags & monitor.tagset[monitor.selected_tags_index];
-                    if visible_tags != 0 {
+                    if client.is_visible_on(monitor.tagset[monitor.selected_tags_index]) {
                         result.push(window);
                     }
                     current = client.next;
@@ -1378,7 +2587,12 @@ impl WindowManager {
             if let Some(target_monitor) = self.monitors.get(target_monitor_index) {
                 client.tags = target_monitor.tagset[target_monitor.selected_tags_index];
             }
+            client.is_sticky = false;
         }
+        if let Some(tags) = self.clients.get(&window).map(|client| client.tags) {
+            self.save_client_tag(window, tags)?;
+        }
+        self.write_net_wm_state(window)?;
 
         self.attach_aside(window, target_monitor_index);
         self.attach_stack(window, target_monitor_index);
@@ -1416,7 +2630,7 @@ impl WindowManager {
             return false;
         };
 
-        (client.tags & monitor.tagset[monitor.selected_tags_index]) != 0
+        client.is_visible_on(monitor.tagset[monitor.selected_tags_index])
     }
 
     fn showhide(&mut self, window: Option<Window>) -> WmResult<()> {
@@ -1433,7 +2647,7 @@ impl WindowManager {
             None => return Ok(()),
         };
 
-        let is_visible = (client.tags & monitor.tagset[monitor.selected_tags_index]) != 0;
+        let is_visible = client.is_visible_on(monitor.tagset[monitor.selected_tags_index]);
 
         if is_visible {
             self.connection.configure_window(
@@ -1480,299 +2694,723 @@ impl WindowManager {
                 }
             }
 
-            self.showhide(client.stack_next)?;
-        } else {
-            self.showhide(client.stack_next)?;
+            self.showhide(client.stack_next)?;
+        } else {
+            self.showhide(client.stack_next)?;
+
+            let width = client.width_with_border() as i32;
+            self.connection.configure_window(
+                window,
+                &ConfigureWindowAux::new()
+                    .x(width * -2)
+                    .y(client.y_position as i32),
+            )?;
+        }
+
+        Ok(())
+    }
+
+    pub fn view_tag(&mut self, tag_index: usize) -> WmResult<()> {
+        if tag_index >= self.config.tags.len() {
+            return Ok(());
+        }
+
+        let new_tagset = tag_mask(tag_index);
+        let mut layout_name: Option<String> = None;
+        let mut toggle_bar = false;
+
+        // With shared tags the tag may already be shown on another monitor.
+        let mut swap_with = None;
+        if self.config.shared_tags.is_enabled()
+            && let Some(monitor) = self.monitors.get(self.selected_monitor)
+        {
+            let current = monitor.get_selected_tag();
+            let target = if new_tagset == current && self.config.tag_back_and_forth {
+                monitor.tagset[monitor.selected_tags_index ^ 1]
+            } else {
+                new_tagset
+            };
+            if let Some(other) = self.monitor_showing(target) {
+                // Going back to a multi-tag view can overlap several other
+                // monitors, and neither swapping nor focusing one of them
+                // would keep each tag on a single monitor.
+                if target.count_ones() > 1 {
+                    return Ok(());
+                }
+                if self.config.shared_tags == SharedTags::Focus {
+                    return self.focus_monitor_index(other);
+                }
+                swap_with = Some((other, current));
+            }
+        }
+
+        if let Some(monitor) = self.monitors.get_mut(self.selected_monitor) {
+            if new_tagset == monitor.tagset[monitor.selected_tags_index] {
+                if !self.config.tag_back_and_forth {
+                    return Ok(());
+                }
+                monitor.tagset.swap(0, 1);
+                if let Some(ref mut pertag) = monitor.pertag {
+                    let tmp = pertag.previous_tag;
+                    pertag.previous_tag = pertag.current_tag;
+                    pertag.current_tag = tmp;
+                }
+            } else {
+                monitor.selected_tags_index ^= 1;
+                monitor.tagset[monitor.selected_tags_index] = new_tagset;
+                if let Some(ref mut pertag) = monitor.pertag {
+                    pertag.previous_tag = pertag.current_tag;
+                    pertag.current_tag = tag_index + 1;
+                }
+            }
+
+            if let Some(ref pertag) = monitor.pertag {
+                monitor.num_master = pertag.num_masters[pertag.current_tag];
+                monitor.master_factor = pertag.master_factors[pertag.current_tag];
+                layout_name = Some(pertag.layouts[pertag.current_tag].clone());
+                if monitor.show_bar != pertag.show_bars[pertag.current_tag] {
+                    toggle_bar = true;
+                }
+            }
+        }
+
+        let previous_layout = self.layout.name();
+        if let Some(name) = layout_name {
+            if let Ok(layout) = layout_from_str(&name) {
+                self.layout = layout;
+            }
+        }
+
+        if toggle_bar {
+            self.toggle_bar()?;
+        }
+
+        if let Some((other, tagset)) = swap_with {
+            self.set_monitor_view(other, tagset);
+        }
+        if self.config.shared_tags.is_enabled() {
+            self.assign_shared_clients();
+        }
+
+        self.save_selected_tags()?;
+        self.emit_tag();
+        self.emit_layout_if_changed(previous_layout);
+        self.focus(None)?;
+        self.apply_layout()?;
+        self.update_bar()?;
+
+        Ok(())
+    }
+
+    pub fn toggleview(&mut self, tag_index: usize) -> WmResult<()> {
+        if tag_index >= self.config.tags.len() {
+            return Ok(());
+        }
+
+        // A shared tag can't be shown on two monitors at once.
+        if self.config.shared_tags.is_enabled()
+            && self.monitor_showing(tag_mask(tag_index)).is_some()
+        {
+            return Ok(());
+        }
+
+        let num_tags = self.config.tags.len();
+        let all_tags_mask = (1u32 << num_tags) - 1;
+        let mut layout_name: Option<String> = None;
+        let mut toggle_bar = false;
+
+        if let Some(monitor) = self.monitors.get_mut(self.selected_monitor) {
+            let mask = tag_mask(tag_index);
+            let new_tagset = monitor.tagset[monitor.selected_tags_index] ^ mask;
+
+            if new_tagset == 0 {
+                return Ok(());
+            }
+
+            monitor.tagset[monitor.selected_tags_index] = new_tagset;
+
+            if let Some(ref mut pertag) = monitor.pertag {
+                if new_tagset == all_tags_mask {
+                    pertag.previous_tag = pertag.current_tag;
+                    pertag.current_tag = 0;
+                }
+
+                if pertag.current_tag > 0 && (new_tagset & (1 << (pertag.current_tag - 1))) == 0 {
+                    pertag.previous_tag = pertag.current_tag;
+                    pertag.current_tag = (new_tagset.trailing_zeros() as usize) + 1;
+                }
+
+                monitor.num_master = pertag.num_masters[pertag.current_tag];
+                monitor.master_factor = pertag.master_factors[pertag.current_tag];
+                layout_name = Some(pertag.layouts[pertag.current_tag].clone());
+                if monitor.show_bar != pertag.show_bars[pertag.current_tag] {
+                    toggle_bar = true;
+                }
+            }
+        }
+
+        let previous_layout = self.layout.name();
+        if let Some(name) = layout_name {
+            if let Ok(layout) = layout_from_str(&name) {
+                self.layout = layout;
+            }
+        }
+
+        if toggle_bar {
+            self.toggle_bar()?;
+        }
+
+        if self.config.shared_tags.is_enabled() {
+            self.assign_shared_clients();
+        }
+
+        self.save_selected_tags()?;
+        self.emit_tag();
+        self.emit_layout_if_changed(previous_layout);
+        self.focus(None)?;
+        self.apply_layout()?;
+        self.update_bar()?;
+
+        Ok(())
+    }
+
+    fn save_selected_tags(&self) -> WmResult<()> {
+        let net_current_desktop = self.atoms.net_current_desktop;
+
+        let selected_tags = self
+            .monitors
+            .get(self.selected_monitor)
+            .map(|m| m.tagset[m.selected_tags_index])
+            .unwrap_or(tag_mask(0));
+        let desktop = selected_tags.trailing_zeros();
+
+        let bytes = (desktop as u32).to_ne_bytes();
+        self.connection.change_property(
+            PropMode::REPLACE,
+            self.root,
+            net_current_desktop,
+            AtomEnum::CARDINAL,
+            32,
+            1,
+            &bytes,
+        )?;
+
+        self.connection.flush()?;
+        Ok(())
+    }
+
+    pub fn move_to_tag(&mut self, tag_index: usize) -> WmResult<()> {
+        if tag_index >= self.config.tags.len() {
+            return Ok(());
+        }
+
+        let focused = match self
+            .monitors
+            .get(self.selected_monitor)
+            .and_then(|m| m.selected_client)
+        {
+            Some(win) => win,
+            None => return Ok(()),
+        };
+
+        let mask = tag_mask(tag_index);
+
+        if let Some(client) = self.clients.get_mut(&focused) {
+            client.tags = mask;
+            client.is_sticky = false;
+        }
+        self.write_net_wm_state(focused)?;
+
+        if let Err(error) = self.save_client_tag(focused, mask) {
+            eprintln!("Failed to save client tag: {:?}", error);
+        }
+
+        if self.config.shared_tags.is_enabled() {
+            self.assign_shared_clients();
+        }
+
+        self.focus(None)?;
+        self.apply_layout()?;
+        self.update_bar()?;
+
+        Ok(())
+    }
+
+    pub fn toggletag(&mut self, tag_index: usize) -> WmResult<()> {
+        if tag_index >= self.config.tags.len() {
+            return Ok(());
+        }
+
+        let focused = match self
+            .monitors
+            .get(self.selected_monitor)
+            .and_then(|m| m.selected_client)
+        {
+            Some(win) => win,
+            None => return Ok(()),
+        };
+
+        let mask = tag_mask(tag_index);
+        let current_tags = self.clients.get(&focused).map(|c| c.tags).unwrap_or(0);
+        let new_tags = current_tags ^ mask;
+
+        if new_tags == 0 {
+            return Ok(());
+        }
+
+        let all_tags = tag_mask(self.config.tags.len()) - 1;
+        if let Some(client) = self.clients.get_mut(&focused) {
+            client.tags = new_tags;
+            client.is_sticky &= new_tags & all_tags == all_tags;
+        }
+        self.write_net_wm_state(focused)?;
+
+        if let Err(error) = self.save_client_tag(focused, new_tags) {
+            eprintln!("Failed to save client tag: {:?}", error);
+        }
 
-            let width = client.width_with_border() as i32;
-            self.connection.configure_window(
-                window,
-                &ConfigureWindowAux::new()
-                    .x(width * -2)
-                    .y(client.y_position as i32),
-            )?;
+        if self.config.shared_tags.is_enabled() {
+            self.assign_shared_clients();
         }
 
+        self.focus(None)?;
+        self.apply_layout()?;
+        self.update_bar()?;
+
         Ok(())
     }
 
-    pub fn view_tag(&mut self, tag_index: usize) -> WmResult<()> {
-        if tag_index >= self.config.tags.len() {
+    pub fn cycle_focus(&mut self, direction: i32) -> WmResult<()> {
+        let visible = self.visible_windows();
+
+        if visible.is_empty() {
             return Ok(());
         }
 
-        let new_tagset = tag_mask(tag_index);
-        let mut layout_name: Option<String> = None;
-        let mut toggle_bar = false;
+        let current = self
+            .monitors
+            .get(self.selected_monitor)
+            .and_then(|m| m.selected_client);
 
-        if let Some(monitor) = self.monitors.get_mut(self.selected_monitor) {
-            if new_tagset == monitor.tagset[monitor.selected_tags_index] {
-                if !self.config.tag_back_and_forth {
-                    return Ok(());
-                }
-                monitor.tagset.swap(0, 1);
-                if let Some(ref mut pertag) = monitor.pertag {
-                    let tmp = pertag.previous_tag;
-                    pertag.previous_tag = pertag.current_tag;
-                    pertag.current_tag = tmp;
-                }
+        let next_window = if let Some(current) = current {
+            if let Some(current_index) = visible.iter().position(|&w| w == current) {
+                let next_index = if direction > 0 {
+                    (current_index + 1) % visible.len()
+                } else {
+                    (current_index + visible.len() - 1) % visible.len()
+                };
+                visible[next_index]
             } else {
-                monitor.selected_tags_index ^= 1;
-                monitor.tagset[monitor.selected_tags_index] = new_tagset;
-                if let Some(ref mut pertag) = monitor.pertag {
-                    pertag.previous_tag = pertag.current_tag;
-                    pertag.current_tag = tag_index + 1;
-                }
+                visible[0]
             }
+        } else {
+            visible[0]
+        };
 
-            if let Some(ref pertag) = monitor.pertag {
-                monitor.num_master = pertag.num_masters[pertag.current_tag];
-                monitor.master_factor = pertag.master_factors[pertag.current_tag];
-                layout_name = Some(pertag.layouts[pertag.current_tag].clone());
-                if monitor.show_bar != pertag.show_bars[pertag.current_tag] {
-                    toggle_bar = true;
-                }
-            }
+        let is_tabbed = self.layout.name() == "tabbed";
+        if is_tabbed {
+            self.connection.configure_window(
+                next_window,
+                &ConfigureWindowAux::new().stack_mode(StackMode::ABOVE),
+            )?;
         }
 
-        if let Some(name) = layout_name {
-            if let Ok(layout) = layout_from_str(&name) {
-                self.layout = layout;
-            }
+        self.focus(Some(next_window))?;
+
+        if is_tabbed {
+            self.update_tab_bars()?;
         }
 
-        if toggle_bar {
-            self.toggle_bar()?;
+        Ok(())
+    }
+
+    fn grab_keys(&mut self) -> WmResult<()> {
+        self.keyboard_mapping = Some(keyboard::grab_keys(
+            &self.connection,
+            self.root,
+            &self.config.keybindings,
+            self.current_key,
+        )?);
+        Ok(())
+    }
+
+    fn kill_client(&self, window: Window) -> WmResult<()> {
+        if self.send_event(window, self.atoms.wm_delete_window)? {
+            self.connection.flush()?;
+        } else {
+            eprintln!(
+                "Window {} doesn't support WM_DELETE_WINDOW, killing forcefully",
+                window
+            );
+            self.connection.kill_client(window)?;
+            self.connection.flush()?;
         }
+        Ok(())
+    }
 
-        self.save_selected_tags()?;
-        self.focus(None)?;
-        self.apply_layout()?;
-        self.update_bar()?;
+    /// Pings every client that supports `_NET_WM_PING`, and marks the ones
+    /// that left an earlier ping unanswered as unresponsive.
+    fn ping_clients(&mut self) -> WmResult<()> {
+        const PING_TIMEOUT: Duration = Duration::from_secs(3);
 
+        let windows: Vec<Window> = self.clients.keys().copied().collect();
+        for window in windows {
+            match self.pending_pings.get(&window) {
+                Some(sent_at) if sent_at.elapsed() >= PING_TIMEOUT => {
+                    self.set_unresponsive(window, true)?;
+                }
+                Some(_) => {}
+                None => self.ping_client(window)?,
+            }
+        }
         Ok(())
     }
 
-    pub fn toggleview(&mut self, tag_index: usize) -> WmResult<()> {
-        if tag_index >= self.config.tags.len() {
+    fn ping_client(&mut self, window: Window) -> WmResult<()> {
+        if self.pending_pings.contains_key(&window)
+            || !self.clients.get(&window).is_some_and(|c| c.supports_ping)
+        {
             return Ok(());
         }
 
-        let num_tags = self.config.tags.len();
-        let all_tags_mask = (1u32 << num_tags) - 1;
-        let mut layout_name: Option<String> = None;
-        let mut toggle_bar = false;
-
-        if let Some(monitor) = self.monitors.get_mut(self.selected_monitor) {
-            let mask = tag_mask(tag_index);
-            let new_tagset = monitor.tagset[monitor.selected_tags_index] ^ mask;
+        let event = x11rb::protocol::xproto::ClientMessageEvent {
+            response_type: x11rb::protocol::xproto::CLIENT_MESSAGE_EVENT,
+            format: 32,
+            sequence: 0,
+            window,
+            type_: self.atoms.wm_protocols,
+            data: x11rb::protocol::xproto::ClientMessageData::from([
+                self.atoms.net_wm_ping,
+                x11rb::CURRENT_TIME,
+                window,
+                0,
+                0,
+            ]),
+        };
+        self.connection
+            .send_event(false, window, EventMask::NO_EVENT, event)?;
+        self.pending_pings.insert(window, Instant::now());
+        Ok(())
+    }
 
-            if new_tagset == 0 {
-                return Ok(());
-            }
+    fn set_unresponsive(&mut self, window: Window, unresponsive: bool) -> WmResult<()> {
+        let Some(client) = self.clients.get_mut(&window) else {
+            return Ok(());
+        };
+        if client.is_unresponsive == unresponsive {
+            return Ok(());
+        }
+        client.is_unresponsive = unresponsive;
 
-            monitor.tagset[monitor.selected_tags_index] = new_tagset;
+        let focused = self
+            .monitors
+            .get(self.selected_monitor)
+            .and_then(|m| m.selected_client)
+            == Some(window);
+        self.connection.change_window_attributes(
+            window,
+            &ChangeWindowAttributesAux::new().border_pixel(self.border_color(window, focused)),
+        )?;
+        self.connection.flush()?;
+        Ok(())
+    }
 
-            if let Some(ref mut pertag) = monitor.pertag {
-                if new_tagset == all_tags_mask {
-                    pertag.previous_tag = pertag.current_tag;
-                    pertag.current_tag = 0;
-                }
+    fn border_color(&self, window: Window, focused: bool) -> u32 {
+        if self.clients.get(&window).is_some_and(|c| c.is_unresponsive) {
+            self.config.border_unresponsive
+        } else if focused {
+            self.config.border_focused
+        } else {
+            self.config.border_unfocused
+        }
+    }
 
-                if pertag.current_tag > 0 && (new_tagset & (1 << (pertag.current_tag - 1))) == 0 {
-                    pertag.previous_tag = pertag.current_tag;
-                    pertag.current_tag = (new_tagset.trailing_zeros() as usize) + 1;
-                }
+    fn confirm_force_kill(&mut self, window: Window) -> WmResult<()> {
+        let name = self
+            .clients
+            .get(&window)
+            .map(|c| c.name.clone())
+            .unwrap_or_default();
+        let message = format!("\"{}\" is not responding.\nForce it to quit?", name);
 
-                monitor.num_master = pertag.num_masters[pertag.current_tag];
-                monitor.master_factor = pertag.master_factors[pertag.current_tag];
-                layout_name = Some(pertag.layouts[pertag.current_tag].clone());
-                if monitor.show_bar != pertag.show_bars[pertag.current_tag] {
-                    toggle_bar = true;
-                }
+        let monitor = &self.monitors[self.selected_monitor];
+        self.confirm_overlay.show_prompt(
+            &self.connection,
+            &self.font,
+            &message,
+            monitor.screen_x as i16,
+            monitor.screen_y as i16,
+            monitor.screen_width as u16,
+            monitor.screen_height as u16,
+        )?;
+        let grab = self
+            .connection
+            .grab_keyboard(
+                false,
+                self.confirm_overlay.window(),
+                x11rb::CURRENT_TIME,
+                GrabMode::ASYNC,
+                GrabMode::ASYNC,
+            )?
+            .reply()?;
+        // Without the grab the prompt would never see Y, N or Escape.
+        if grab.status != GrabStatus::SUCCESS {
+            if let Err(error) = self.confirm_overlay.hide(&self.connection) {
+                eprintln!("Failed to hide confirm overlay: {:?}", error);
             }
+            return Ok(());
         }
+        self.pending_kill = Some(window);
+        Ok(())
+    }
 
-        if let Some(name) = layout_name {
-            if let Ok(layout) = layout_from_str(&name) {
-                self.layout = layout;
+    fn close_confirm(&mut self) -> WmResult<()> {
+        self.pending_kill = None;
+        if let Err(error) = self.confirm_overlay.hide(&self.connection) {
+            eprintln!("Failed to hide confirm overlay: {:?}", error);
+        }
+        self.connection.ungrab_keyboard(x11rb::CURRENT_TIME)?;
+        self.connection.flush()?;
+        Ok(())
+    }
+
+    /// SIGKILLs a hung client through `_NET_WM_PID`, falling back to
+    /// `XKillClient` when it has no pid or runs on another host.
+    fn force_kill(&self, window: Window) -> WmResult<()> {
+        if let Some(pid) = self.local_pid(window) {
+            match crate::signal::kill_process(pid) {
+                Ok(()) => return Ok(()),
+                Err(error) => eprintln!("Failed to kill pid {}: {}", pid, error),
             }
         }
+        self.connection.kill_client(window)?;
+        self.connection.flush()?;
+        Ok(())
+    }
 
-        if toggle_bar {
-            self.toggle_bar()?;
+    /// Remembers the monitor and tags a command was spawned on.
+    fn record_launch(&mut self, launch: Option<Launch>) {
+        self.launches
+            .retain(|pending| pending.started.elapsed() < LAUNCH_TIMEOUT);
+        let Some(launch) = launch else {
+            return;
+        };
+        let Some(monitor) = self.monitors.get(self.selected_monitor) else {
+            return;
+        };
+        self.launches.push(PendingLaunch {
+            launch,
+            monitor_index: self.selected_monitor,
+            tags: monitor.tagset[monitor.selected_tags_index],
+            started: Instant::now(),
+            matched: false,
+        });
+    }
+
+    /// Finds the launch a new window belongs to, first by `_NET_STARTUP_ID`
+    /// and then by `_NET_WM_PID`, and returns the monitor and tags it was
+    /// spawned on. A startup ID is only good for one window, so that launch
+    /// is dropped; a PID only places the first window it matches.
+    fn launch_placement(&mut self, window: Window) -> Option<(usize, TagMask)> {
+        self.launches
+            .retain(|pending| pending.started.elapsed() < LAUNCH_TIMEOUT);
+        if self.launches.is_empty() {
+            return None;
         }
 
-        self.save_selected_tags()?;
-        self.focus(None)?;
-        self.apply_layout()?;
-        self.update_bar()?;
+        let startup_id = self
+            .connection
+            .get_property(
+                false,
+                window,
+                self.atoms.net_startup_id,
+                self.atoms.utf8_string,
+                0,
+                256,
+            )
+            .ok()
+            .and_then(|cookie| cookie.reply().ok())
+            .and_then(|reply| String::from_utf8(reply.value).ok());
+
+        let by_startup_id = match startup_id {
+            Some(id) if !id.is_empty() => self
+                .launches
+                .iter()
+                .rposition(|pending| pending.launch.startup_id == id),
+            _ => None,
+        };
+        let (monitor_index, tags) = match by_startup_id {
+            Some(index) => {
+                let pending = self.launches.remove(index);
+                (pending.monitor_index, pending.tags)
+            }
+            None => {
+                let pid = self.local_pid(window)?;
+                let pending = self.launches.iter_mut().rev().find(|pending| {
+                    !pending.matched
+                        && pending
+                            .launch
+                            .pid
+                            .is_some_and(|launched| crate::signal::is_descendant_of(pid, launched))
+                })?;
+                pending.matched = true;
+                (pending.monitor_index, pending.tags)
+            }
+        };
+
+        let all_tags = tag_mask(self.config.tags.len()) - 1;
+        let tags = tags & all_tags;
+        if monitor_index >= self.monitors.len() || tags == 0 {
+            return None;
+        }
+        Some((monitor_index, tags))
+    }
+
+    fn local_pid(&self, window: Window) -> Option<u32> {
+        let pid = self
+            .connection
+            .get_property(
+                false,
+                window,
+                self.atoms.net_wm_pid,
+                AtomEnum::CARDINAL,
+                0,
+                1,
+            )
+            .ok()?
+            .reply()
+            .ok()?
+            .value32()?
+            .next()?;
+
+        // _NET_WM_PID is only meaningful on the machine the client runs on.
+        let machine = self
+            .connection
+            .get_property(
+                false,
+                window,
+                self.atoms.wm_client_machine,
+                AtomEnum::ANY,
+                0,
+                64,
+            )
+            .ok()?
+            .reply()
+            .ok()?;
+        if !machine.value.is_empty() {
+            let hostname = std::fs::read_to_string("/proc/sys/kernel/hostname").ok()?;
+            if machine.value != hostname.trim().as_bytes() {
+                return None;
+            }
+        }
 
-        Ok(())
+        Some(pid)
     }
 
-    fn save_selected_tags(&self) -> WmResult<()> {
-        let net_current_desktop = self.atoms.net_current_desktop;
-
-        let selected_tags = self
-            .monitors
-            .get(self.selected_monitor)
-            .map(|m| m.tagset[m.selected_tags_index])
-            .unwrap_or(tag_mask(0));
-        let desktop = selected_tags.trailing_zeros();
-
-        let bytes = (desktop as u32).to_ne_bytes();
+    fn set_frame_extents(&self, window: Window, border_width: u32) -> WmResult<()> {
+        let bytes: Vec<u8> = [border_width; 4]
+            .iter()
+            .flat_map(|v| v.to_ne_bytes())
+            .collect();
         self.connection.change_property(
             PropMode::REPLACE,
-            self.root,
-            net_current_desktop,
+            window,
+            self.atoms.net_frame_extents,
             AtomEnum::CARDINAL,
             32,
-            1,
+            4,
             &bytes,
         )?;
-
-        self.connection.flush()?;
         Ok(())
     }
 
-    pub fn move_to_tag(&mut self, tag_index: usize) -> WmResult<()> {
-        if tag_index >= self.config.tags.len() {
+    /// `_NET_WM_MOVERESIZE`: a client-side decorated window asks us to start
+    /// an interactive move or resize, as if it had been clicked with the
+    /// modifier held.
+    fn start_moveresize(&mut self, window: Window, direction: u32) -> WmResult<()> {
+        const MOVE: u32 = 8;
+
+        // A cancel only matters while a move or resize is running, and the
+        // loops in drag_window and resize_window_from watch for it there.
+        // The keyboard variants (9 and 10) aren't supported: both loops
+        // follow the pointer and end on a button release.
+        if direction != MOVE && direction > 7 {
             return Ok(());
         }
 
-        let focused = match self
-            .monitors
-            .get(self.selected_monitor)
-            .and_then(|m| m.selected_client)
-        {
-            Some(win) => win,
-            None => return Ok(()),
-        };
-
-        let mask = tag_mask(tag_index);
-
-        if let Some(client) = self.clients.get_mut(&focused) {
-            client.tags = mask;
+        // drag_window and resize_window_with_mouse act on the selection.
+        if let Some(monitor_index) = self.clients.get(&window).map(|c| c.monitor_index) {
+            self.select_monitor(monitor_index);
         }
+        self.focus(Some(window))?;
 
-        if let Err(error) = self.save_client_tag(focused, mask) {
-            eprintln!("Failed to save client tag: {:?}", error);
+        if direction == MOVE {
+            self.drag_window(window)
+        } else {
+            self.resize_window_from(window, ResizeEdges::from_moveresize(direction))
         }
+    }
 
-        self.focus(None)?;
-        self.apply_layout()?;
-        self.update_bar()?;
-
-        Ok(())
+    /// Whether `event` is a `_NET_WM_MOVERESIZE` cancelling the move or
+    /// resize running on `window`.
+    fn is_moveresize_cancel(&self, event: &ClientMessageEvent, window: Window) -> bool {
+        event.type_ == self.atoms.net_wm_moveresize
+            && event.window == window
+            && event.data.as_data32()[2] == MOVERESIZE_CANCEL
     }
 
-    pub fn toggletag(&mut self, tag_index: usize) -> WmResult<()> {
-        if tag_index >= self.config.tags.len() {
+    /// `_NET_MOVERESIZE_WINDOW`: like a ConfigureRequest, but in root
+    /// coordinates. Bits 8-11 of `flags` say which of x, y, width and
+    /// height are present.
+    fn moveresize_window(&mut self, window: Window, flags: u32, values: &[u32]) -> WmResult<()> {
+        let Some(client) = self.clients.get(&window) else {
             return Ok(());
-        }
-
-        let focused = match self
-            .monitors
-            .get(self.selected_monitor)
-            .and_then(|m| m.selected_client)
-        {
-            Some(win) => win,
-            None => return Ok(()),
         };
 
-        let mask = tag_mask(tag_index);
-        let current_tags = self.clients.get(&focused).map(|c| c.tags).unwrap_or(0);
-        let new_tags = current_tags ^ mask;
-
-        if new_tags == 0 {
-            return Ok(());
+        let has_layout = self.monitor_layout(client.monitor_index).name() != "normie";
+        if client.is_fullscreen || (!client.is_floating && has_layout) {
+            return self.send_configure_notify(window);
         }
 
-        if let Some(client) = self.clients.get_mut(&focused) {
-            client.tags = new_tags;
-        }
+        let mut x = client.x_position as i32;
+        let mut y = client.y_position as i32;
+        let mut width = client.width as i32;
+        let mut height = client.height as i32;
 
-        if let Err(error) = self.save_client_tag(focused, new_tags) {
-            eprintln!("Failed to save client tag: {:?}", error);
+        if flags & (1 << 8) != 0 {
+            x = values[0] as i32;
         }
-
-        self.focus(None)?;
-        self.apply_layout()?;
-        self.update_bar()?;
-
-        Ok(())
-    }
-
-    pub fn cycle_focus(&mut self, direction: i32) -> WmResult<()> {
-        let visible = self.visible_windows();
-
-        if visible.is_empty() {
-            return Ok(());
+        if flags & (1 << 9) != 0 {
+            y = values[1] as i32;
         }
-
-        let current = self
-            .monitors
-            .get(self.selected_monitor)
-            .and_then(|m| m.selected_client);
-
-        let next_window = if let Some(current) = current {
-            if let Some(current_index) = visible.iter().position(|&w| w == current) {
-                let next_index = if direction > 0 {
-                    (current_index + 1) % visible.len()
-                } else {
-                    (current_index + visible.len() - 1) % visible.len()
-                };
-                visible[next_index]
-            } else {
-                visible[0]
-            }
-        } else {
-            visible[0]
-        };
-
-        let is_tabbed = self.layout.name() == "tabbed";
-        if is_tabbed {
-            self.connection.configure_window(
-                next_window,
-                &ConfigureWindowAux::new().stack_mode(StackMode::ABOVE),
-            )?;
+        if flags & (1 << 10) != 0 {
+            width = (values[2] as i32).max(1);
         }
-
-        self.focus(Some(next_window))?;
-
-        if is_tabbed {
-            self.update_tab_bars()?;
+        if flags & (1 << 11) != 0 {
+            height = (values[3] as i32).max(1);
         }
 
-        Ok(())
-    }
-
-    fn grab_keys(&mut self) -> WmResult<()> {
-        self.keyboard_mapping = Some(keyboard::grab_keys(
-            &self.connection,
-            self.root,
-            &self.config.keybindings,
-            self.current_key,
-        )?);
-        Ok(())
-    }
+        if let Some(client) = self.clients.get_mut(&window) {
+            client.x_position = x as i16;
+            client.y_position = y as i16;
+            client.width = width as u16;
+            client.height = height as u16;
+        }
 
-    fn kill_client(&self, window: Window) -> WmResult<()> {
-        if self.send_event(window, self.atoms.wm_delete_window)? {
-            self.connection.flush()?;
-        } else {
-            eprintln!(
-                "Window {} doesn't support WM_DELETE_WINDOW, killing forcefully",
-                window
-            );
-            self.connection.kill_client(window)?;
-            self.connection.flush()?;
+        if self.is_visible(window) {
+            self.connection.configure_window(
+                window,
+                &ConfigureWindowAux::new()
+                    .x(x)
+                    .y(y)
+                    .width(width as u32)
+                    .height(height as u32),
+            )?;
         }
-        Ok(())
+        self.send_configure_notify(window)
     }
 
-    fn send_event(&self, window: Window, protocol: Atom) -> WmResult<bool> {
+    fn supports_protocol(&self, window: Window, protocol: Atom) -> WmResult<bool> {
         let protocols_reply = self
             .connection
             .get_property(
@@ -1796,7 +3434,11 @@ impl WindowManager {
             .map(|chunk| u32::from_ne_bytes([chunk[0], chunk[1], chunk[2], chunk[3]]))
             .collect();
 
-        if !protocols.contains(&protocol) {
+        Ok(protocols.contains(&protocol))
+    }
+
+    fn send_event(&self, window: Window, protocol: Atom) -> WmResult<bool> {
+        if !self.supports_protocol(window, protocol)? {
             return Ok(false);
         }
 
@@ -1822,8 +3464,12 @@ impl WindowManager {
     }
 
     fn set_urgent(&mut self, window: Window, urgent: bool) -> WmResult<()> {
-        if let Some(client) = self.clients.get_mut(&window) {
+        if let Some(client) = self.clients.get_mut(&window)
+            && client.is_urgent != urgent
+        {
             client.is_urgent = urgent;
+            self.emit(IpcEvent::Urgent { window, urgent });
+            self.write_net_wm_state(window)?;
         }
 
         let hints_reply = self
@@ -1864,26 +3510,6 @@ impl WindowManager {
         Ok(())
     }
 
-    fn get_window_atom_property(&self, window: Window, property: Atom) -> WmResult<Option<Atom>> {
-        let reply = self
-            .connection
-            .get_property(false, window, property, AtomEnum::ATOM, 0, 1)?
-            .reply();
-
-        match reply {
-            Ok(prop) if !prop.value.is_empty() && prop.value.len() >= 4 => {
-                let atom = u32::from_ne_bytes([
-                    prop.value[0],
-                    prop.value[1],
-                    prop.value[2],
-                    prop.value[3],
-                ]);
-                Ok(Some(atom))
-            }
-            _ => Ok(None),
-        }
-    }
-
     fn get_window_atom_list_property(&self, window: Window, property: Atom) -> WmResult<Vec<Atom>> {
         let reply = self
             .connection
@@ -1926,17 +3552,6 @@ impl WindowManager {
         let monitor = &self.monitors[monitor_idx];
 
         if fullscreen && !self.fullscreen_windows.contains(&window) {
-            let bytes = self.atoms.net_wm_state_fullscreen.to_ne_bytes().to_vec();
-            self.connection.change_property(
-                PropMode::REPLACE,
-                window,
-                self.atoms.net_wm_state,
-                AtomEnum::ATOM,
-                32,
-                1,
-                &bytes,
-            )?;
-
             if let Some(client) = self.clients.get_mut(&window) {
                 client.is_fullscreen = true;
                 client.old_state = client.is_floating;
@@ -1948,6 +3563,7 @@ impl WindowManager {
                 client.border_width = 0;
                 client.is_floating = true;
             }
+            self.write_net_wm_state(window)?;
 
             self.fullscreen_windows.insert(window);
             self.floating_windows.insert(window);
@@ -1962,64 +3578,277 @@ impl WindowManager {
                     .height(monitor.screen_height as u32)
                     .stack_mode(x11rb::protocol::xproto::StackMode::ABOVE),
             )?;
+            self.set_frame_extents(window, 0)?;
 
             self.connection.flush()?;
         } else if !fullscreen && self.fullscreen_windows.contains(&window) {
-            self.connection.change_property(
-                PropMode::REPLACE,
+            self.fullscreen_windows.remove(&window);
+
+            let (was_floating, restored_x, restored_y, restored_width, restored_height, restored_border) = self
+                .clients
+                .get(&window)
+                .map(|client| {
+                    (
+                        client.old_state,
+                        client.old_x_position,
+                        client.old_y_position,
+                        client.old_width,
+                        client.old_height,
+                        client.old_border_width,
+                    )
+                })
+                .unwrap_or((false, 0, 0, 100, 100, 0));
+
+            if !was_floating {
+                self.floating_windows.remove(&window);
+            }
+
+            if let Some(client) = self.clients.get_mut(&window) {
+                client.is_fullscreen = false;
+                client.is_floating = client.old_state;
+                client.border_width = client.old_border_width;
+                client.x_position = client.old_x_position;
+                client.y_position = client.old_y_position;
+                client.width = client.old_width;
+                client.height = client.old_height;
+            }
+            self.write_net_wm_state(window)?;
+
+            self.connection.configure_window(
                 window,
-                self.atoms.net_wm_state,
-                AtomEnum::ATOM,
-                32,
-                0,
-                &[],
+                &ConfigureWindowAux::new()
+                    .x(restored_x as i32)
+                    .y(restored_y as i32)
+                    .width(restored_width as u32)
+                    .height(restored_height as u32)
+                    .border_width(restored_border as u32),
             )?;
+            self.set_frame_extents(window, restored_border as u32)?;
+
+            self.apply_layout()?;
+        }
+
+        Ok(())
+    }
+
+    /// Rewrites `_NET_WM_STATE` from the client's tracked state flags.
+    fn write_net_wm_state(&self, window: Window) -> WmResult<()> {
+        let Some(client) = self.clients.get(&window) else {
+            return Ok(());
+        };
+
+        let states = [
+            (client.is_fullscreen, self.atoms.net_wm_state_fullscreen),
+            (client.is_modal, self.atoms.net_wm_state_modal),
+            (client.is_sticky, self.atoms.net_wm_state_sticky),
+            (
+                client.is_maximized_vert,
+                self.atoms.net_wm_state_maximized_vert,
+            ),
+            (
+                client.is_maximized_horz,
+                self.atoms.net_wm_state_maximized_horz,
+            ),
+            (client.is_shaded, self.atoms.net_wm_state_shaded),
+            (client.skip_taskbar, self.atoms.net_wm_state_skip_taskbar),
+            (client.skip_pager, self.atoms.net_wm_state_skip_pager),
+            (client.is_hidden, self.atoms.net_wm_state_hidden),
+            (client.is_above, self.atoms.net_wm_state_above),
+            (client.is_below, self.atoms.net_wm_state_below),
+            (client.is_urgent, self.atoms.net_wm_state_demands_attention),
+        ];
+        let bytes: Vec<u8> = states
+            .iter()
+            .filter(|(set, _)| *set)
+            .flat_map(|(_, atom)| atom.to_ne_bytes())
+            .collect();
+
+        self.connection.change_property(
+            PropMode::REPLACE,
+            window,
+            self.atoms.net_wm_state,
+            AtomEnum::ATOM,
+            32,
+            bytes.len() as u32 / 4,
+            &bytes,
+        )?;
+        Ok(())
+    }
+
+    /// Applies one `_NET_WM_STATE` change, where `action` is 0 (remove),
+    /// 1 (add) or 2 (toggle). `_NET_WM_STATE_HIDDEN` is owned by the window
+    /// manager and ignored here. Callers are responsible for re-running the
+    /// layout afterwards.
+    fn set_net_wm_state(&mut self, window: Window, state: Atom, action: u32) -> WmResult<()> {
+        let Some(client) = self.clients.get(&window) else {
+            return Ok(());
+        };
+
+        let current = if state == self.atoms.net_wm_state_fullscreen {
+            client.is_fullscreen
+        } else if state == self.atoms.net_wm_state_modal {
+            client.is_modal
+        } else if state == self.atoms.net_wm_state_sticky {
+            client.is_sticky
+        } else if state == self.atoms.net_wm_state_maximized_vert {
+            client.is_maximized_vert
+        } else if state == self.atoms.net_wm_state_maximized_horz {
+            client.is_maximized_horz
+        } else if state == self.atoms.net_wm_state_shaded {
+            client.is_shaded
+        } else if state == self.atoms.net_wm_state_skip_taskbar {
+            client.skip_taskbar
+        } else if state == self.atoms.net_wm_state_skip_pager {
+            client.skip_pager
+        } else if state == self.atoms.net_wm_state_above {
+            client.is_above
+        } else if state == self.atoms.net_wm_state_below {
+            client.is_below
+        } else if state == self.atoms.net_wm_state_demands_attention {
+            client.is_urgent
+        } else {
+            return Ok(());
+        };
+
+        let enable = match action {
+            0 => false,
+            1 => true,
+            2 => !current,
+            _ => return Ok(()),
+        };
+        if enable == current {
+            return Ok(());
+        }
 
-            self.fullscreen_windows.remove(&window);
+        if state == self.atoms.net_wm_state_fullscreen {
+            return self.set_window_fullscreen(window, enable);
+        }
+        if state == self.atoms.net_wm_state_demands_attention {
+            return self.set_urgent(window, enable);
+        }
+        if state == self.atoms.net_wm_state_sticky {
+            self.set_sticky(window, enable)?;
+        } else if state == self.atoms.net_wm_state_maximized_vert {
+            let horz = client.is_maximized_horz;
+            self.set_maximized(window, enable, horz)?;
+        } else if state == self.atoms.net_wm_state_maximized_horz {
+            let vert = client.is_maximized_vert;
+            self.set_maximized(window, vert, enable)?;
+        } else if let Some(client) = self.clients.get_mut(&window) {
+            if state == self.atoms.net_wm_state_modal {
+                client.is_modal = enable;
+                if enable && !client.is_floating {
+                    client.is_floating = true;
+                    self.floating_windows.insert(window);
+                }
+            } else if state == self.atoms.net_wm_state_shaded {
+                client.is_shaded = enable;
+            } else if state == self.atoms.net_wm_state_skip_taskbar {
+                client.skip_taskbar = enable;
+            } else if state == self.atoms.net_wm_state_skip_pager {
+                client.skip_pager = enable;
+            } else if state == self.atoms.net_wm_state_above {
+                client.is_above = enable;
+                client.is_below &= !enable;
+            } else if state == self.atoms.net_wm_state_below {
+                client.is_below = enable;
+                client.is_above &= !enable;
+            }
+        }
+
+        self.write_net_wm_state(window)
+    }
 
-            let (was_floating, restored_x, restored_y, restored_width, restored_height, restored_border) = self
-                .clients
-                .get(&window)
-                .map(|client| {
-                    (
-                        client.old_state,
-                        client.old_x_position,
-                        client.old_y_position,
-                        client.old_width,
-                        client.old_height,
-                        client.old_border_width,
-                    )
-                })
-                .unwrap_or((false, 0, 0, 100, 100, 0));
+    /// Puts a client on every tag, or back onto just the tags its monitor
+    /// is currently viewing.
+    fn set_sticky(&mut self, window: Window, sticky: bool) -> WmResult<()> {
+        let Some(monitor_index) = self.clients.get(&window).map(|c| c.monitor_index) else {
+            return Ok(());
+        };
 
-            if !was_floating {
-                self.floating_windows.remove(&window);
-            }
+        let tags = if sticky {
+            tag_mask(self.config.tags.len()) - 1
+        } else {
+            self.monitors
+                .get(monitor_index)
+                .map(|m| m.tagset[m.selected_tags_index])
+                .unwrap_or(tag_mask(0))
+        };
 
-            if let Some(client) = self.clients.get_mut(&window) {
-                client.is_fullscreen = false;
-                client.is_floating = client.old_state;
-                client.border_width = client.old_border_width;
-                client.x_position = client.old_x_position;
-                client.y_position = client.old_y_position;
-                client.width = client.old_width;
-                client.height = client.old_height;
-            }
+        if let Some(client) = self.clients.get_mut(&window) {
+            client.is_sticky = sticky;
+            client.tags = tags;
+        }
+        self.save_client_tag(window, tags)
+    }
 
-            self.connection.configure_window(
-                window,
-                &ConfigureWindowAux::new()
-                    .x(restored_x as i32)
-                    .y(restored_y as i32)
-                    .width(restored_width as u32)
-                    .height(restored_height as u32)
-                    .border_width(restored_border as u32),
-            )?;
+    /// Stretches a client across its monitor's window area along the given
+    /// axes. The geometry from before the first axis was maximized is
+    /// restored once neither axis is.
+    fn set_maximized(&mut self, window: Window, vert: bool, horz: bool) -> WmResult<()> {
+        let Some(monitor_index) = self.clients.get(&window).map(|c| c.monitor_index) else {
+            return Ok(());
+        };
+        let bar_height = self.bar_space(monitor_index);
+        let Some(client) = self.clients.get_mut(&window) else {
+            return Ok(());
+        };
+        let Some(monitor) = self.monitors.get(monitor_index) else {
+            return Ok(());
+        };
+        let bar_offset = if monitor.top_bar { bar_height } else { 0 };
 
-            self.apply_layout()?;
+        if client.maximize_restore.is_none() && (vert || horz) {
+            client.maximize_restore = Some((
+                client.x_position,
+                client.y_position,
+                client.width,
+                client.height,
+            ));
+            client.maximize_was_floating = client.is_floating;
         }
+        client.is_maximized_vert = vert;
+        client.is_maximized_horz = horz;
 
-        Ok(())
+        let Some((mut x, mut y, mut width, mut height)) = client.maximize_restore else {
+            return Ok(());
+        };
+
+        if vert || horz {
+            let border = 2 * client.border_width as i32;
+            if horz {
+                x = monitor.window_area_x as i16;
+                width = (monitor.window_area_width - border).max(1) as u16;
+            }
+            if vert {
+                y = (monitor.window_area_y + bar_offset) as i16;
+                height = (monitor.window_area_height - bar_height - border).max(1) as u16;
+            }
+            client.is_floating = true;
+            self.floating_windows.insert(window);
+        } else {
+            client.maximize_restore = None;
+            client.is_floating = client.maximize_was_floating;
+            if !client.is_floating {
+                self.floating_windows.remove(&window);
+            }
+        }
+
+        client.x_position = x;
+        client.y_position = y;
+        client.width = width;
+        client.height = height;
+
+        self.connection.configure_window(
+            window,
+            &ConfigureWindowAux::new()
+                .x(x as i32)
+                .y(y as i32)
+                .width(width as u32)
+                .height(height as u32),
+        )?;
+        self.apply_layout()?;
+        self.restack()
     }
 
     fn get_transient_parent(&self, window: Window) -> Option<Window> {
@@ -2070,17 +3899,116 @@ impl WindowManager {
         (String::new(), String::new())
     }
 
-    fn apply_rules(&mut self, window: Window) -> WmResult<()> {
+    /// Caches the client's `WM_CLASS` so rules, hooks and `get_tree` don't
+    /// each need a round trip to the server.
+    fn update_window_class(&mut self, window: Window) {
         let (instance, class) = self.get_window_class_instance(window);
-        let title = self
+        if let Some(client) = self.clients.get_mut(&window) {
+            client.instance = instance;
+            client.class = class;
+        }
+    }
+
+    /// Caches whether the client answers `_NET_WM_PING`, so the periodic
+    /// ping doesn't have to read `WM_PROTOCOLS` every time.
+    fn update_window_protocols(&mut self, window: Window) -> WmResult<()> {
+        let supports_ping = self.supports_protocol(window, self.atoms.net_wm_ping)?;
+        if let Some(client) = self.clients.get_mut(&window) {
+            client.supports_ping = supports_ping;
+        }
+        Ok(())
+    }
+
+    /// The activation policy for a window: the last matching rule that sets
+    /// one, falling back to the global policy.
+    fn activation_policy(&self, window: Window) -> ActivationPolicy {
+        let Some(client) = self.clients.get(&window) else {
+            return self.config.activation_policy;
+        };
+
+        self.config
+            .window_rules
+            .iter()
+            .rev()
+            .filter(|rule| rule.matches(&client.class, &client.instance, &client.name))
+            .find_map(|rule| rule.activation)
+            .unwrap_or(self.config.activation_policy)
+    }
+
+    /// Handles a `_NET_ACTIVE_WINDOW` request.
+    fn activate_window(&mut self, window: Window) -> WmResult<()> {
+        let selected_window = self
+            .monitors
+            .get(self.selected_monitor)
+            .and_then(|m| m.selected_client);
+        if Some(window) == selected_window {
+            return Ok(());
+        }
+
+        let policy = match self.activation_policy(window) {
+            ActivationPolicy::FocusIfVisible if !self.is_visible(window) => {
+                ActivationPolicy::Urgent
+            }
+            policy => policy,
+        };
+
+        if policy == ActivationPolicy::Urgent {
+            if self.clients.get(&window).is_some_and(|c| !c.is_urgent) {
+                self.set_urgent(window, true)?;
+            }
+            return Ok(());
+        }
+
+        if let Some(client) = self.clients.get_mut(&window)
+            && client.is_hidden
+        {
+            client.is_hidden = false;
+            self.write_net_wm_state(window)?;
+        }
+
+        if policy == ActivationPolicy::Bring {
+            let tags = self
+                .monitors
+                .get(self.selected_monitor)
+                .map(|m| m.tagset[m.selected_tags_index])
+                .unwrap_or(tag_mask(0));
+            self.move_window_to_monitor(window, self.selected_monitor)?;
+            if let Some(client) = self.clients.get_mut(&window) {
+                client.tags = tags;
+                client.is_sticky = false;
+            }
+            self.save_client_tag(window, tags)?;
+            self.write_net_wm_state(window)?;
+        } else if !self.is_visible(window) {
+            let Some((monitor_index, tags)) =
+                self.clients.get(&window).map(|c| (c.monitor_index, c.tags))
+            else {
+                return Ok(());
+            };
+            if let Some(old_selected) = selected_window {
+                self.unfocus(old_selected, true)?;
+            }
+            self.select_monitor(monitor_index);
+            self.view_tag(unmask_tag(tags))?;
+        }
+
+        self.focus(Some(window))?;
+        self.apply_layout()?;
+        self.restack()?;
+        self.update_bar()?;
+        Ok(())
+    }
+
+    fn apply_rules(&mut self, window: Window) -> WmResult<()> {
+        let (instance, class, title) = self
             .clients
             .get(&window)
-            .map(|c| c.name.clone())
+            .map(|c| (c.instance.clone(), c.class.clone(), c.name.clone()))
             .unwrap_or_default();
 
         let mut rule_tags: Option<u32> = None;
         let mut rule_floating: Option<bool> = None;
-        let mut rule_monitor: Option<usize> = None;
+        let mut rule_monitor: &[MonitorSelector] = &[];
         let mut rule_focus = false;
 
         for rule in &self.config.window_rules {
@@ -2091,8 +4019,8 @@ impl WindowManager {
                 if rule.is_floating.is_some() {
                     rule_floating = rule.is_floating;
                 }
-                if rule.monitor.is_some() {
-                    rule_monitor = rule.monitor;
+                if !rule.monitor.is_empty() {
+                    rule_monitor = &rule.monitor;
                 }
                 rule_focus = rule.focus.unwrap_or(false);
             }
@@ -2108,9 +4036,7 @@ impl WindowManager {
                 }
             }
 
-            if let Some(monitor_index) = rule_monitor
-                && monitor_index < self.monitors.len()
-            {
+            if let Some(monitor_index) = MonitorSelector::resolve(rule_monitor, &self.monitors) {
                 client.monitor_index = monitor_index;
             }
 
@@ -2154,6 +4080,8 @@ impl WindowManager {
                     .unwrap_or(tag_mask(0));
                 (self.selected_monitor, tags)
             }
+        } else if let Some(placement) = self.launch_placement(window) {
+            placement
         } else {
             let tags = self
                 .monitors
@@ -2177,11 +4105,30 @@ impl WindowManager {
 
         self.clients.insert(window, client);
         self.update_window_title(window)?;
+        self.update_window_class(window);
+        self.update_window_protocols(window)?;
+        self.update_window_icon(window);
 
         if !is_transient {
             self.apply_rules(window)?;
         }
 
+        // With shared tags a window opens on whichever monitor shows its tags.
+        if self.config.shared_tags.is_enabled()
+            && let Some(client) = self.clients.get(&window)
+            && !self
+                .monitors
+                .get(client.monitor_index)
+                .is_some_and(|monitor| client.is_visible_on(monitor.get_selected_tag()))
+            && let Some(target) = self
+                .monitors
+                .iter()
+                .position(|monitor| client.is_visible_on(monitor.get_selected_tag()))
+            && let Some(client) = self.clients.get_mut(&window)
+        {
+            client.monitor_index = target;
+        }
+
         let client_monitor = self
             .clients
             .get(&window)
@@ -2233,8 +4180,12 @@ impl WindowManager {
             window,
             &ChangeWindowAttributesAux::new().border_pixel(self.config.border_unfocused),
         )?;
+        self.set_frame_extents(window, border_width)?;
         self.send_configure_notify(window)?;
         self.update_window_type(window)?;
+        // Drops any state we don't honour, such as a stale HIDDEN left
+        // behind by a previous window manager.
+        self.write_net_wm_state(window)?;
         self.update_size_hints(window)?;
         self.update_window_hints(window)?;
 
@@ -2285,6 +4236,12 @@ impl WindowManager {
         self.attach_stack(window, client_monitor);
         self.windows.push(window);
 
+        // Initial states (fullscreen, maximized) may have resized the client.
+        let (w, h) = self
+            .clients
+            .get(&window)
+            .map(|c| (c.width as i32, c.height as i32))
+            .unwrap_or((w, h));
         let off_screen_x = x + 2 * self.screen.width_in_pixels as i32;
         self.connection.configure_window(
             window,
@@ -2301,6 +4258,12 @@ impl WindowManager {
         let final_tags = self.clients.get(&window).map(|c| c.tags).unwrap_or(tags);
         let _ = self.save_client_tag(window, final_tags);
 
+        self.emit(IpcEvent::Manage {
+            window,
+            monitor: client_monitor,
+            tags: final_tags,
+        });
+
         if client_monitor == self.selected_monitor
             && let Some(old_sel) = self
                 .monitors
@@ -2323,7 +4286,7 @@ impl WindowManager {
         self.focus(None)?;
         self.update_bar()?;
 
-        if self.layout.name() == "tabbed" {
+        if self.has_tabbed_monitor() {
             self.update_tab_bars()?;
         }
 
@@ -2422,7 +4385,7 @@ impl WindowManager {
 
         self.connection.change_window_attributes(
             window,
-            &ChangeWindowAttributesAux::new().border_pixel(self.config.border_unfocused),
+            &ChangeWindowAttributesAux::new().border_pixel(self.border_color(window, false)),
         )?;
 
         if reset_input_focus {
@@ -2476,7 +4439,7 @@ impl WindowManager {
                 .unwrap_or(self.selected_monitor);
 
             if monitor_idx != self.selected_monitor {
-                self.selected_monitor = monitor_idx;
+                self.select_monitor(monitor_idx);
             }
 
             if self.clients.get(&win).is_some_and(|c| c.is_urgent) {
@@ -2490,7 +4453,7 @@ impl WindowManager {
 
             self.connection.change_window_attributes(
                 win,
-                &ChangeWindowAttributesAux::new().border_pixel(self.config.border_focused),
+                &ChangeWindowAttributesAux::new().border_pixel(self.border_color(win, true)),
             )?;
 
             let never_focus = self
@@ -2516,80 +4479,503 @@ impl WindowManager {
 
             let _ = self.send_event(win, self.atoms.wm_take_focus);
 
-            if let Some(monitor) = self.monitors.get_mut(self.selected_monitor) {
-                monitor.selected_client = Some(win);
-            }
+            if let Some(monitor) = self.monitors.get_mut(self.selected_monitor) {
+                monitor.selected_client = Some(win);
+            }
+
+            self.previous_focused = Some(win);
+        } else {
+            self.connection.set_input_focus(
+                InputFocus::POINTER_ROOT,
+                self.root,
+                x11rb::CURRENT_TIME,
+            )?;
+
+            self.connection
+                .delete_property(self.root, self.atoms.net_active_window)?;
+
+            if let Some(monitor) = self.monitors.get_mut(self.selected_monitor) {
+                monitor.selected_client = None;
+            }
+        }
+
+        // `manage_window` selects the new client before calling us, so
+        // compare against what subscribers last heard rather than
+        // `old_selected`.
+        if self.reported_focus != focus_client {
+            self.reported_focus = focus_client;
+            self.emit(IpcEvent::Focus {
+                monitor: self.selected_monitor,
+                window: focus_client,
+            });
+        }
+
+        // The work area follows the selected monitor.
+        self.update_workarea()?;
+        self.connection.flush()?;
+
+        Ok(())
+    }
+
+    fn restack(&mut self) -> WmResult<()> {
+        let windows_to_restack = self.stacking_order(self.selected_monitor);
+
+        for (i, &win) in windows_to_restack.iter().enumerate() {
+            if i == 0 {
+                self.connection.configure_window(
+                    win,
+                    &ConfigureWindowAux::new().stack_mode(StackMode::ABOVE),
+                )?;
+            } else {
+                self.connection.configure_window(
+                    win,
+                    &ConfigureWindowAux::new()
+                        .sibling(windows_to_restack[i - 1])
+                        .stack_mode(StackMode::BELOW),
+                )?;
+            }
+        }
+
+        self.restack_unmanaged()?;
+        self.update_client_list_stacking()?;
+
+        Ok(())
+    }
+
+    /// The order `restack` puts a monitor's clients in, top-most first.
+    fn stacking_order(&self, monitor_index: usize) -> Vec<Window> {
+        let monitor = match self.monitors.get(monitor_index) {
+            Some(m) => m,
+            None => return Vec::new(),
+        };
+
+        let mut windows_to_restack: Vec<Window> = Vec::new();
+
+        if let Some(selected) = monitor.selected_client
+            && self.floating_windows.contains(&selected)
+        {
+            windows_to_restack.push(selected);
+        }
+
+        let mut current = monitor.stack_head;
+        while let Some(win) = current {
+            if self.windows.contains(&win)
+                && self.floating_windows.contains(&win)
+                && Some(win) != monitor.selected_client
+            {
+                windows_to_restack.push(win);
+            }
+            current = self.clients.get(&win).and_then(|c| c.stack_next);
+        }
+
+        current = monitor.stack_head;
+        while let Some(win) = current {
+            if self.windows.contains(&win) && !self.floating_windows.contains(&win) {
+                windows_to_restack.push(win);
+            }
+            current = self.clients.get(&win).and_then(|c| c.stack_next);
+        }
+
+        // Stable, so the focus/stack order is kept within each layer.
+        windows_to_restack.sort_by_key(|win| match self.clients.get(win) {
+            Some(client) if client.is_above => 0,
+            Some(client) if client.is_below => 2,
+            _ => 1,
+        });
+
+        windows_to_restack
+    }
+
+    fn restack_unmanaged(&self) -> WmResult<()> {
+        // Docks go above the clients but stay under a visible fullscreen
+        // one, so panels don't cover fullscreen video or games.
+        let lowest_fullscreen = self
+            .stacking_order(self.selected_monitor)
+            .into_iter()
+            .filter(|&window| {
+                self.is_visible(window)
+                    && self
+                        .clients
+                        .get(&window)
+                        .is_some_and(|client| client.is_fullscreen)
+            })
+            .last();
+
+        for (&window, kind) in &self.unmanaged {
+            let aux = match (kind, lowest_fullscreen) {
+                (UnmanagedWindow::Desktop, _) => {
+                    ConfigureWindowAux::new().stack_mode(StackMode::BELOW)
+                }
+                (UnmanagedWindow::Dock(_), Some(fullscreen)) => ConfigureWindowAux::new()
+                    .sibling(fullscreen)
+                    .stack_mode(StackMode::BELOW),
+                (UnmanagedWindow::Dock(_), None) | (UnmanagedWindow::Notification, _) => {
+                    ConfigureWindowAux::new().stack_mode(StackMode::ABOVE)
+                }
+            };
+            self.connection.configure_window(window, &aux)?;
+        }
+        Ok(())
+    }
+
+    fn unmanaged_kind(&self, window: Window) -> Option<UnmanagedWindow> {
+        let types = self
+            .get_window_atom_list_property(window, self.atoms.net_wm_window_type)
+            .ok()?;
+        types.iter().find_map(|&window_type| {
+            if window_type == self.atoms.net_wm_window_type_dock {
+                Some(UnmanagedWindow::Dock(Strut::default()))
+            } else if window_type == self.atoms.net_wm_window_type_desktop {
+                Some(UnmanagedWindow::Desktop)
+            } else if window_type == self.atoms.net_wm_window_type_notification
+                || window_type == self.atoms.net_wm_window_type_tooltip
+            {
+                Some(UnmanagedWindow::Notification)
+            } else {
+                None
+            }
+        })
+    }
+
+    /// Unmanaged windows are mapped as they are: they get no border, no
+    /// tags and no focus. Only dock struts affect the layout.
+    fn map_unmanaged(&mut self, window: Window, kind: UnmanagedWindow) -> WmResult<()> {
+        self.connection.change_window_attributes(
+            window,
+            &ChangeWindowAttributesAux::new().event_mask(EventMask::PROPERTY_CHANGE),
+        )?;
+
+        let kind = match kind {
+            UnmanagedWindow::Dock(_) => {
+                UnmanagedWindow::Dock(self.read_strut(window).unwrap_or_default())
+            }
+            UnmanagedWindow::Desktop => {
+                self.connection
+                    .configure_window(window, &ConfigureWindowAux::new().border_width(0))?;
+                UnmanagedWindow::Desktop
+            }
+            UnmanagedWindow::Notification => UnmanagedWindow::Notification,
+        };
+
+        self.connection.map_window(window)?;
+        self.unmanaged.insert(window, kind);
+        self.restack_unmanaged()?;
+
+        if let UnmanagedWindow::Dock(_) = kind {
+            self.update_struts();
+            self.apply_layout()?;
+        }
+        Ok(())
+    }
+
+    fn remove_unmanaged(&mut self, window: Window) -> WmResult<()> {
+        if let Some(UnmanagedWindow::Dock(_)) = self.unmanaged.remove(&window) {
+            self.update_struts();
+            self.apply_layout()?;
+        }
+        Ok(())
+    }
 
-            self.previous_focused = Some(win);
-        } else {
-            self.connection.set_input_focus(
-                InputFocus::POINTER_ROOT,
-                self.root,
-                x11rb::CURRENT_TIME,
-            )?;
+    fn read_strut(&self, window: Window) -> Option<Strut> {
+        [self.atoms.net_wm_strut_partial, self.atoms.net_wm_strut]
+            .into_iter()
+            .find_map(|property| {
+                let reply = self
+                    .connection
+                    .get_property(false, window, property, AtomEnum::CARDINAL, 0, 12)
+                    .ok()?
+                    .reply()
+                    .ok()?;
+                let values: Vec<u32> = reply.value32()?.collect();
+                Strut::from_cardinals(&values)
+            })
+    }
 
-            self.connection
-                .delete_property(self.root, self.atoms.net_active_window)?;
+    fn update_struts(&mut self) {
+        let root_width = self.screen.width_in_pixels as i32;
+        let root_height = self.screen.height_in_pixels as i32;
+        for monitor in &mut self.monitors {
+            let struts = self.unmanaged.values().filter_map(|kind| match kind {
+                UnmanagedWindow::Dock(strut) => Some(strut),
+                _ => None,
+            });
+            monitor.apply_struts(struts, root_width, root_height);
+        }
+    }
 
-            if let Some(monitor) = self.monitors.get_mut(self.selected_monitor) {
-                monitor.selected_client = None;
+    /// Re-detects monitors after outputs were connected, removed or resized.
+    /// A monitor keeps its tags, layouts and clients when one with the same
+    /// geometry (or failing that, any leftover one) is still there; clients
+    /// of monitors that vanished move to the selected one, keeping their tags.
+    fn update_monitors(&mut self) -> WmResult<()> {
+        let detected = detect_monitors(&self.connection, &self.screen, self.root)?;
+        let unchanged = detected.len() == self.monitors.len()
+            && detected
+                .iter()
+                .zip(&self.monitors)
+                .all(|(new, old)| new.has_same_geometry(old) && new.output == old.output);
+
+        if !unchanged {
+            self.rebuild_monitors(detected)?;
+            if self.config.shared_tags.is_enabled() {
+                self.spread_shared_tags();
+                self.assign_shared_clients();
+                self.focus(None)?;
             }
         }
 
-        self.connection.flush()?;
-
+        self.update_struts();
+        self.update_desktop_info()?;
+        self.apply_layout()?;
+        self.update_workarea()?;
+        self.update_systray()?;
+        self.update_bar()?;
+        if self.has_tabbed_monitor() {
+            self.update_tab_bars()?;
+        }
         Ok(())
     }
 
-    fn restack(&mut self) -> WmResult<()> {
-        let monitor = match self.monitors.get(self.selected_monitor) {
-            Some(m) => m,
-            None => return Ok(()),
-        };
+    fn rebuild_monitors(&mut self, detected: Vec<Monitor>) -> WmResult<()> {
+        let old_count = self.monitors.len();
 
-        let mut windows_to_restack: Vec<Window> = Vec::new();
+        // The old monitor each detected one takes over, if any: the same
+        // output first, then the same geometry, then whatever is left.
+        let mut sources: Vec<Option<usize>> = vec![None; detected.len()];
+        let mut taken = vec![false; old_count];
+        for (new_index, new) in detected.iter().enumerate() {
+            if let Some(old_index) = (0..old_count).find(|&i| {
+                !taken[i] && new.output.is_some() && self.monitors[i].output == new.output
+            }) {
+                sources[new_index] = Some(old_index);
+                taken[old_index] = true;
+            }
+        }
+        for (new_index, new) in detected.iter().enumerate() {
+            if sources[new_index].is_some() {
+                continue;
+            }
+            if let Some(old_index) =
+                (0..old_count).find(|&i| !taken[i] && self.monitors[i].has_same_geometry(new))
+            {
+                sources[new_index] = Some(old_index);
+                taken[old_index] = true;
+            }
+        }
+        for source in sources.iter_mut().filter(|source| source.is_none()) {
+            if let Some(old_index) = taken.iter().position(|&t| !t) {
+                *source = Some(old_index);
+                taken[old_index] = true;
+            }
+        }
 
-        if let Some(selected) = monitor.selected_client
-            && self.floating_windows.contains(&selected)
+        let mut mapping: Vec<Option<usize>> = vec![None; old_count];
+        for (new_index, source) in sources.iter().enumerate() {
+            if let Some(old_index) = source {
+                mapping[*old_index] = Some(new_index);
+            }
+        }
+        let target = mapping
+            .get(self.selected_monitor)
+            .copied()
+            .flatten()
+            .unwrap_or(0);
+
+        // The tray lives inside its bar's window, so hand the icons back
+        // before that bar is destroyed or ends up on another monitor.
+        let tray_monitor = MonitorSelector::resolve(&self.config.systray_monitor, &detected);
+        let mut reclaim_tray = self.systray.is_none()
+            && MonitorSelector::resolve(&self.config.systray_monitor, &self.monitors).is_none();
+        if let Some(tray) = &mut self.systray
+            && (tray_monitor != Some(tray.monitor_index())
+                || mapping.get(tray.monitor_index()).copied().flatten() != tray_monitor)
         {
-            windows_to_restack.push(selected);
+            tray.release(&self.connection, self.root)?;
+            self.systray = None;
+            reclaim_tray = true;
         }
 
-        let mut current = monitor.stack_head;
-        while let Some(win) = current {
-            if self.windows.contains(&win)
-                && self.floating_windows.contains(&win)
-                && Some(win) != monitor.selected_client
-            {
-                windows_to_restack.push(win);
-            }
-            current = self.clients.get(&win).and_then(|c| c.stack_next);
+        let old_origins: Vec<(i32, i32)> = self
+            .monitors
+            .iter()
+            .map(|monitor| (monitor.screen_x, monitor.screen_y))
+            .collect();
+        let mut old_monitors: Vec<Option<Monitor>> = std::mem::take(&mut self.monitors)
+            .into_iter()
+            .map(Some)
+            .collect();
+        let mut old_bars: Vec<Option<Bar>> = std::mem::take(&mut self.bars)
+            .into_iter()
+            .map(Some)
+            .collect();
+        let mut old_tab_bars: Vec<Option<crate::tab_bar::TabBar>> =
+            std::mem::take(&mut self.tab_bars)
+                .into_iter()
+                .map(Some)
+                .collect();
+
+        for (new_index, new) in detected.into_iter().enumerate() {
+            let reused = sources[new_index].and_then(|old_index| {
+                let monitor = old_monitors[old_index].take()?;
+                Some((
+                    monitor,
+                    old_bars.get_mut(old_index).and_then(Option::take),
+                    old_tab_bars.get_mut(old_index).and_then(Option::take),
+                ))
+            });
+
+            let (monitor, bar, tab_bar) = match reused {
+                Some((mut monitor, bar, tab_bar)) => {
+                    // A different output taking over the slot is a new
+                    // monitor as far as its profile is concerned.
+                    let output_changed = monitor.output != new.output;
+                    monitor.screen_x = new.screen_x;
+                    monitor.screen_y = new.screen_y;
+                    monitor.screen_width = new.screen_width;
+                    monitor.screen_height = new.screen_height;
+                    monitor.output = new.output;
+                    monitor.model = new.model;
+                    if output_changed {
+                        monitor.init_pertag(&self.config, new_index);
+                    } else {
+                        monitor.apply_profile(&self.config, new_index);
+                    }
+                    (monitor, bar, tab_bar)
+                }
+                None => {
+                    let mut monitor = new;
+                    monitor.init_pertag(&self.config, new_index);
+                    (monitor, None, None)
+                }
+            };
+
+            let bar = match bar {
+                Some(mut bar) => {
+                    bar.reposition(
+                        &self.connection,
+                        self.display,
+                        monitor.screen_x as i16,
+                        monitor.bar_y(bar.height()) as i16,
+                        monitor.screen_width as u16,
+                    )?;
+                    bar
+                }
+                None => Bar::new(
+                    &self.connection,
+                    &self.screen,
+                    self.screen_number,
+                    &self.config,
+                    self.display,
+                    &self.font,
+                    monitor.screen_x as i16,
+                    monitor.bar_y(Bar::height_for(&self.font)) as i16,
+                    monitor.screen_width as u16,
+                )?,
+            };
+
+            let tab_bar = match tab_bar {
+                Some(tab_bar) => tab_bar,
+                None => {
+                    let bar_height = bar.height() as i32;
+                    let tab_bar = crate::tab_bar::TabBar::new(
+                        &self.connection,
+                        &self.screen,
+                        self.screen_number,
+                        self.display,
+                        &self.font,
+                        (monitor.screen_x + monitor.gap_outer_horizontal) as i16,
+                        (monitor.screen_y + bar_height + monitor.gap_outer_vertical) as i16,
+                        monitor
+                            .screen_width
+                            .saturating_sub(2 * monitor.gap_outer_horizontal)
+                            as u16,
+                        self.config.scheme_occupied,
+                        self.config.scheme_selected,
+                    )?;
+                    tab_bar.hide(&self.connection)?;
+                    tab_bar
+                }
+            };
+
+            self.monitors.push(monitor);
+            self.bars.push(bar);
+            self.tab_bars.push(tab_bar);
         }
 
-        current = monitor.stack_head;
-        while let Some(win) = current {
-            if self.windows.contains(&win) && !self.floating_windows.contains(&win) {
-                windows_to_restack.push(win);
+        for bar in old_bars.into_iter().flatten() {
+            bar.destroy(&self.connection)?;
+        }
+        for tab_bar in old_tab_bars.into_iter().flatten() {
+            tab_bar.destroy(&self.connection)?;
+        }
+
+        // Walk the vanished monitors' lists before any client is re-homed.
+        let mut orphans: Vec<Window> = Vec::new();
+        let mut orphan_stack: Vec<Window> = Vec::new();
+        for monitor in old_monitors.iter().flatten() {
+            let mut current = monitor.clients_head;
+            while let Some(window) = current {
+                orphans.push(window);
+                current = self.clients.get(&window).and_then(|client| client.next);
+            }
+            let mut current = monitor.stack_head;
+            while let Some(window) = current {
+                orphan_stack.push(window);
+                current = self
+                    .clients
+                    .get(&window)
+                    .and_then(|client| client.stack_next);
             }
-            current = self.clients.get(&win).and_then(|c| c.stack_next);
         }
 
-        for (i, &win) in windows_to_restack.iter().enumerate() {
-            if i == 0 {
-                self.connection.configure_window(
-                    win,
-                    &ConfigureWindowAux::new().stack_mode(StackMode::ABOVE),
-                )?;
-            } else {
-                self.connection.configure_window(
-                    win,
-                    &ConfigureWindowAux::new()
-                        .sibling(windows_to_restack[i - 1])
-                        .stack_mode(StackMode::BELOW),
-                )?;
+        for client in self.clients.values_mut() {
+            let old_index = client.monitor_index;
+            let new_index = mapping.get(old_index).copied().flatten().unwrap_or(target);
+            let Some(monitor) = self.monitors.get(new_index) else {
+                continue;
+            };
+            client.monitor_index = new_index;
+
+            // Keep floating windows at the same spot relative to their
+            // monitor, and on it.
+            if let Some(&(old_x, old_y)) = old_origins.get(old_index) {
+                let x = client.x_position as i32 - old_x + monitor.screen_x;
+                let y = client.y_position as i32 - old_y + monitor.screen_y;
+                let max_x =
+                    monitor.screen_x + monitor.screen_width - client.width_with_border() as i32;
+                let max_y =
+                    monitor.screen_y + monitor.screen_height - client.height_with_border() as i32;
+                client.x_position = x.min(max_x).max(monitor.screen_x) as i16;
+                client.y_position = y.min(max_y).max(monitor.screen_y) as i16;
             }
         }
 
+        for &window in orphans.iter().rev() {
+            self.attach(window, target);
+        }
+        for &window in orphan_stack.iter().rev() {
+            self.attach_stack(window, target);
+        }
+
+        self.select_monitor(target);
+        self.published_workarea = None;
+
+        if reclaim_tray
+            && let Some(index) = tray_monitor
+            && let Some(bar) = self.bars.get(index)
+        {
+            self.systray = Systray::new(
+                &self.connection,
+                &self.screen,
+                self.screen_number,
+                bar.window(),
+                index,
+                bar.height(),
+                self.config.scheme_normal.background,
+            )?;
+        }
+
+        self.focus(None)?;
         Ok(())
     }
 
@@ -2620,7 +5006,7 @@ impl WindowManager {
         let mut current_window = monitor.clients_head;
         while let Some(window) = current_window {
             if let Some(client) = self.clients.get(&window) {
-                if client.tags & selected_tags != 0 && !client.is_floating {
+                if client.is_visible_on(selected_tags) && !client.is_floating {
                     stack_windows.push(window);
                 }
                 current_window = client.next;
@@ -2697,8 +5083,9 @@ impl WindowManager {
                     break;
                 }
                 if let Some(client) = self.clients.get(&window) {
-                    let visible_tags = client.tags & monitor.tagset[monitor.selected_tags_index];
-                    if visible_tags != 0 && !client.is_floating {
+                    if client.is_visible_on(monitor.tagset[monitor.selected_tags_index])
+                        && !client.is_floating
+                    {
                         previous = Some(window);
                     }
                     current = client.next;
@@ -2711,9 +5098,9 @@ impl WindowManager {
                 let mut current = monitor.clients_head;
                 while let Some(window) = current {
                     if let Some(client) = self.clients.get(&window) {
-                        let visible_tags =
-                            client.tags & monitor.tagset[monitor.selected_tags_index];
-                        if visible_tags != 0 && !client.is_floating {
+                        if client.is_visible_on(monitor.tagset[monitor.selected_tags_index])
+                            && !client.is_floating
+                        {
                             last = Some(window);
                         }
                         current = client.next;
@@ -2802,10 +5189,131 @@ impl WindowManager {
             return Ok(());
         }
 
-        let target_monitor = match self.get_adjacent_monitor(direction) {
-            Some(idx) if idx != self.selected_monitor => idx,
-            _ => return Ok(()),
+        match self.get_adjacent_monitor(direction) {
+            Some(idx) => self.focus_monitor_index(idx),
+            None => Ok(()),
+        }
+    }
+
+    /// Another monitor than the selected one showing any of `tags`.
+    fn monitor_showing(&self, tags: TagMask) -> Option<usize> {
+        (0..self.monitors.len()).find(|&index| {
+            index != self.selected_monitor && self.monitors[index].get_selected_tag() & tags != 0
+        })
+    }
+
+    /// Switches a monitor other than the selected one to `tagset`, loading
+    /// its per-tag settings the way `view_tag` does.
+    fn set_monitor_view(&mut self, monitor_index: usize, tagset: TagMask) {
+        let all_tags = tag_mask(self.config.tags.len()) - 1;
+        let Some(monitor) = self.monitors.get_mut(monitor_index) else {
+            return;
+        };
+        monitor.selected_tags_index ^= 1;
+        monitor.tagset[monitor.selected_tags_index] = tagset;
+        if let Some(ref mut pertag) = monitor.pertag {
+            pertag.previous_tag = pertag.current_tag;
+            pertag.current_tag = if tagset == all_tags {
+                0
+            } else {
+                tagset.trailing_zeros() as usize + 1
+            };
+            monitor.num_master = pertag.num_masters[pertag.current_tag];
+            monitor.master_factor = pertag.master_factors[pertag.current_tag];
+            monitor.show_bar = pertag.show_bars[pertag.current_tag];
+        }
+    }
+
+    /// Gives every monitor that shares a tag with an earlier one the first
+    /// tag nobody shows, so each shared tag is on at most one monitor.
+    fn spread_shared_tags(&mut self) {
+        let tag_count = self.config.tags.len();
+        let mut shown: TagMask = 0;
+        for index in 0..self.monitors.len() {
+            let view = self.monitors[index].get_selected_tag();
+            if view & shown == 0 {
+                shown |= view;
+                continue;
+            }
+            let Some(free) = (0..tag_count).find(|&tag| {
+                let mask = tag_mask(tag);
+                shown & mask == 0
+                    && self
+                        .monitors
+                        .iter()
+                        .all(|m| m.get_selected_tag() & mask == 0)
+            }) else {
+                // More monitors than tags; leave the rest doubled up.
+                continue;
+            };
+            self.set_monitor_view(index, tag_mask(free));
+            shown |= tag_mask(free);
+        }
+        // The selected monitor may have moved too; reload its layout.
+        self.select_monitor(self.selected_monitor);
+    }
+
+    /// With shared tags a client lives on whichever monitor shows one of
+    /// its tags, so move clients over after views or tags change.
+    fn assign_shared_clients(&mut self) {
+        let moves: Vec<(Window, usize)> = self
+            .windows
+            .iter()
+            .filter_map(|&window| {
+                let client = self.clients.get(&window)?;
+                let current = self.monitors.get(client.monitor_index)?;
+                if client.is_visible_on(current.get_selected_tag()) {
+                    return None;
+                }
+                let target = self
+                    .monitors
+                    .iter()
+                    .position(|monitor| client.is_visible_on(monitor.get_selected_tag()))?;
+                Some((window, target))
+            })
+            .collect();
+
+        for (window, target) in moves {
+            self.detach(window);
+            self.detach_stack(window);
+            if let Some(client) = self.clients.get_mut(&window) {
+                let from = &self.monitors[client.monitor_index];
+                let to = &self.monitors[target];
+                client.x_position = (client.x_position as i32 - from.screen_x + to.screen_x) as i16;
+                client.y_position = (client.y_position as i32 - from.screen_y + to.screen_y) as i16;
+                client.monitor_index = target;
+            }
+            self.attach(window, target);
+            self.attach_stack(window, target);
+        }
+    }
+
+    /// Puts the pointer in the middle of a monitor just focused from the
+    /// keyboard, when `oxwm.monitor.set_warp_pointer` asked for it.
+    fn warp_pointer_to_monitor(&mut self, monitor_index: usize) -> WmResult<()> {
+        if !self.config.warp_pointer_to_monitor {
+            return Ok(());
+        }
+        let Some(monitor) = self.monitors.get(monitor_index) else {
+            return Ok(());
         };
+        let (x, y) = monitor.center();
+        self.connection
+            .warp_pointer(x11rb::NONE, self.root, 0, 0, 0, 0, x as i16, y as i16)?;
+        self.connection.flush()?;
+        Ok(())
+    }
+
+    /// Index of the monitor on the named RandR output, or with that EDID
+    /// model name.
+    fn monitor_by_name(&self, name: &str) -> Option<usize> {
+        MonitorSelector::resolve(&[MonitorSelector::Name(name.to_string())], &self.monitors)
+    }
+
+    fn focus_monitor_index(&mut self, target_monitor: usize) -> WmResult<()> {
+        if target_monitor == self.selected_monitor || target_monitor >= self.monitors.len() {
+            return Ok(());
+        }
 
         let old_selected = self
             .monitors
@@ -2816,7 +5324,7 @@ impl WindowManager {
             self.unfocus(win, true)?;
         }
 
-        self.selected_monitor = target_monitor;
+        self.select_monitor(target_monitor);
         self.focus(None)?;
 
         Ok(())
@@ -2881,23 +5389,14 @@ impl WindowManager {
         let snap = 32;
         let is_normie = self.layout.name() == "normie";
 
+        if !self.grab_pointer_for_drag()? {
+            return Ok(());
+        }
+
         if !was_floating && !is_normie {
             self.toggle_floating()?;
         }
 
-        self.connection
-            .grab_pointer(
-                false,
-                self.root,
-                EventMask::POINTER_MOTION | EventMask::BUTTON_RELEASE | EventMask::BUTTON_PRESS,
-                GrabMode::ASYNC,
-                GrabMode::ASYNC,
-                x11rb::NONE,
-                x11rb::NONE,
-                x11rb::CURRENT_TIME,
-            )?
-            .reply()?;
-
         let pointer = self.connection.query_pointer(self.root)?.reply()?;
         let (start_x, start_y) = (pointer.root_x as i32, pointer.root_y as i32);
 
@@ -2957,6 +5456,17 @@ impl WindowManager {
                     }
                 }
                 Event::ButtonRelease(_) => break,
+                Event::ClientMessage(e) if self.is_moveresize_cancel(&e, window) => {
+                    if let Some(client) = self.clients.get_mut(&window) {
+                        client.x_position = orig_x;
+                        client.y_position = orig_y;
+                    }
+                    self.connection.configure_window(
+                        window,
+                        &ConfigureWindowAux::new().x(orig_x as i32).y(orig_y as i32),
+                    )?;
+                    break;
+                }
                 _ => {}
             }
         }
@@ -2974,7 +5484,7 @@ impl WindowManager {
             let new_monitor = self.get_monitor_for_rect(x as i32, y as i32, w as i32, h as i32);
             if new_monitor != monitor_idx {
                 self.move_window_to_monitor(window, new_monitor)?;
-                self.selected_monitor = new_monitor;
+                self.select_monitor(new_monitor);
                 self.focus(None)?;
             }
         }
@@ -3018,7 +5528,7 @@ impl WindowManager {
             let c = self.clients.get(&win)?;
             current = c.next;
 
-            if win == exclude || c.is_floating || (c.tags & tags) == 0 {
+            if win == exclude || c.is_floating || !c.is_visible_on(tags) {
                 continue;
             }
 
@@ -3065,7 +5575,49 @@ impl WindowManager {
         }
     }
 
+    /// Grabs the pointer for an interactive move or resize. The loops only
+    /// end on a button release, so this gives up when another client holds
+    /// the pointer or no button is down any more.
+    fn grab_pointer_for_drag(&self) -> WmResult<bool> {
+        let grab = self
+            .connection
+            .grab_pointer(
+                false,
+                self.root,
+                EventMask::POINTER_MOTION | EventMask::BUTTON_RELEASE | EventMask::BUTTON_PRESS,
+                GrabMode::ASYNC,
+                GrabMode::ASYNC,
+                x11rb::NONE,
+                x11rb::NONE,
+                x11rb::CURRENT_TIME,
+            )?
+            .reply()?;
+        if grab.status != GrabStatus::SUCCESS {
+            return Ok(false);
+        }
+
+        let buttons = KeyButMask::BUTTON1
+            | KeyButMask::BUTTON2
+            | KeyButMask::BUTTON3
+            | KeyButMask::BUTTON4
+            | KeyButMask::BUTTON5;
+        let pointer = self.connection.query_pointer(self.root)?.reply()?;
+        if pointer.mask & buttons == KeyButMask::from(0u16) {
+            self.connection
+                .ungrab_pointer(x11rb::CURRENT_TIME)?
+                .check()?;
+            return Ok(false);
+        }
+        Ok(true)
+    }
+
     fn resize_window_with_mouse(&mut self, window: Window) -> WmResult<()> {
+        self.resize_window_from(window, ResizeEdges::BOTTOM_RIGHT)
+    }
+
+    /// Resizes `window` by dragging the given edges until the button is
+    /// released or a client cancels with `_NET_WM_MOVERESIZE`.
+    fn resize_window_from(&mut self, window: Window, edges: ResizeEdges) -> WmResult<()> {
         let is_fullscreen = self
             .clients
             .get(&window)
@@ -3113,7 +5665,7 @@ impl WindowManager {
             let mut current = monitor.clients_head;
             while let Some(w) = current {
                 if let Some(c) = self.clients.get(&w) {
-                    let visible = (c.tags & monitor.tagset[monitor.selected_tags_index]) != 0;
+                    let visible = c.is_visible_on(monitor.tagset[monitor.selected_tags_index]);
                     if visible && !c.is_floating {
                         tiled_count += 1;
                     }
@@ -3127,33 +5679,32 @@ impl WindowManager {
             }
         }
 
-        if !was_floating && !is_normie {
-            self.toggle_floating()?;
+        if !self.grab_pointer_for_drag()? {
+            return Ok(());
         }
-
-        self.connection.warp_pointer(
-            x11rb::NONE,
-            window,
-            0,
-            0,
-            0,
-            0,
-            (orig_width + border_width - 1) as i16,
-            (orig_height + border_width - 1) as i16,
-        )?;
-
-        self.connection
-            .grab_pointer(
-                false,
-                self.root,
-                EventMask::POINTER_MOTION | EventMask::BUTTON_RELEASE | EventMask::BUTTON_PRESS,
-                GrabMode::ASYNC,
-                GrabMode::ASYNC,
-                x11rb::NONE,
+
+        if !was_floating && !is_normie {
+            self.toggle_floating()?;
+        }
+
+        // A client-side decorated window starts the resize with the pointer
+        // already on the edge it wants to drag.
+        if edges == ResizeEdges::BOTTOM_RIGHT {
+            self.connection.warp_pointer(
                 x11rb::NONE,
-                x11rb::CURRENT_TIME,
-            )?
-            .reply()?;
+                window,
+                0,
+                0,
+                0,
+                0,
+                (orig_width + border_width - 1) as i16,
+                (orig_height + border_width - 1) as i16,
+            )?;
+        }
+
+        // The edges that don't move, in root coordinates.
+        let right = orig_x as i32 + orig_width as i32;
+        let bottom = orig_y as i32 + orig_height as i32;
 
         let mut last_time = 0u32;
 
@@ -3167,12 +5718,20 @@ impl WindowManager {
                     }
                     last_time = e.time;
 
-                    let new_width = ((e.root_x as i32 - orig_x as i32 - 2 * border_width as i32
-                        + 1)
-                    .max(1)) as u32;
-                    let new_height = ((e.root_y as i32 - orig_y as i32 - 2 * border_width as i32
-                        + 1)
-                    .max(1)) as u32;
+                    let new_width = if edges.left {
+                        (right - e.root_x as i32).max(1)
+                    } else if edges.right {
+                        (e.root_x as i32 - orig_x as i32 - 2 * border_width as i32 + 1).max(1)
+                    } else {
+                        orig_width as i32
+                    };
+                    let new_height = if edges.top {
+                        (bottom - e.root_y as i32).max(1)
+                    } else if edges.bottom {
+                        (e.root_y as i32 - orig_y as i32 - 2 * border_width as i32 + 1).max(1)
+                    } else {
+                        orig_height as i32
+                    };
 
                     let should_resize = is_normie
                         || self
@@ -3186,11 +5745,26 @@ impl WindowManager {
                             window,
                             client.x_position as i32,
                             client.y_position as i32,
-                            new_width as i32,
-                            new_height as i32,
+                            new_width,
+                            new_height,
                         );
 
+                        // Dragging the left or top edge keeps the opposite
+                        // one in place.
+                        let new_x = if edges.left {
+                            right - hint_width
+                        } else {
+                            orig_x as i32
+                        };
+                        let new_y = if edges.top {
+                            bottom - hint_height
+                        } else {
+                            orig_y as i32
+                        };
+
                         if let Some(client_mut) = self.clients.get_mut(&window) {
+                            client_mut.x_position = new_x as i16;
+                            client_mut.y_position = new_y as i16;
                             client_mut.width = hint_width as u16;
                             client_mut.height = hint_height as u16;
                         }
@@ -3198,6 +5772,8 @@ impl WindowManager {
                         self.connection.configure_window(
                             window,
                             &ConfigureWindowAux::new()
+                                .x(new_x)
+                                .y(new_y)
                                 .width(hint_width as u32)
                                 .height(hint_height as u32),
                         )?;
@@ -3205,13 +5781,32 @@ impl WindowManager {
                     }
                 }
                 Event::ButtonRelease(_) => break,
+                Event::ClientMessage(e) if self.is_moveresize_cancel(&e, window) => {
+                    if let Some(client) = self.clients.get_mut(&window) {
+                        client.x_position = orig_x;
+                        client.y_position = orig_y;
+                        client.width = orig_width;
+                        client.height = orig_height;
+                    }
+                    self.connection.configure_window(
+                        window,
+                        &ConfigureWindowAux::new()
+                            .x(orig_x as i32)
+                            .y(orig_y as i32)
+                            .width(orig_width as u32)
+                            .height(orig_height as u32),
+                    )?;
+                    break;
+                }
                 _ => {}
             }
         }
 
         let final_client = self.clients.get(&window).map(|c| (c.width, c.border_width));
 
-        if let Some((w, bw)) = final_client {
+        if edges == ResizeEdges::BOTTOM_RIGHT
+            && let Some((w, bw)) = final_client
+        {
             self.connection.warp_pointer(
                 x11rb::NONE,
                 window,
@@ -3237,7 +5832,7 @@ impl WindowManager {
             let new_monitor = self.get_monitor_for_rect(x as i32, y as i32, w as i32, h as i32);
             if new_monitor != monitor_idx {
                 self.move_window_to_monitor(window, new_monitor)?;
-                self.selected_monitor = new_monitor;
+                self.select_monitor(new_monitor);
                 self.focus(None)?;
             }
         }
@@ -3279,6 +5874,35 @@ impl WindowManager {
                 }
                 return Ok(Control::Continue);
             }
+            Event::KeyPress(ref e) if e.event == self.confirm_overlay.window() => {
+                use crate::keyboard::keysyms;
+                if let Some(mapping) = &self.keyboard_mapping {
+                    let keysym = mapping.keycode_to_keysym(e.detail);
+                    if keysym == keysyms::XK_Y || keysym == keysyms::XK_RETURN {
+                        let target = self.pending_kill;
+                        self.close_confirm()?;
+                        if let Some(window) = target {
+                            self.force_kill(window)?;
+                        }
+                    } else if keysym == keysyms::XK_ESCAPE
+                        || keysym == keysyms::XK_N
+                        || keysym == keysyms::XK_Q
+                    {
+                        self.close_confirm()?;
+                    }
+                }
+                return Ok(Control::Continue);
+            }
+            Event::Expose(ref expose_event)
+                if expose_event.window == self.confirm_overlay.window() =>
+            {
+                if self.confirm_overlay.is_visible()
+                    && let Err(error) = self.confirm_overlay.draw(&self.connection, &self.font)
+                {
+                    eprintln!("Failed to draw confirm overlay: {:?}", error);
+                }
+                return Ok(Control::Continue);
+            }
             Event::KeyPress(ref e) if e.event == self.keybind_overlay.window() => {
                 if self.keybind_overlay.is_visible()
                     && !self.keybind_overlay.should_suppress_input()
@@ -3322,21 +5946,58 @@ impl WindowManager {
                     return Ok(Control::Continue);
                 }
 
-                if !self.windows.contains(&event.window) {
+                if let Some(kind) = self.unmanaged_kind(event.window) {
+                    self.map_unmanaged(event.window, kind)?;
+                } else if !self.windows.contains(&event.window) {
                     self.manage_window(event.window)?;
                 }
             }
             Event::UnmapNotify(event) => {
-                if self.windows.contains(&event.window) && self.is_window_visible(event.window) {
+                if self.unmanaged.contains_key(&event.window) {
+                    self.remove_unmanaged(event.window)?;
+                } else if self.windows.contains(&event.window)
+                    && self.is_window_visible(event.window)
+                {
                     self.remove_window(event.window, false)?;
                 }
             }
             Event::DestroyNotify(event) => {
-                if self.windows.contains(&event.window) {
+                if let Some(tray) = &mut self.systray
+                    && tray.remove(event.window)
+                {
+                    self.update_systray()?;
+                    return Ok(Control::Continue);
+                }
+
+                if self.unmanaged.contains_key(&event.window) {
+                    self.remove_unmanaged(event.window)?;
+                } else if self.windows.contains(&event.window) {
                     self.remove_window(event.window, true)?;
                 }
             }
             Event::PropertyNotify(event) => {
+                if let Some(tray) = &mut self.systray
+                    && tray.is_xembed_info(event.atom)
+                    && tray.contains(event.window)
+                {
+                    if tray.update_icon(&self.connection, event.window) {
+                        self.update_systray()?;
+                    }
+                    return Ok(Control::Continue);
+                }
+
+                if let Some(UnmanagedWindow::Dock(_)) = self.unmanaged.get(&event.window)
+                    && (event.atom == self.atoms.net_wm_strut
+                        || event.atom == self.atoms.net_wm_strut_partial)
+                {
+                    let strut = self.read_strut(event.window).unwrap_or_default();
+                    self.unmanaged
+                        .insert(event.window, UnmanagedWindow::Dock(strut));
+                    self.update_struts();
+                    self.apply_layout()?;
+                    return Ok(Control::Continue);
+                }
+
                 if event.state == Property::DELETE {
                     return Ok(Control::Continue);
                 }
@@ -3345,7 +6006,7 @@ impl WindowManager {
                     return Ok(Control::Continue);
                 }
 
-                if event.atom == AtomEnum::WM_TRANSIENT_FOR.into() {
+                if event.atom == Atom::from(AtomEnum::WM_TRANSIENT_FOR) {
                     let is_floating = self
                         .clients
                         .get(&event.window)
@@ -3361,24 +6022,47 @@ impl WindowManager {
                         self.floating_windows.insert(event.window);
                         self.apply_layout()?;
                     }
-                } else if event.atom == AtomEnum::WM_NORMAL_HINTS.into() {
+                } else if event.atom == Atom::from(AtomEnum::WM_NORMAL_HINTS) {
                     if let Some(c) = self.clients.get_mut(&event.window) {
                         c.hints_valid = false;
                     }
-                } else if event.atom == AtomEnum::WM_HINTS.into() {
+                } else if event.atom == Atom::from(AtomEnum::WM_HINTS) {
                     self.update_window_hints(event.window)?;
                     self.update_bar()?;
                 }
 
                 if event.atom == self.atoms.wm_name || event.atom == self.atoms.net_wm_name {
                     let _ = self.update_window_title(event.window);
-                    if self.layout.name() == "tabbed" {
+                    if self.has_tabbed_monitor() {
+                        self.update_tab_bars()?;
+                    }
+                }
+
+                if event.atom == Atom::from(AtomEnum::WM_CLASS) {
+                    self.update_window_class(event.window);
+                }
+
+                if event.atom == self.atoms.wm_protocols {
+                    self.update_window_protocols(event.window)?;
+                }
+
+                if event.atom == self.atoms.net_wm_icon {
+                    self.update_window_icon(event.window);
+                    if self.has_tabbed_monitor() {
                         self.update_tab_bars()?;
                     }
+                    if self.config.tag_icons {
+                        self.update_bar()?;
+                    }
                 }
 
                 if event.atom == self.atoms.net_wm_window_type {
+                    let was_floating = self.floating_windows.contains(&event.window);
                     self.update_window_type(event.window)?;
+                    if self.floating_windows.contains(&event.window) != was_floating {
+                        self.apply_layout()?;
+                        self.restack()?;
+                    }
                 }
             }
             Event::EnterNotify(event) => {
@@ -3398,7 +6082,7 @@ impl WindowManager {
                         {
                             self.unfocus(old_selected, false)?;
                         }
-                        self.selected_monitor = client.monitor_index;
+                        self.select_monitor(client.monitor_index);
                         self.update_bar()?;
                     }
                     self.focus(Some(event.event))?;
@@ -3422,7 +6106,7 @@ impl WindowManager {
                         self.unfocus(old_selected, true)?;
                     }
 
-                    self.selected_monitor = monitor_index;
+                    self.select_monitor(monitor_index);
                     self.focus(None)?;
                     self.update_bar()?;
                     self.update_tab_bars()?;
@@ -3447,44 +6131,8 @@ impl WindowManager {
                         self.grab_keys()?;
                         self.update_bar()?;
 
-                        match action {
-                            KeyAction::Quit => return Ok(Control::Quit),
-                            KeyAction::Restart => match self.try_reload_config() {
-                                Ok(()) => {
-                                    self.gaps_enabled = self.config.gaps_enabled;
-                                    self.error_message = None;
-                                    if let Err(error) = self.overlay.hide(&self.connection) {
-                                        eprintln!(
-                                            "Failed to hide overlay after config reload: {:?}",
-                                            error
-                                        );
-                                    }
-                                    self.apply_layout()?;
-                                    self.update_bar()?;
-                                }
-                                Err(err) => {
-                                    eprintln!("Config reload error: {}", err);
-                                    self.error_message = Some(err.to_string());
-                                    let monitor = &self.monitors[self.selected_monitor];
-                                    let monitor_x = monitor.screen_x as i16;
-                                    let monitor_y = monitor.screen_y as i16;
-                                    let screen_width = monitor.screen_width as u16;
-                                    let screen_height = monitor.screen_height as u16;
-                                    match self.overlay.show_error(
-                                        &self.connection,
-                                        &self.font,
-                                        err,
-                                        monitor_x,
-                                        monitor_y,
-                                        screen_width,
-                                        screen_height,
-                                    ) {
-                                        Ok(()) => eprintln!("Error modal displayed"),
-                                        Err(e) => eprintln!("Failed to show error modal: {:?}", e),
-                                    }
-                                }
-                            },
-                            _ => self.handle_key_action(action, &arg)?,
+                        if matches!(self.run_action(action, &arg)?, Control::Quit) {
+                            return Ok(Control::Quit);
                         }
                     }
                     keyboard::handlers::KeychordResult::InProgress(candidates) => {
@@ -3522,7 +6170,7 @@ impl WindowManager {
                 if let Some((monitor_index, bar)) = is_bar_click {
                     if let Some(tag_index) = bar.handle_click(event.event_x) {
                         if monitor_index != self.selected_monitor {
-                            self.selected_monitor = monitor_index;
+                            self.select_monitor(monitor_index);
                         }
                         self.view_tag(tag_index)?;
                     }
@@ -3530,12 +6178,11 @@ impl WindowManager {
                     let is_tab_bar_click = self
                         .tab_bars
                         .iter()
-                        .enumerate()
-                        .find(|(_, tab_bar)| tab_bar.window() == event.event);
+                        .position(|tab_bar| tab_bar.window() == event.event);
 
-                    if let Some((monitor_index, tab_bar)) = is_tab_bar_click {
+                    if let Some(monitor_index) = is_tab_bar_click {
                         if monitor_index != self.selected_monitor {
-                            self.selected_monitor = monitor_index;
+                            self.select_monitor(monitor_index);
                         }
 
                         let visible_windows: Vec<(Window, String)> = self
@@ -3554,7 +6201,7 @@ impl WindowManager {
                                         .get(monitor_index)
                                         .map(|m| m.tagset[m.selected_tags_index])
                                         .unwrap_or(0);
-                                    if (client.tags & monitor_tags) != 0 {
+                                    if client.is_visible_on(monitor_tags) {
                                         return Some((window, client.name.clone()));
                                     }
                                 }
@@ -3562,8 +6209,8 @@ impl WindowManager {
                             })
                             .collect();
 
-                        if let Some(clicked_window) =
-                            tab_bar.get_clicked_window(&visible_windows, event.event_x)
+                        if let Some(clicked_window) = self.tab_bars[monitor_index]
+                            .get_clicked_window(&visible_windows, event.event_x)
                         {
                             self.connection.configure_window(
                                 clicked_window,
@@ -3581,13 +6228,13 @@ impl WindowManager {
                             & !(u16::from(ModMask::LOCK) | u16::from(ModMask::M2));
                         let modkey_held = state_clean & u16::from(self.config.modkey) != 0;
 
-                        if modkey_held && event.detail == ButtonIndex::M1.into() {
+                        if modkey_held && event.detail == u8::from(ButtonIndex::M1) {
                             if self.clients.contains_key(&event.child) {
                                 self.drag_window(event.child)?;
                             }
                             self.connection
                                 .allow_events(Allow::REPLAY_POINTER, event.time)?;
-                        } else if modkey_held && event.detail == ButtonIndex::M3.into() {
+                        } else if modkey_held && event.detail == u8::from(ButtonIndex::M3) {
                             if self.clients.contains_key(&event.child) {
                                 self.resize_window_with_mouse(event.child)?;
                             }
@@ -3606,11 +6253,11 @@ impl WindowManager {
                             & !(u16::from(ModMask::LOCK) | u16::from(ModMask::M2));
                         let modkey_held = state_clean & u16::from(self.config.modkey) != 0;
 
-                        if modkey_held && event.detail == ButtonIndex::M1.into() {
+                        if modkey_held && event.detail == u8::from(ButtonIndex::M1) {
                             self.drag_window(event.event)?;
                             self.connection
                                 .allow_events(Allow::REPLAY_POINTER, event.time)?;
-                        } else if modkey_held && event.detail == ButtonIndex::M3.into() {
+                        } else if modkey_held && event.detail == u8::from(ButtonIndex::M3) {
                             self.resize_window_with_mouse(event.event)?;
                             self.connection
                                 .allow_events(Allow::REPLAY_POINTER, event.time)?;
@@ -3752,43 +6399,102 @@ impl WindowManager {
                 self.connection.flush()?;
             }
             Event::ClientMessage(event) => {
+                if let Some(tray) = &mut self.systray
+                    && tray.is_opcode_message(&event)
+                {
+                    if tray.handle_opcode(&self.connection, &event)? {
+                        self.update_systray()?;
+                    }
+                    return Ok(Control::Continue);
+                }
+
+                if event.type_ == self.atoms.net_current_desktop {
+                    let desktop = event.data.as_data32()[0] as usize;
+                    let current = self
+                        .monitors
+                        .get(self.selected_monitor)
+                        .map(|m| m.tagset[m.selected_tags_index]);
+                    // Pagers re-request the current desktop on every click;
+                    // don't let that trigger tag_back_and_forth.
+                    if desktop < self.config.tags.len() && current != Some(tag_mask(desktop)) {
+                        self.view_tag(desktop)?;
+                    }
+                    return Ok(Control::Continue);
+                }
+
+                // Ping replies are sent back to the root window.
+                if event.type_ == self.atoms.wm_protocols
+                    && event.data.as_data32()[0] == self.atoms.net_wm_ping
+                {
+                    let window = event.data.as_data32()[2];
+                    self.pending_pings.remove(&window);
+                    self.set_unresponsive(window, false)?;
+                    return Ok(Control::Continue);
+                }
+
+                // Sent before the window is mapped, so it may not be a
+                // client yet.
+                if event.type_ == self.atoms.net_request_frame_extents {
+                    self.set_frame_extents(event.window, self.config.border_width)?;
+                    return Ok(Control::Continue);
+                }
+
                 if !self.clients.contains_key(&event.window) {
                     return Ok(Control::Continue);
                 }
 
                 if event.type_ == self.atoms.net_wm_state {
                     let data = event.data.as_data32();
-                    let atom1 = data.get(1).copied().unwrap_or(0);
-                    let atom2 = data.get(2).copied().unwrap_or(0);
-
-                    if atom1 == self.atoms.net_wm_state_fullscreen
-                        || atom2 == self.atoms.net_wm_state_fullscreen
+                    let action = data[0];
+
+                    // Both maximize axes usually arrive in one message;
+                    // apply them together so the restore geometry is only
+                    // captured once.
+                    let vert = self.atoms.net_wm_state_maximized_vert;
+                    let horz = self.atoms.net_wm_state_maximized_horz;
+                    if (data[1] == vert && data[2] == horz) || (data[1] == horz && data[2] == vert)
                     {
-                        let action = data[0];
-                        let fullscreen = match action {
-                            1 => true,
+                        let (is_vert, is_horz) = self
+                            .clients
+                            .get(&event.window)
+                            .map(|c| (c.is_maximized_vert, c.is_maximized_horz))
+                            .unwrap_or_default();
+                        let maximize = match action {
                             0 => false,
-                            2 => !self.fullscreen_windows.contains(&event.window),
+                            1 => true,
+                            2 => !(is_vert && is_horz),
                             _ => return Ok(Control::Continue),
                         };
-                        self.set_window_fullscreen(event.window, fullscreen)?;
-                        self.restack()?;
+                        self.set_maximized(event.window, maximize, maximize)?;
+                        self.write_net_wm_state(event.window)?;
+                    } else {
+                        for &state in &data[1..3] {
+                            self.set_net_wm_state(event.window, state, action)?;
+                        }
                     }
-                } else if event.type_ == self.atoms.net_active_window {
-                    let selected_window = self
-                        .monitors
-                        .get(self.selected_monitor)
-                        .and_then(|m| m.selected_client);
-
-                    let is_urgent = self
-                        .clients
-                        .get(&event.window)
-                        .map(|c| c.is_urgent)
-                        .unwrap_or(false);
 
-                    if Some(event.window) != selected_window && !is_urgent {
-                        self.set_urgent(event.window, true)?;
+                    self.apply_layout()?;
+                    if !self.is_visible(event.window)
+                        && self
+                            .monitors
+                            .get(self.selected_monitor)
+                            .and_then(|m| m.selected_client)
+                            == Some(event.window)
+                    {
+                        self.focus(None)?;
                     }
+                    self.restack()?;
+                    self.update_bar()?;
+                } else if event.type_ == self.atoms.net_active_window {
+                    self.activate_window(event.window)?;
+                } else if event.type_ == self.atoms.net_close_window {
+                    self.kill_client(event.window)?;
+                } else if event.type_ == self.atoms.net_moveresize_window {
+                    let data = event.data.as_data32();
+                    self.moveresize_window(event.window, data[0], &data[1..5])?;
+                } else if event.type_ == self.atoms.net_wm_moveresize {
+                    let direction = event.data.as_data32()[2];
+                    self.start_moveresize(event.window, direction)?;
                 }
             }
             Event::FocusIn(event) => {
@@ -3818,47 +6524,51 @@ impl WindowManager {
                     self.grab_keys()?;
                 }
             }
-            Event::ConfigureNotify(event) => {
-                if event.window == self.root {
-                    let old_width = self.screen.width_in_pixels;
-                    let old_height = self.screen.height_in_pixels;
-
-                    if event.width != old_width || event.height != old_height {
-                        self.screen = self.connection.setup().roots[self.screen_number].clone();
-
-                        for monitor_index in 0..self.monitors.len() {
-                            let monitor = &self.monitors[monitor_index];
-                            let monitor_x = monitor.screen_x;
-                            let monitor_y = monitor.screen_y;
-                            let monitor_width = monitor.screen_width as u32;
-                            let monitor_height = monitor.screen_height as u32;
-
-                            let fullscreen_on_monitor: Vec<Window> = self
-                                .fullscreen_windows
-                                .iter()
-                                .filter(|&&window| {
-                                    self.clients
-                                        .get(&window)
-                                        .map(|client| client.monitor_index == monitor_index)
-                                        .unwrap_or(false)
-                                })
-                                .copied()
-                                .collect();
-
-                            for window in fullscreen_on_monitor {
-                                self.connection.configure_window(
-                                    window,
-                                    &ConfigureWindowAux::new()
-                                        .x(monitor_x)
-                                        .y(monitor_y)
-                                        .width(monitor_width)
-                                        .height(monitor_height),
-                                )?;
-                            }
-                        }
-
-                        self.apply_layout()?;
+            Event::ConfigureNotify(event)
+                if event.window == self.root
+                    && (event.width != self.screen.width_in_pixels
+                        || event.height != self.screen.height_in_pixels) =>
+            {
+                self.screen.width_in_pixels = event.width;
+                self.screen.height_in_pixels = event.height;
+                self.monitors_dirty = true;
+            }
+            Event::RandrScreenChangeNotify(event) if event.root == self.root => {
+                self.screen.width_in_pixels = event.width;
+                self.screen.height_in_pixels = event.height;
+                self.monitors_dirty = true;
+            }
+            Event::RandrNotify(_) => {
+                self.monitors_dirty = true;
+            }
+            Event::ReparentNotify(event) => {
+                if let Some(tray) = &mut self.systray
+                    && event.parent != tray.window()
+                    && tray.remove(event.window)
+                {
+                    self.update_systray()?;
+                }
+            }
+            // Icons get the bar's height no matter what they ask for.
+            Event::ResizeRequest(event)
+                if self
+                    .systray
+                    .as_ref()
+                    .is_some_and(|tray| tray.contains(event.window)) =>
+            {
+                self.update_systray()?;
+            }
+            Event::SelectionClear(event) => {
+                if let Some(tray) = &mut self.systray
+                    && event.owner == tray.window()
+                {
+                    eprintln!("Another system tray took over, removing ours");
+                    tray.release(&self.connection, self.root)?;
+                    if let Some(bar) = self.bars.get_mut(tray.monitor_index()) {
+                        bar.set_tray_width(0);
                     }
+                    self.systray = None;
+                    self.update_bar()?;
                 }
             }
             _ => {}
@@ -3872,120 +6582,100 @@ impl WindowManager {
             self.showhide(stack_head)?;
         }
 
-        let is_normie = self.layout.name() == LayoutType::Normie.as_str();
+        let monitor_count = self.monitors.len();
+        for monitor_index in 0..monitor_count {
+            let layout = self.monitor_layout(monitor_index);
+            if layout.name() == LayoutType::Normie.as_str() {
+                continue;
+            }
+
+            let monitor = &self.monitors[monitor_index];
+            let border_width = self.config.border_width;
+            let gaps = self.monitor_gaps(monitor);
 
-        if !is_normie {
-            let monitor_count = self.monitors.len();
-            for monitor_index in 0..monitor_count {
-                let monitor = &self.monitors[monitor_index];
-                let border_width = self.config.border_width;
+            let monitor_x = monitor.window_area_x;
+            let monitor_y = monitor.window_area_y;
+            let monitor_width = monitor.window_area_width;
+            let monitor_height = monitor.window_area_height;
+            let scroll_offset = monitor.scroll_offset;
 
-                let gaps = if self.gaps_enabled {
-                    GapConfig {
-                        inner_horizontal: self.config.gap_inner_horizontal,
-                        inner_vertical: self.config.gap_inner_vertical,
-                        outer_horizontal: self.config.gap_outer_horizontal,
-                        outer_vertical: self.config.gap_outer_vertical,
-                    }
+            let mut visible: Vec<Window> = Vec::new();
+            let mut current = self.next_tiled(monitor.clients_head, monitor);
+            while let Some(window) = current {
+                visible.push(window);
+                if let Some(client) = self.clients.get(&window) {
+                    current = self.next_tiled(client.next, monitor);
                 } else {
-                    GapConfig {
-                        inner_horizontal: 0,
-                        inner_vertical: 0,
-                        outer_horizontal: 0,
-                        outer_vertical: 0,
-                    }
-                };
+                    break;
+                }
+            }
 
-                let monitor_x = monitor.screen_x;
-                let monitor_y = monitor.screen_y;
-                let monitor_width = monitor.screen_width;
-                let monitor_height = monitor.screen_height;
-                let scroll_offset = monitor.scroll_offset;
+            let bar_height = self.bar_space(monitor_index);
+            let bar_offset = if monitor.top_bar { bar_height } else { 0 };
+            let usable_height = monitor_height.saturating_sub(bar_height);
+            let master_factor = monitor.master_factor;
+            let num_master = monitor.num_master;
+            let smartgaps_enabled = self.config.smartgaps_enabled;
+
+            let geometries = layout.arrange(
+                &visible,
+                monitor_width as u32,
+                usable_height as u32,
+                &gaps,
+                master_factor,
+                num_master,
+                smartgaps_enabled,
+            );
 
-                let mut visible: Vec<Window> = Vec::new();
-                let mut current = self.next_tiled(monitor.clients_head, monitor);
-                while let Some(window) = current {
-                    visible.push(window);
-                    if let Some(client) = self.clients.get(&window) {
-                        current = self.next_tiled(client.next, monitor);
-                    } else {
-                        break;
-                    }
+            for (window, geometry) in visible.iter().zip(geometries.iter()) {
+                let mut adjusted_width = geometry.width.saturating_sub(2 * border_width);
+                let mut adjusted_height = geometry.height.saturating_sub(2 * border_width);
+
+                if let Some(client) = self.clients.get(window).cloned()
+                    && !client.is_floating
+                {
+                    let (_, _, hint_width, hint_height, _) = self.apply_size_hints(
+                        *window,
+                        geometry.x_coordinate,
+                        geometry.y_coordinate,
+                        adjusted_width as i32,
+                        adjusted_height as i32,
+                    );
+                    adjusted_width = hint_width as u32;
+                    adjusted_height = hint_height as u32;
                 }
 
-                let bar_height = if self.show_bar {
-                    self.bars
-                        .get(monitor_index)
-                        .map(|bar| bar.height() as u32)
-                        .unwrap_or(0)
+                let is_scrolling = layout.name() == "scrolling";
+                let adjusted_x = if is_scrolling {
+                    geometry.x_coordinate + monitor_x - scroll_offset
                 } else {
-                    0
+                    geometry.x_coordinate + monitor_x
                 };
-                let usable_height = monitor_height.saturating_sub(bar_height as i32);
-                let master_factor = monitor.master_factor;
-                let num_master = monitor.num_master;
-                let smartgaps_enabled = self.config.smartgaps_enabled;
-
-                let geometries = self.layout.arrange(
-                    &visible,
-                    monitor_width as u32,
-                    usable_height as u32,
-                    &gaps,
-                    master_factor,
-                    num_master,
-                    smartgaps_enabled,
-                );
-
-                for (window, geometry) in visible.iter().zip(geometries.iter()) {
-                    let mut adjusted_width = geometry.width.saturating_sub(2 * border_width);
-                    let mut adjusted_height = geometry.height.saturating_sub(2 * border_width);
-
-                    if let Some(client) = self.clients.get(window).cloned()
-                        && !client.is_floating
-                    {
-                        let (_, _, hint_width, hint_height, _) = self.apply_size_hints(
-                            *window,
-                            geometry.x_coordinate,
-                            geometry.y_coordinate,
-                            adjusted_width as i32,
-                            adjusted_height as i32,
-                        );
-                        adjusted_width = hint_width as u32;
-                        adjusted_height = hint_height as u32;
-                    }
-
-                    let is_scrolling = self.layout.name() == "scrolling";
-                    let adjusted_x = if is_scrolling {
-                        geometry.x_coordinate + monitor_x - scroll_offset
-                    } else {
-                        geometry.x_coordinate + monitor_x
-                    };
-                    let adjusted_y = geometry.y_coordinate + monitor_y + bar_height as i32;
+                let adjusted_y = geometry.y_coordinate + monitor_y + bar_offset;
 
-                    if let Some(client) = self.clients.get_mut(window) {
-                        client.x_position = adjusted_x as i16;
-                        client.y_position = adjusted_y as i16;
-                        client.width = adjusted_width as u16;
-                        client.height = adjusted_height as u16;
-                    }
+                if let Some(client) = self.clients.get_mut(window) {
+                    client.x_position = adjusted_x as i16;
+                    client.y_position = adjusted_y as i16;
+                    client.width = adjusted_width as u16;
+                    client.height = adjusted_height as u16;
+                }
 
-                    self.connection.configure_window(
-                        *window,
-                        &ConfigureWindowAux::new()
-                            .x(adjusted_x)
-                            .y(adjusted_y)
-                            .width(adjusted_width)
-                            .height(adjusted_height)
-                            .border_width(border_width),
-                    )?;
+                self.connection.configure_window(
+                    *window,
+                    &ConfigureWindowAux::new()
+                        .x(adjusted_x)
+                        .y(adjusted_y)
+                        .width(adjusted_width)
+                        .height(adjusted_height)
+                        .border_width(border_width),
+                )?;
 
-                    if let Some(c) = self.clients.get_mut(window) {
-                        c.x_position = adjusted_x as i16;
-                        c.y_position = adjusted_y as i16;
-                        c.width = adjusted_width as u16;
-                        c.height = adjusted_height as u16;
-                        c.border_width = border_width as u16;
-                    }
+                if let Some(c) = self.clients.get_mut(window) {
+                    c.x_position = adjusted_x as i16;
+                    c.y_position = adjusted_y as i16;
+                    c.width = adjusted_width as u16;
+                    c.height = adjusted_height as u16;
+                    c.border_width = border_width as u16;
                 }
             }
         }
@@ -4001,7 +6691,7 @@ impl WindowManager {
 
             let has_visible_fullscreen = self.fullscreen_windows.iter().any(|&w| {
                 self.clients.get(&w).map_or(false, |c| {
-                    c.monitor_index == monitor_index && (c.tags & tags) != 0
+                    c.monitor_index == monitor_index && c.is_visible_on(tags)
                 })
             });
 
@@ -4026,56 +6716,47 @@ impl WindowManager {
                         }
                     }
                 }
-            } else if self.show_bar {
-                if let Some(bar) = self.bars.get(monitor_index) {
+            } else if let Some(bar) = self.bars.get(monitor_index) {
+                if monitor.show_bar {
                     self.connection.map_window(bar.window())?;
+                } else {
+                    self.connection.unmap_window(bar.window())?;
                 }
             }
         }
 
         self.connection.flush()?;
 
-        let is_tabbed = self.layout.name() == LayoutType::Tabbed.as_str();
-
-        if is_tabbed {
-            let outer_horizontal = if self.gaps_enabled {
-                self.config.gap_outer_horizontal
-            } else {
-                0
-            };
-            let outer_vertical = if self.gaps_enabled {
-                self.config.gap_outer_vertical
-            } else {
-                0
-            };
+        let tabbed: Vec<bool> = (0..self.monitors.len())
+            .map(|index| self.monitor_layout(index).name() == LayoutType::Tabbed.as_str())
+            .collect();
 
-            for monitor_index in 0..self.tab_bars.len() {
-                if let Some(monitor) = self.monitors.get(monitor_index) {
-                    let bar_height = if self.show_bar {
-                        self.bars
-                            .get(monitor_index)
-                            .map(|bar| bar.height() as f32)
-                            .unwrap_or(0.0)
-                    } else {
-                        0.0
-                    };
+        for monitor_index in 0..self.tab_bars.len() {
+            if !tabbed.get(monitor_index).copied().unwrap_or(false) {
+                continue;
+            }
+            if let Some(monitor) = self.monitors.get(monitor_index) {
+                let gaps = self.monitor_gaps(monitor);
+                let bar_offset = if monitor.top_bar {
+                    self.bar_space(monitor_index)
+                } else {
+                    0
+                };
 
-                    let tab_bar_x = (monitor.screen_x + outer_horizontal as i32) as i16;
-                    let tab_bar_y =
-                        (monitor.screen_y as f32 + bar_height + outer_vertical as f32) as i16;
-                    let tab_bar_width = monitor
-                        .screen_width
-                        .saturating_sub(2 * outer_horizontal as i32)
-                        as u16;
+                let tab_bar_x = (monitor.screen_x + gaps.outer_horizontal as i32) as i16;
+                let tab_bar_y = (monitor.screen_y + bar_offset + gaps.outer_vertical as i32) as i16;
+                let tab_bar_width = monitor
+                    .screen_width
+                    .saturating_sub(2 * gaps.outer_horizontal as i32)
+                    as u16;
 
-                    if let Err(e) = self.tab_bars[monitor_index].reposition(
-                        &self.connection,
-                        tab_bar_x,
-                        tab_bar_y,
-                        tab_bar_width,
-                    ) {
-                        eprintln!("Failed to reposition tab bar: {:?}", e);
-                    }
+                if let Err(e) = self.tab_bars[monitor_index].reposition(
+                    &self.connection,
+                    tab_bar_x,
+                    tab_bar_y,
+                    tab_bar_width,
+                ) {
+                    eprintln!("Failed to reposition tab bar: {:?}", e);
                 }
             }
         }
@@ -4090,12 +6771,13 @@ impl WindowManager {
                         return false;
                     }
                     if let Some(monitor) = self.monitors.get(monitor_index) {
-                        return (client.tags & monitor.tagset[monitor.selected_tags_index]) != 0;
+                        return client.is_visible_on(monitor.tagset[monitor.selected_tags_index]);
                     }
                 }
                 false
             });
 
+            let is_tabbed = tabbed.get(monitor_index).copied().unwrap_or(false);
             if is_tabbed && has_visible_windows {
                 if let Err(e) = self.tab_bars[monitor_index].show(&self.connection) {
                     eprintln!("Failed to show tab bar: {:?}", e);
@@ -4105,14 +6787,23 @@ impl WindowManager {
             }
         }
 
-        if is_tabbed {
+        if tabbed.contains(&true) {
             self.update_tab_bars()?;
         }
 
+        self.update_workarea()?;
+
         Ok(())
     }
 
     pub fn change_layout<L: Layout + 'static>(&mut self, new_layout: L) -> WmResult<()> {
+        if let Some(pertag) = self
+            .monitors
+            .get_mut(self.selected_monitor)
+            .and_then(|monitor| monitor.pertag.as_mut())
+        {
+            pertag.layouts[pertag.current_tag] = new_layout.name().to_string();
+        }
         self.layout = Box::new(new_layout);
         self.apply_layout()?;
         Ok(())
@@ -4265,9 +6956,8 @@ impl WindowManager {
         if let Some(name) = net_name
             && !name.value.is_empty()
             && let Ok(title) = String::from_utf8(name.value.clone())
-            && let Some(client) = self.clients.get_mut(&window)
         {
-            client.name = title;
+            self.set_client_title(window, title);
             return Ok(());
         }
 
@@ -4285,14 +6975,53 @@ impl WindowManager {
 
         if !wm_name.value.is_empty()
             && let Ok(title) = String::from_utf8(wm_name.value.clone())
-            && let Some(client) = self.clients.get_mut(&window)
         {
-            client.name = title;
+            self.set_client_title(window, title);
         }
 
         Ok(())
     }
 
+    fn set_client_title(&mut self, window: Window, title: String) {
+        let Some(client) = self.clients.get_mut(&window) else {
+            return;
+        };
+
+        if client.name != title {
+            client.name = title.clone();
+            self.emit(IpcEvent::Title {
+                window,
+                name: title,
+            });
+        }
+    }
+
+    /// Re-reads `_NET_WM_ICON` and scales the image closest to the bar's icon
+    /// size once, so redraws can reuse it.
+    fn update_window_icon(&mut self, window: Window) {
+        let icon = self
+            .connection
+            .get_property(
+                false,
+                window,
+                self.atoms.net_wm_icon,
+                AtomEnum::CARDINAL,
+                0,
+                u32::MAX,
+            )
+            .ok()
+            .and_then(|cookie| cookie.reply().ok())
+            .and_then(|reply| {
+                let data: Vec<u32> = reply.value32()?.collect();
+                Icon::from_net_wm_icon(self.display, &data, Bar::icon_size_for(&self.font))
+            })
+            .map(Rc::new);
+
+        if let Some(client) = self.clients.get_mut(&window) {
+            client.icon = icon;
+        }
+    }
+
     fn update_window_hints(&mut self, window: Window) -> WmResult<()> {
         let hints_reply = self
             .connection
@@ -4329,7 +7058,12 @@ impl WindowManager {
                     &new_hints,
                 )?;
             } else if let Some(client) = self.clients.get_mut(&window) {
-                client.is_urgent = (flags & 256) != 0;
+                let urgent = (flags & 256) != 0;
+                if client.is_urgent != urgent {
+                    client.is_urgent = urgent;
+                    self.emit(IpcEvent::Urgent { window, urgent });
+                    self.write_net_wm_state(window)?;
+                }
             }
 
             if hints.value.len() >= 8 && (flags & 1) != 0 {
@@ -4354,24 +7088,53 @@ impl WindowManager {
     fn update_window_type(&mut self, window: Window) -> WmResult<()> {
         if let Ok(state_atoms) = self.get_window_atom_list_property(window, self.atoms.net_wm_state)
         {
-            if state_atoms.contains(&self.atoms.net_wm_state_fullscreen) {
-                self.set_window_fullscreen(window, true)?;
+            for state in state_atoms {
+                self.set_net_wm_state(window, state, 1)?;
             }
         }
 
-        if let Ok(Some(type_atom)) =
-            self.get_window_atom_property(window, self.atoms.net_wm_window_type)
-            && type_atom == self.atoms.net_wm_window_type_dialog
+        let floating_types = [
+            self.atoms.net_wm_window_type_dialog,
+            self.atoms.net_wm_window_type_splash,
+            self.atoms.net_wm_window_type_utility,
+            self.atoms.net_wm_window_type_toolbar,
+            self.atoms.net_wm_window_type_menu,
+        ];
+        let types = self.get_window_atom_list_property(window, self.atoms.net_wm_window_type)?;
+
+        if types.iter().any(|atom| floating_types.contains(atom))
+            && self.clients.get(&window).is_some_and(|c| !c.is_floating)
         {
             if let Some(client) = self.clients.get_mut(&window) {
                 client.is_floating = true;
             }
             self.floating_windows.insert(window);
+            self.center_client(window);
         }
 
         Ok(())
     }
 
+    /// Centers a floating client in its monitor's window area.
+    fn center_client(&mut self, window: Window) {
+        let Some(client) = self.clients.get(&window) else {
+            return;
+        };
+        let Some(monitor) = self.monitors.get(client.monitor_index) else {
+            return;
+        };
+
+        let width = client.width as i32 + 2 * client.border_width as i32;
+        let height = client.height as i32 + 2 * client.border_width as i32;
+        let x = monitor.window_area_x + (monitor.window_area_width - width).max(0) / 2;
+        let y = monitor.window_area_y + (monitor.window_area_height - height).max(0) / 2;
+
+        if let Some(client) = self.clients.get_mut(&window) {
+            client.x_position = x as i16;
+            client.y_position = y as i16;
+        }
+    }
+
     fn apply_size_hints(
         &mut self,
         window: Window,
@@ -4520,8 +7283,9 @@ impl WindowManager {
         let mut current = start;
         while let Some(window) = current {
             if let Some(client) = self.clients.get(&window) {
-                let visible_tags = client.tags & monitor.tagset[monitor.selected_tags_index];
-                if visible_tags != 0 && !client.is_floating {
+                if client.is_visible_on(monitor.tagset[monitor.selected_tags_index])
+                    && !client.is_floating
+                {
                     return Some(window);
                 }
                 current = client.next;
@@ -4536,7 +7300,7 @@ impl WindowManager {
         let mut current = start;
         while let Some(window) = current {
             if let Some(client) = self.clients.get(&window) {
-                let visible_on_tags = (client.tags & tags) != 0;
+                let visible_on_tags = client.is_visible_on(tags);
                 if !client.is_floating && visible_on_tags {
                     return Some(window);
                 }
@@ -4697,6 +7461,12 @@ impl WindowManager {
             .get(self.selected_monitor)
             .and_then(|m| m.selected_client);
 
+        let unmanaged = if self.lua.has_hooks(HookEvent::ClientUnmanage) {
+            self.client_info(window)
+        } else {
+            None
+        };
+
         if !destroyed {
             if let Some(client) = self.clients.get(&window) {
                 let old_border_width = client.old_border_width;
@@ -4706,6 +7476,13 @@ impl WindowManager {
                 )?;
             }
             self.set_wm_state(window, 0)?;
+            self.connection
+                .delete_property(window, self.atoms.net_wm_desktop)?;
+        }
+
+        self.pending_pings.remove(&window);
+        if self.pending_kill == Some(window) {
+            self.close_confirm()?;
         }
 
         if self.clients.contains_key(&window) {
@@ -4719,6 +7496,12 @@ impl WindowManager {
         self.update_client_list()?;
 
         if self.windows.len() < initial_count {
+            self.emit(IpcEvent::Unmanage { window });
+            if let Some(info) = unmanaged {
+                self.pending_hooks
+                    .push((HookEvent::ClientUnmanage, HookArg::Client(info)));
+            }
+
             if focused == Some(window) {
                 let visible = self.visible_windows_on_monitor(self.selected_monitor);
                 if let Some(&new_win) = visible.last() {
@@ -4742,6 +7525,14 @@ impl WindowManager {
     }
 
     fn has_windows_on_tag(&self, monitor_number: usize, tag_index: usize) -> bool {
+        // Shared tags hold windows no matter which monitor last showed them.
+        if self.config.shared_tags.is_enabled() {
+            return self
+                .clients
+                .values()
+                .any(|client| unmask_tag(client.tags) == tag_index);
+        }
+
         let Some(monitor) = self.monitors.get(monitor_number) else {
             return false;
         };
@@ -102,6 +102,9 @@ oxwm.set_terminal(terminal)
 oxwm.set_modkey(modkey) -- This is for Mod + mouse binds, such as drag/resize
 oxwm.set_tags(tags)
 
+-- Reload automatically when this file (or a require'd module) is saved
+-- oxwm.auto_reload(true)
+
 -------------------------------------------------------------------------------
 -- Layouts
 -------------------------------------------------------------------------------
@@ -16,7 +16,8 @@
 ---@class oxwm
 oxwm = {}
 
----Spawn a command
+---Spawn a command. Its windows open on the tags and monitor it was spawned on,
+---even if you switch away before they map.
 ---@param cmd string|string[] Command to spawn (string or array of strings)
 ---@return table Action table for keybinding
 function oxwm.spawn(cmd) end
@@ -41,6 +42,39 @@ function oxwm.set_tags(tags) end
 ---@param enabled boolean Enable or disable auto-tiling
 function oxwm.auto_tile(enabled) end
 
+---Reload the config automatically when it or a required module is saved
+---@param enabled boolean Enable or disable watching the config for changes
+function oxwm.auto_reload(enabled) end
+
+---@alias oxwm.ActivationPolicy
+---| "urgent"           # Only mark the window urgent (default)
+---| "focus"            # View the window's tag on its monitor and focus it
+---| "focus_if_visible" # Focus the window if it is visible, otherwise mark it urgent
+---| "bring"            # Move the window to the current tag and monitor and focus it
+
+---Choose what happens when an application asks for one of its windows to be
+---activated, e.g. a browser opening a link clicked in a terminal.
+---Rules can override this per window with their `activation` field.
+---@param policy oxwm.ActivationPolicy
+function oxwm.set_activation_policy(policy) end
+
+---@alias oxwm.HookEvent
+---| "client_manage"   # A window was mapped and is now managed (client table)
+---| "client_unmanage" # A managed window went away (client table)
+---| "client_urgent"   # A client's urgency hint changed (client table)
+---| "focus_change"    # Focus moved to another client (client table, or nil)
+---| "tag_view"        # The visible tags changed (monitor table)
+---| "layout_change"   # The layout changed (monitor table)
+---| "monitor_change"  # Another monitor was selected (monitor table)
+---| "title_change"    # A client's title changed (client table)
+
+---Run a function whenever a window manager event happens.
+---Action functions such as oxwm.spawn() or oxwm.tag.view() called inside the
+---callback run straight away, so queries later in the callback see their effect.
+---@param event oxwm.HookEvent Event name
+---@param callback fun(info: table?) Called with a client or monitor table
+function oxwm.on(event, callback) end
+
 ---Set layout symbol override
 ---@param name string Layout name (e.g., "tiling", "normie", "tabbed", "grid", "monocle")
 ---@param symbol string Symbol to display (e.g., "[T]", "[F]", "[=]")
@@ -51,7 +85,9 @@ function oxwm.set_layout_symbol(name, symbol) end
 oxwm.rule = {}
 
 ---Add a window rule
----@param rule {class: string?, instance: string?, title: string?, role: string?, floating: boolean?, tag: integer?, fullscreen: boolean?, focus: boolean?} Rule configuration
+---`monitor` is a monitor index, an output or EDID model name ("DP-2"), or a list
+---of those tried in order; the first one connected wins
+---@param rule {class: string?, instance: string?, title: string?, role: string?, floating: boolean?, tag: integer?, monitor: integer|string|(integer|string)[]?, fullscreen: boolean?, focus: boolean?, activation: oxwm.ActivationPolicy?} Rule configuration
 function oxwm.rule.add(rule) end
 
 ---Quit the window manager
@@ -89,14 +125,16 @@ function oxwm.inc_num_master(delta) end
 oxwm.key = {}
 
 ---Bind a key combination to an action
+---A function can be given instead of an action table; action functions called
+---inside it run straight away, so later queries such as oxwm.tag.current() see their effect.
 ---@param modifiers string|string[] Modifier keys (e.g., {"Mod4"}, {"Mod4", "Shift"})
 ---@param key string Key name (e.g., "Return", "Q", "1")
----@param action table Action returned by oxwm functions
+---@param action table|fun() Action returned by oxwm functions, or a Lua function
 function oxwm.key.bind(modifiers, key, action) end
 
 ---Bind a keychord (multi-key sequence) to an action
 ---@param keys table[] Array of key presses, each: {{modifiers}, key}
----@param action table Action returned by oxwm functions
+---@param action table|fun() Action returned by oxwm functions, or a Lua function
 function oxwm.key.chord(keys, action) end
 
 ---Gap configuration module
@@ -143,11 +181,16 @@ function oxwm.border.set_focused_color(color) end
 ---@param color string|integer Color as hex string ("#666666", "0x666666") or integer
 function oxwm.border.set_unfocused_color(color) end
 
+---Set the border color of windows that stopped answering pings
+---@param color string|integer Color as hex string ("#ff5555", "0xff5555") or integer
+function oxwm.border.set_unresponsive_color(color) end
+
 ---Client/window management module
 ---@class oxwm.client
 oxwm.client = {}
 
----Kill the focused window
+---Kill the focused window. On a window that has stopped responding, asks
+---for confirmation and then sends SIGKILL to its process.
 ---@return table Action table for keybinding
 function oxwm.client.kill() end
 
@@ -169,20 +212,106 @@ function oxwm.client.focus_stack(dir) end
 ---@return table Action table for keybinding
 function oxwm.client.move_stack(dir) end
 
+---@class oxwm.ClientInfo
+---@field window integer X window id
+---@field name string Window title
+---@field class string WM_CLASS class
+---@field instance string WM_CLASS instance
+---@field tags integer Tag bitmask
+---@field monitor integer Monitor index (0-based)
+---@field floating boolean
+---@field fullscreen boolean
+---@field urgent boolean
+---@field x integer
+---@field y integer
+---@field width integer
+---@field height integer
+
+---Get the focused client (only meaningful inside hooks and key functions)
+---@return oxwm.ClientInfo|nil
+function oxwm.client.focused() end
+
+---List every managed client
+---@return oxwm.ClientInfo[]
+function oxwm.client.list() end
+
+---Get a client's WM_CLASS class
+---@param window? integer Window id (defaults to the focused client)
+---@return string|nil
+function oxwm.client.get_class(window) end
+
+---Get a client's title
+---@param window? integer Window id (defaults to the focused client)
+---@return string|nil
+function oxwm.client.get_title(window) end
+
+---Get the tags a client is on
+---@param window? integer Window id (defaults to the focused client)
+---@return integer[]|nil indices Tag indices (0-based)
+function oxwm.client.get_tags(window) end
+
 ---Monitor management module
 ---@class oxwm.monitor
 oxwm.monitor = {}
 
 ---Focus monitor
----@param dir integer Direction (-1 for previous, 1 for next)
+---@param dir integer|string Direction (-1 for previous, 1 for next), or an output or model name
 ---@return table Action table for keybinding
 function oxwm.monitor.focus(dir) end
 
 ---Send focused window to monitor
----@param dir integer Direction (-1 for previous, 1 for next)
+---@param dir integer|string Direction (-1 for previous, 1 for next), or an output or model name
 ---@return table Action table for keybinding
 function oxwm.monitor.tag(dir) end
 
+---Focus the nearest monitor on one side of the focused one, going by where the monitors sit
+---@param direction "left"|"right"|"up"|"down"
+---@return table Action table for keybinding
+function oxwm.monitor.focus_direction(direction) end
+
+---Send focused window to the nearest monitor on one side of the focused one
+---@param direction "left"|"right"|"up"|"down"
+---@return table Action table for keybinding
+function oxwm.monitor.tag_direction(direction) end
+
+---Move the pointer to the middle of a monitor focused with oxwm.monitor.focus or focus_direction
+---@param enabled boolean Enable or disable pointer warping (default false)
+function oxwm.monitor.set_warp_pointer(enabled) end
+
+---@class oxwm.MonitorInfo
+---@field index integer Monitor index (0-based)
+---@field x integer
+---@field y integer
+---@field width integer
+---@field height integer
+---@field output string|nil RandR output name, e.g. "eDP-1"
+---@field model string|nil Monitor model from its EDID
+---@field tagset integer Bitmask of the visible tags
+---@field layout string Current layout name
+---@field focused integer|nil Window id of the selected client
+
+---List all monitors
+---@return oxwm.MonitorInfo[]
+function oxwm.monitor.list() end
+
+---Get a monitor's geometry
+---@param index? integer Monitor index (0-based, defaults to the focused monitor)
+---@return {x: integer, y: integer, width: integer, height: integer}|nil
+function oxwm.monitor.geometry(index) end
+
+---@class oxwm.MonitorProfile
+---@field layout string? Default layout for every tag ("tiling", "scrolling", ...)
+---@field mfact number? Default master factor (0.05 - 0.95)
+---@field show_bar boolean? Whether the bar starts out shown
+---@field top_bar boolean? false puts the bar at the bottom of the monitor
+---@field gaps {inner: integer|integer[]?, outer: integer|integer[]?}|false? Gaps as a size or {horizontal, vertical}; false for none
+
+---Give one monitor its own defaults instead of the global ones. Applied when
+---the monitor is first set up, including when it is plugged in later
+---@param monitor integer|string Monitor index, or an output or model name
+---@param profile oxwm.MonitorProfile
+function oxwm.monitor.configure(monitor, profile) end
+
 ---Layout management module
 ---@class oxwm.layout
 oxwm.layout = {}
@@ -213,6 +342,15 @@ oxwm.tag = {}
 ---@return table Action table for keybinding
 function oxwm.tag.view(index) end
 
+---Get the first tag shown on the focused monitor
+---@return integer|nil index Tag index (0-based)
+function oxwm.tag.current() end
+
+---Get the tags holding at least one client
+---@param monitor? integer Monitor index (0-based, defaults to the focused monitor)
+---@return integer[] indices Tag indices (0-based)
+function oxwm.tag.occupied(monitor) end
+
 ---View/switch to next tag
 ---@return table Action table for keybinding
 function oxwm.tag.view_next() end
@@ -248,6 +386,11 @@ function oxwm.tag.toggletag(index) end
 ---@param enabled boolean Enable or disable tag_back_and_forth
 function oxwm.tag.set_back_and_forth(enabled) end
 
+---Share one set of tags between all monitors, as in xmonad. A tag is shown on at most one monitor at a time.
+---"greedy" (or true) swaps the tag over from the monitor showing it; "focus" focuses that monitor instead.
+---@param mode boolean|"off"|"greedy"|"focus" Shared tag mode (default "off")
+function oxwm.tag.set_shared(mode) end
+
 ---Status bar configuration module
 ---@class oxwm.bar
 oxwm.bar = {}
@@ -327,6 +470,16 @@ function oxwm.bar.set_scheme_urgent(foreground, background, underline) end
 ---@param hide boolean Whether to hide vacant tags
 function oxwm.bar.set_hide_vacant_tags(hide) end
 
+---Show the icons (_NET_WM_ICON) of each tag's windows next to the tag label
+---@param enabled boolean Whether to draw window icons per tag
+function oxwm.bar.set_tag_icons(enabled) end
+
+---Choose which monitor's bar hosts the system tray (default: 0, the first monitor)
+---Pass false to disable the tray, or a list of indices and output names to try in order.
+---Takes effect the next time oxwm starts.
+---@param monitor integer|string|(integer|string)[]|boolean Monitor index or output name, or false to disable
+function oxwm.bar.set_systray(monitor) end
+
 ---Add an autostart command
 ---@param cmd string Command to run at startup
 function oxwm.autostart(cmd) end